@@ -1,61 +1,60 @@
 #![no_std]
 #![no_main]
-#![feature(alloc_error_handler)]
+#![feature(used_with_arg)]
 
 extern crate alloc;
-mod st;
 
-use st::*;
-
-use alloc::boxed::Box;
+use fomos_app::*;
 use imagine::pixel_formats::RGBA8888;
 
 const IMG: &[u8] = include_bytes!("./qr.png");
 
 pub struct Store {
     pix: imagine::image::Bitmap<RGBA8888>,
+    ///Size of the surface last drawn, it keeps its pixels between calls
+    drawn: (usize, usize),
 }
 
-#[no_mangle]
-pub extern "C" fn _start(ctx: &mut Context<Store>) -> i32 {
-    unsafe { ALLOCATOR.swap(ctx) };
-    unsafe { LOGGER.swap(ctx.log) };
-
-    // (ctx.log)("back start");
-    // x86_64::instructions::interrupts::int3();
-
-    let store: Box<Store>;
-    if let Some(ptr) = ctx.store.take() {
-        store = ptr;
-    } else {
-        st::log("store not found");
-        let pix = imagine::image::Bitmap::try_from_png_bytes(IMG);
-        if pix.is_none() {
-            st::log("No pix");
-            return -1;
-        }
-        store = Box::new(Store { pix: pix.unwrap() })
-    }
+manifest!(Manifest::new("app_background")
+    .version(env!("CARGO_PKG_VERSION"))
+    .caps(caps::FB | caps::ALLOC | caps::STORE)
+    .layer(LAYER_BACKGROUND)
+    .autostart(true));
 
-    let pix = &store.pix;
-
-    let dx = (libm::cosf((ctx.start_time as f32) * 0.001) * 2.0) as usize;
-
-    for y in 0..ctx.fb.h {
-        for x in 0..ctx.fb.w {
-            let lx = x as f32 / (ctx.fb.w) as f32;
-            let ly = y as f32 / (ctx.fb.h) as f32;
-            let px = (lx * pix.width as f32) as usize;
-            let py = (ly * pix.height as f32) as usize;
-            let v = pix.pixels[px + py * pix.width as usize];
-            let p = &mut ctx.fb.pixels[x + y * ctx.fb.w];
-            p.r = v.r;
-            p.g = v.g;
-            p.b = v.b;
-        }
+entry!(Store);
+
+impl App for Store {
+    fn init(_ctx: &mut Context<Self>) -> Self {
+        log("store not found");
+        // Embedded at build time, it cannot fail to decode at runtime
+        let pix = imagine::image::Bitmap::try_from_png_bytes(IMG).expect("No pix");
+        Store { pix, drawn: (0, 0) }
     }
 
-    *ctx.store = Some(store);
+    fn update(&mut self, ctx: &mut Context<Self>) -> Ret {
+        let pix = &self.pix;
+        let (w, h) = (ctx.fb.w, ctx.fb.h);
+        if self.drawn == (w, h) {
+            if ctx.has(caps::DAMAGE) {
+                // Nothing changed
+                (ctx.damage)(core::ptr::null(), 0);
+            }
+            return Ret::Sleep(100);
+        }
+        for y in 0..h {
+            let py = (y as f32 / h as f32 * pix.height as f32) as usize;
+            for (x, p) in ctx.fb.row_mut(y).iter_mut().enumerate() {
+                let px = (x as f32 / w as f32 * pix.width as f32) as usize;
+                let v = pix.pixels[px + py * pix.width as usize];
+                p.r = v.r;
+                p.g = v.g;
+                p.b = v.b;
+                p.a = 255;
+            }
+        }
+        self.drawn = (w, h);
 
-    return 0;
+        // Nothing animates here, only a new surface size needs a redraw
+        Ret::Sleep(100)
+    }
 }
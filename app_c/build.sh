@@ -1,2 +1,3 @@
 mkdir -p target
-gcc -nostdlib -static -fPIE -pie -o ./target/main main.c
\ No newline at end of file
+# fomos.h is generated: cd ../fomos_abi && cargo run --bin gen_c_header > ../app_c/fomos.h
+gcc -nostdlib -static-pie -fPIE -ffreestanding -fno-builtin -fno-tree-loop-distribute-patterns -O2 -o ./target/main main.c libc_lite.c
@@ -0,0 +1,436 @@
+// Generated by `cargo run --bin gen_c_header` in fomos_abi, do not edit.
+// C view of the types shared by the Fomos kernel and its apps.
+#ifndef FOMOS_H
+#define FOMOS_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#define CONTEXT_VERSION 13
+#define KEY_COUNT 1024
+#define HISTORY_SIZE 64
+#define CAP_LOG 0x1ull
+#define CAP_FB 0x2ull
+#define CAP_ALLOC 0x4ull
+#define CAP_STORE 0x8ull
+#define CAP_INPUT 0x10ull
+#define CAP_PROC 0x20ull
+#define CAP_MEM 0x40ull
+#define CAP_ABORT 0x80ull
+#define CAP_IPC 0x100ull
+#define CAP_SHM 0x200ull
+#define CAP_SERVICE 0x400ull
+#define CAP_CLIPBOARD 0x800ull
+#define CAP_SURFACE 0x1000ull
+#define CAP_DAMAGE 0x2000ull
+#define CAP_FRAME 0x4000ull
+#define CAP_DISPLAY 0x8000ull
+#define KEY_STATE_OFF 0
+#define KEY_STATE_OFF_FROM_ON 1
+#define KEY_STATE_OFF_TRANSIENT_ON 2
+#define KEY_STATE_ON_FROM_OFF 128
+#define KEY_STATE_ON_TRANSIENT_OFF 129
+#define KEY_STATE_ON 130
+#define PROC_READY 0
+#define PROC_SLEEPING 1
+#define PROC_WAITING_INPUT 2
+#define PROC_WAITING_MESSAGE 3
+#define PROC_EXITED 4
+#define PROC_CRASHED 5
+#define PROC_OVER_BUDGET 1
+#define EXIT_KILLED (-9)
+#define EXIT_FAULT (-11)
+#define EXIT_PANIC (-6)
+#define EXIT_TIMEOUT (-14)
+#define IPC_NO_TARGET (-1)
+#define IPC_TOO_LARGE (-2)
+#define IPC_FULL (-3)
+#define IPC_TOO_SMALL (-4)
+#define IPC_BAD_NAME (-5)
+#define IPC_NO_MEMORY (-6)
+#define SHM_EXISTS (-1)
+#define SHM_NOT_FOUND (-2)
+#define SHM_BAD_NAME (-3)
+#define SHM_BAD_SIZE (-4)
+#define SHM_NO_MEMORY (-5)
+#define SERVICE_GONE (-2147483647 - 1)
+#define SERVICE_FAULT (-2147483647)
+#define SERVICE_EXISTS (-2147483646)
+#define SERVICE_BAD_NAME (-2147483645)
+#define SERVICE_BAD_ENTRY (-2147483644)
+#define CLIP_BAD_ENTRY (-1)
+#define CLIP_TOO_LARGE (-2)
+#define CLIP_TOO_SMALL (-3)
+#define CLIP_NO_MEMORY (-4)
+#define SURFACE_BAD_SIZE (-1)
+#define SURFACE_BAD_LAYER (-2)
+#define SURFACE_NO_MEMORY (-3)
+#define CHANNEL_BIT 0x8000000000000000ull
+#define MAX_MESSAGE 4096
+#define INBOX_CAPACITY 64
+#define SHM_MAX_SIZE 67108864
+#define CLIP_EMPTY 0
+#define CLIP_TEXT 1
+#define CLIP_IMAGE 2
+#define CLIP_MAX_SIZE 16777216
+#define LAYER_NORMAL 0
+#define LAYER_BACKGROUND 1
+#define LAYER_OVERLAY 2
+#define SURFACE_MAX_SIDE 8192
+#define SURFACE_MAX_SCREENS 2
+#define DAMAGE_MAX_RECTS 64
+#define DISPLAY_MAX_SCREENS 16
+#define MANIFEST_MAGIC "FOMOSMF1"
+#define MANIFEST_SECTION ".fomos_manifest"
+#define MANIFEST_AUTOSTART 1
+
+#define RET_CONTINUE 0
+#define RET_EXIT(status) ((status) < 0 ? (status) : (0x1000000 | ((status) & 0xffffff)))
+#define RET_SLEEP(ms) (0x2000000 | ((ms) > 0xffffff ? 0xffffff : (ms)))
+#define RET_WAIT_INPUT 0x3000000
+#define RET_WAIT_MESSAGE 0x4000000
+
+// Entry point of a service, see `register_`
+typedef int32_t (*ServiceFn)(const void *arg, uint32_t arg_len, void *out, uint32_t out_cap);
+
+// One framebuffer pixel
+typedef struct
+{
+    uint8_t r;
+    uint8_t g;
+    uint8_t b;
+    uint8_t a;
+} RGBA;
+_Static_assert(sizeof(RGBA) == 4, "RGBA size");
+_Static_assert(offsetof(RGBA, r) == 0, "RGBA.r offset");
+_Static_assert(offsetof(RGBA, g) == 1, "RGBA.g offset");
+_Static_assert(offsetof(RGBA, b) == 2, "RGBA.b offset");
+_Static_assert(offsetof(RGBA, a) == 3, "RGBA.a offset");
+
+// Framebuffer shared with the app for the duration of one call
+typedef struct
+{
+    RGBA *pixels;
+    size_t len;
+    size_t w;
+    size_t h;
+} FB;
+_Static_assert(sizeof(FB) == 32, "FB size");
+_Static_assert(offsetof(FB, pixels) == 0, "FB.pixels offset");
+_Static_assert(offsetof(FB, w) == 16, "FB.w offset");
+_Static_assert(offsetof(FB, h) == 24, "FB.h offset");
+
+// Key press (trigger) or release, with its Linux evdev code
+typedef struct
+{
+    bool trigger;
+    size_t key;
+} InputEvent;
+_Static_assert(sizeof(InputEvent) == 16, "InputEvent size");
+_Static_assert(offsetof(InputEvent, trigger) == 0, "InputEvent.trigger offset");
+_Static_assert(offsetof(InputEvent, key) == 8, "InputEvent.key offset");
+
+// Mouse position, state of every key (KEY_STATE_*) and the last key events
+typedef struct
+{
+    size_t mouse_x;
+    size_t mouse_y;
+    uint8_t keys[KEY_COUNT];
+    size_t history_last_index;
+    InputEvent history_ring[HISTORY_SIZE];
+} Input;
+_Static_assert(sizeof(Input) == 2072, "Input size");
+_Static_assert(offsetof(Input, mouse_x) == 0, "Input.mouse_x offset");
+_Static_assert(offsetof(Input, mouse_y) == 8, "Input.mouse_y offset");
+_Static_assert(offsetof(Input, keys) == 16, "Input.keys offset");
+_Static_assert(offsetof(Input, history_last_index) == 1040, "Input.history_last_index offset");
+_Static_assert(offsetof(Input, history_ring) == 1048, "Input.history_ring offset");
+
+// One entry of the process table, see `ps`
+typedef struct
+{
+    uint64_t pid;
+    uint64_t start_time;
+    uint64_t calls;
+    int32_t exit_status;
+    uint8_t state;
+    uint8_t flags;
+    uint8_t _reserved[2];
+    char name[32];
+} ProcInfo;
+_Static_assert(sizeof(ProcInfo) == 64, "ProcInfo size");
+_Static_assert(offsetof(ProcInfo, pid) == 0, "ProcInfo.pid offset");
+_Static_assert(offsetof(ProcInfo, start_time) == 8, "ProcInfo.start_time offset");
+_Static_assert(offsetof(ProcInfo, calls) == 16, "ProcInfo.calls offset");
+_Static_assert(offsetof(ProcInfo, exit_status) == 24, "ProcInfo.exit_status offset");
+_Static_assert(offsetof(ProcInfo, state) == 28, "ProcInfo.state offset");
+_Static_assert(offsetof(ProcInfo, flags) == 29, "ProcInfo.flags offset");
+_Static_assert(offsetof(ProcInfo, _reserved) == 30, "ProcInfo._reserved offset");
+_Static_assert(offsetof(ProcInfo, name) == 32, "ProcInfo.name offset");
+
+// Memory accounting of one process, see `mem`
+typedef struct
+{
+    uint64_t live;
+    uint64_t peak;
+    uint64_t allocs;
+    uint64_t frees;
+    uint64_t failed;
+    uint64_t quota;
+} MemInfo;
+_Static_assert(sizeof(MemInfo) == 48, "MemInfo size");
+_Static_assert(offsetof(MemInfo, live) == 0, "MemInfo.live offset");
+_Static_assert(offsetof(MemInfo, peak) == 8, "MemInfo.peak offset");
+_Static_assert(offsetof(MemInfo, allocs) == 16, "MemInfo.allocs offset");
+_Static_assert(offsetof(MemInfo, frees) == 24, "MemInfo.frees offset");
+_Static_assert(offsetof(MemInfo, failed) == 32, "MemInfo.failed offset");
+_Static_assert(offsetof(MemInfo, quota) == 40, "MemInfo.quota offset");
+
+// Header of a message, see `recv`
+typedef struct
+{
+    uint64_t from;
+    uint64_t channel;
+    uint32_t len;
+    uint32_t _reserved;
+} Message;
+_Static_assert(sizeof(Message) == 24, "Message size");
+_Static_assert(offsetof(Message, from) == 0, "Message.from offset");
+_Static_assert(offsetof(Message, channel) == 8, "Message.channel offset");
+_Static_assert(offsetof(Message, len) == 16, "Message.len offset");
+_Static_assert(offsetof(Message, _reserved) == 20, "Message._reserved offset");
+
+// A shared memory region, see `shm_create`
+typedef struct
+{
+    uint64_t id;
+    uint8_t *ptr;
+    uint64_t size;
+} Shm;
+_Static_assert(sizeof(Shm) == 24, "Shm size");
+_Static_assert(offsetof(Shm, id) == 0, "Shm.id offset");
+_Static_assert(offsetof(Shm, ptr) == 8, "Shm.ptr offset");
+_Static_assert(offsetof(Shm, size) == 16, "Shm.size offset");
+
+// What the clipboard holds, see `clip_get`
+typedef struct
+{
+    uint32_t kind;
+    uint32_t w;
+    uint32_t h;
+    uint32_t _reserved;
+    uint64_t len;
+    uint64_t serial;
+} ClipInfo;
+_Static_assert(sizeof(ClipInfo) == 32, "ClipInfo size");
+_Static_assert(offsetof(ClipInfo, kind) == 0, "ClipInfo.kind offset");
+_Static_assert(offsetof(ClipInfo, w) == 4, "ClipInfo.w offset");
+_Static_assert(offsetof(ClipInfo, h) == 8, "ClipInfo.h offset");
+_Static_assert(offsetof(ClipInfo, _reserved) == 12, "ClipInfo._reserved offset");
+_Static_assert(offsetof(ClipInfo, len) == 16, "ClipInfo.len offset");
+_Static_assert(offsetof(ClipInfo, serial) == 24, "ClipInfo.serial offset");
+
+// Where and how the app surface (`fb`) is shown, see `surface_set`
+typedef struct
+{
+    int32_t x;
+    int32_t y;
+    uint32_t w;
+    uint32_t h;
+    uint32_t layer;
+    uint32_t z;
+    uint8_t opacity;
+    uint8_t _reserved[7];
+} SurfaceInfo;
+_Static_assert(sizeof(SurfaceInfo) == 32, "SurfaceInfo size");
+_Static_assert(offsetof(SurfaceInfo, x) == 0, "SurfaceInfo.x offset");
+_Static_assert(offsetof(SurfaceInfo, y) == 4, "SurfaceInfo.y offset");
+_Static_assert(offsetof(SurfaceInfo, w) == 8, "SurfaceInfo.w offset");
+_Static_assert(offsetof(SurfaceInfo, h) == 12, "SurfaceInfo.h offset");
+_Static_assert(offsetof(SurfaceInfo, layer) == 16, "SurfaceInfo.layer offset");
+_Static_assert(offsetof(SurfaceInfo, z) == 20, "SurfaceInfo.z offset");
+_Static_assert(offsetof(SurfaceInfo, opacity) == 24, "SurfaceInfo.opacity offset");
+_Static_assert(offsetof(SurfaceInfo, _reserved) == 25, "SurfaceInfo._reserved offset");
+
+// Pixels of the app surface changed by a call, see `damage`
+typedef struct
+{
+    uint32_t x;
+    uint32_t y;
+    uint32_t w;
+    uint32_t h;
+} Rect;
+_Static_assert(sizeof(Rect) == 16, "Rect size");
+_Static_assert(offsetof(Rect, x) == 0, "Rect.x offset");
+_Static_assert(offsetof(Rect, y) == 4, "Rect.y offset");
+_Static_assert(offsetof(Rect, w) == 8, "Rect.w offset");
+_Static_assert(offsetof(Rect, h) == 12, "Rect.h offset");
+
+// Frame clock as seen by the app, see `frame`
+typedef struct
+{
+    uint64_t number;
+    uint64_t presented_ms;
+    uint32_t delta_ms;
+    uint32_t refresh_hz;
+} Frame;
+_Static_assert(sizeof(Frame) == 24, "Frame size");
+_Static_assert(offsetof(Frame, number) == 0, "Frame.number offset");
+_Static_assert(offsetof(Frame, presented_ms) == 8, "Frame.presented_ms offset");
+_Static_assert(offsetof(Frame, delta_ms) == 16, "Frame.delta_ms offset");
+_Static_assert(offsetof(Frame, refresh_hz) == 20, "Frame.refresh_hz offset");
+
+// Screens the framebuffer is shown on, see `display`
+typedef struct
+{
+    uint64_t serial;
+    uint32_t w;
+    uint32_t h;
+    uint32_t count;
+    uint32_t _reserved;
+    Rect screens[16];
+} Display;
+_Static_assert(sizeof(Display) == 280, "Display size");
+_Static_assert(offsetof(Display, serial) == 0, "Display.serial offset");
+_Static_assert(offsetof(Display, w) == 8, "Display.w offset");
+_Static_assert(offsetof(Display, h) == 12, "Display.h offset");
+_Static_assert(offsetof(Display, count) == 16, "Display.count offset");
+_Static_assert(offsetof(Display, _reserved) == 20, "Display._reserved offset");
+_Static_assert(offsetof(Display, screens) == 24, "Display.screens offset");
+
+// Describes the app to the kernel, see `FOMOS_MANIFEST`
+typedef struct
+{
+    char magic[8];
+    uint32_t size;
+    uint8_t min_context_version;
+    uint8_t context_version;
+    uint8_t _reserved[2];
+    uint32_t flags;
+    uint32_t window_w;
+    uint32_t window_h;
+    uint32_t layer;
+    uint64_t caps;
+    char name[32];
+    char version[16];
+} Manifest;
+_Static_assert(sizeof(Manifest) == 88, "Manifest size");
+_Static_assert(offsetof(Manifest, magic) == 0, "Manifest.magic offset");
+_Static_assert(offsetof(Manifest, size) == 8, "Manifest.size offset");
+_Static_assert(offsetof(Manifest, min_context_version) == 12, "Manifest.min_context_version offset");
+_Static_assert(offsetof(Manifest, context_version) == 13, "Manifest.context_version offset");
+_Static_assert(offsetof(Manifest, _reserved) == 14, "Manifest._reserved offset");
+_Static_assert(offsetof(Manifest, flags) == 16, "Manifest.flags offset");
+_Static_assert(offsetof(Manifest, window_w) == 20, "Manifest.window_w offset");
+_Static_assert(offsetof(Manifest, window_h) == 24, "Manifest.window_h offset");
+_Static_assert(offsetof(Manifest, layer) == 28, "Manifest.layer offset");
+_Static_assert(offsetof(Manifest, caps) == 32, "Manifest.caps offset");
+_Static_assert(offsetof(Manifest, name) == 40, "Manifest.name offset");
+_Static_assert(offsetof(Manifest, version) == 72, "Manifest.version offset");
+
+// Argument of every call of `_start`. Fields past `size` were not given by the kernel, check `fomos_has` first
+typedef struct
+{
+    uint8_t version;
+    uint8_t _reserved[3];
+    uint32_t size;
+    uint64_t start_time;
+    void (*log)(const char *s, uint32_t len);
+    uint64_t pid;
+    FB fb;
+    void *(*calloc)(size_t size, size_t align);
+    void (*cdalloc)(void *ptr, size_t size, size_t align);
+    void **store;
+    const Input *input;
+    uint64_t caps;
+    int64_t (*spawn)(const char *name, uint32_t len);
+    int32_t (*kill)(uint64_t pid);
+    size_t (*ps)(ProcInfo *out, size_t cap);
+    int32_t (*mem)(uint64_t pid, MemInfo *out);
+    void (*abort)(const char *msg, uint32_t len);
+    int64_t (*open)(const char *name, uint32_t len);
+    int32_t (*send)(uint64_t to, const void *data, uint32_t len);
+    int32_t (*recv)(Message *msg, void *data, uint32_t cap);
+    int32_t (*shm_create)(const char *name, uint32_t len, size_t size, Shm *out);
+    int32_t (*shm_map)(const char *name, uint32_t len, Shm *out);
+    int32_t (*shm_release)(uint64_t id);
+    int32_t (*register_)(const char *name, uint32_t len, ServiceFn entry);
+    int64_t (*lookup)(const char *name, uint32_t len);
+    int32_t (*call)(uint64_t id, const void *arg, uint32_t arg_len, void *out, uint32_t out_cap);
+    int64_t (*clip_set)(const ClipInfo *info, const void *data);
+    int32_t (*clip_get)(ClipInfo *info, void *data, size_t cap);
+    uint64_t (*clip_serial)(void);
+    void (*surface_get)(SurfaceInfo *info);
+    int32_t (*surface_set)(const SurfaceInfo *info);
+    void (*damage)(const Rect *rects, uint32_t n);
+    const Frame *frame;
+    const Display *display;
+} Context;
+_Static_assert(sizeof(Context) == 280, "Context size");
+_Static_assert(offsetof(Context, version) == 0, "Context.version offset");
+_Static_assert(offsetof(Context, _reserved) == 1, "Context._reserved offset");
+_Static_assert(offsetof(Context, size) == 4, "Context.size offset");
+_Static_assert(offsetof(Context, start_time) == 8, "Context.start_time offset");
+_Static_assert(offsetof(Context, log) == 16, "Context.log offset");
+_Static_assert(offsetof(Context, pid) == 24, "Context.pid offset");
+_Static_assert(offsetof(Context, fb) == 32, "Context.fb offset");
+_Static_assert(offsetof(Context, calloc) == 64, "Context.calloc offset");
+_Static_assert(offsetof(Context, cdalloc) == 72, "Context.cdalloc offset");
+_Static_assert(offsetof(Context, store) == 80, "Context.store offset");
+_Static_assert(offsetof(Context, input) == 88, "Context.input offset");
+_Static_assert(offsetof(Context, caps) == 96, "Context.caps offset");
+_Static_assert(offsetof(Context, spawn) == 104, "Context.spawn offset");
+_Static_assert(offsetof(Context, kill) == 112, "Context.kill offset");
+_Static_assert(offsetof(Context, ps) == 120, "Context.ps offset");
+_Static_assert(offsetof(Context, mem) == 128, "Context.mem offset");
+_Static_assert(offsetof(Context, abort) == 136, "Context.abort offset");
+_Static_assert(offsetof(Context, open) == 144, "Context.open offset");
+_Static_assert(offsetof(Context, send) == 152, "Context.send offset");
+_Static_assert(offsetof(Context, recv) == 160, "Context.recv offset");
+_Static_assert(offsetof(Context, shm_create) == 168, "Context.shm_create offset");
+_Static_assert(offsetof(Context, shm_map) == 176, "Context.shm_map offset");
+_Static_assert(offsetof(Context, shm_release) == 184, "Context.shm_release offset");
+_Static_assert(offsetof(Context, register_) == 192, "Context.register_ offset");
+_Static_assert(offsetof(Context, lookup) == 200, "Context.lookup offset");
+_Static_assert(offsetof(Context, call) == 208, "Context.call offset");
+_Static_assert(offsetof(Context, clip_set) == 216, "Context.clip_set offset");
+_Static_assert(offsetof(Context, clip_get) == 224, "Context.clip_get offset");
+_Static_assert(offsetof(Context, clip_serial) == 232, "Context.clip_serial offset");
+_Static_assert(offsetof(Context, surface_get) == 240, "Context.surface_get offset");
+_Static_assert(offsetof(Context, surface_set) == 248, "Context.surface_set offset");
+_Static_assert(offsetof(Context, damage) == 256, "Context.damage offset");
+_Static_assert(offsetof(Context, frame) == 264, "Context.frame offset");
+_Static_assert(offsetof(Context, display) == 272, "Context.display offset");
+
+// Whether the kernel gave every field of the `cap` bits
+static inline bool fomos_has(const Context *ctx, uint64_t cap)
+{
+    uint64_t caps;
+    if (ctx->version >= 2 && ctx->size >= offsetof(Context, caps) + 8)
+        caps = ctx->caps;
+    else if (ctx->version == 0)
+        caps = 0x3;
+    else
+        caps = 0x1f;
+    return (caps & cap) == cap;
+}
+
+// Oldest Context version with the fields of every capability in `caps`
+#define FOMOS_CAPS_VERSION(caps) (((caps) & 0x8000ull) ? 13 : ((caps) & 0x4000ull) ? 12 : ((caps) & 0x2000ull) ? 11 : ((caps) & 0x1000ull) ? 10 : ((caps) & 0x800ull) ? 9 : ((caps) & 0x400ull) ? 8 : ((caps) & 0x200ull) ? 7 : ((caps) & 0x100ull) ? 6 : ((caps) & 0x80ull) ? 5 : ((caps) & 0x40ull) ? 4 : ((caps) & 0x20ull) ? 3 : ((caps) & 0x4ull) ? 1 : ((caps) & 0x8ull) ? 1 : ((caps) & 0x10ull) ? 1 : 0)
+
+// Embeds the Manifest of the app, like `manifest!` in Rust:
+// FOMOS_MANIFEST(CAP_LOG | CAP_ALLOC, .name = "app_c");
+// min_context_version defaults to what the caps need. Without one, the app gets
+// the oldest Context
+#define FOMOS_MANIFEST(caps_, ...)                            \
+    __attribute__((used, section(MANIFEST_SECTION)))          \
+    static const Manifest fomos_manifest = {                  \
+        .magic = MANIFEST_MAGIC,                              \
+        .size = sizeof(Manifest),                             \
+        .context_version = CONTEXT_VERSION,                   \
+        .caps = (caps_),                                      \
+        .min_context_version = FOMOS_CAPS_VERSION(caps_),     \
+        __VA_ARGS__}
+
+#endif
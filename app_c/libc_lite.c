@@ -0,0 +1,245 @@
+#include "libc_lite.h"
+
+// Every block starts with its size, `cdalloc` needs it back
+#define HEADER 16
+#define ALIGN 16
+
+static Context *current;
+
+void fomos_init(Context *ctx)
+{
+    current = ctx;
+}
+
+Context *fomos_ctx(void)
+{
+    return current;
+}
+
+void *malloc(size_t size)
+{
+    if (!current || !fomos_has(current, CAP_ALLOC) || size > SIZE_MAX - HEADER)
+        return NULL;
+    size_t *block = current->calloc(size + HEADER, ALIGN);
+    if (!block)
+        return NULL;
+    *block = size + HEADER;
+    return (char *)block + HEADER;
+}
+
+void *calloc(size_t count, size_t size)
+{
+    if (size && count > SIZE_MAX / size)
+        return NULL;
+    void *ptr = malloc(count * size);
+    if (ptr)
+        memset(ptr, 0, count * size);
+    return ptr;
+}
+
+void free(void *ptr)
+{
+    if (!ptr || !current || !fomos_has(current, CAP_ALLOC))
+        return;
+    size_t *block = (size_t *)((char *)ptr - HEADER);
+    current->cdalloc(block, *block, ALIGN);
+}
+
+void *realloc(void *ptr, size_t size)
+{
+    if (!ptr)
+        return malloc(size);
+    size_t old = *(size_t *)((char *)ptr - HEADER) - HEADER;
+    if (size <= old)
+        return ptr;
+    void *new = malloc(size);
+    if (!new)
+        return NULL;
+    memcpy(new, ptr, old);
+    free(ptr);
+    return new;
+}
+
+void *memcpy(void *dst, const void *src, size_t n)
+{
+    char *d = dst;
+    const char *s = src;
+    while (n--)
+        *d++ = *s++;
+    return dst;
+}
+
+void *memmove(void *dst, const void *src, size_t n)
+{
+    char *d = dst;
+    const char *s = src;
+    if (d < s)
+        return memcpy(dst, src, n);
+    while (n--)
+        d[n] = s[n];
+    return dst;
+}
+
+void *memset(void *dst, int c, size_t n)
+{
+    unsigned char *d = dst;
+    while (n--)
+        *d++ = (unsigned char)c;
+    return dst;
+}
+
+int memcmp(const void *a, const void *b, size_t n)
+{
+    const unsigned char *x = a, *y = b;
+    for (; n; n--, x++, y++)
+        if (*x != *y)
+            return *x - *y;
+    return 0;
+}
+
+size_t strlen(const char *s)
+{
+    size_t n = 0;
+    while (s[n])
+        n++;
+    return n;
+}
+
+int strcmp(const char *a, const char *b)
+{
+    while (*a && *a == *b)
+        a++, b++;
+    return (unsigned char)*a - (unsigned char)*b;
+}
+
+// Output of vsnprintf, counts what does not fit
+typedef struct
+{
+    char *buf;
+    size_t cap;
+    size_t len;
+} Out;
+
+static void put(Out *out, char c)
+{
+    if (out->len + 1 < out->cap)
+        out->buf[out->len] = c;
+    out->len++;
+}
+
+static void put_number(Out *out, unsigned long long n, unsigned base, bool upper, bool negative,
+                       int width, char pad)
+{
+    const char *digits = upper ? "0123456789ABCDEF" : "0123456789abcdef";
+    char tmp[24];
+    int len = 0;
+    do
+    {
+        tmp[len++] = digits[n % base];
+        n /= base;
+    } while (n);
+    if (negative && pad == '0')
+        put(out, '-');
+    for (int i = len + negative; i < width; i++)
+        put(out, pad);
+    if (negative && pad != '0')
+        put(out, '-');
+    while (len)
+        put(out, tmp[--len]);
+}
+
+int vsnprintf(char *buf, size_t cap, const char *fmt, va_list args)
+{
+    Out out = {buf, cap, 0};
+    for (; *fmt; fmt++)
+    {
+        if (*fmt != '%')
+        {
+            put(&out, *fmt);
+            continue;
+        }
+        fmt++;
+        char pad = ' ';
+        if (*fmt == '0')
+            pad = '0', fmt++;
+        int width = 0;
+        while (*fmt >= '0' && *fmt <= '9')
+            width = width * 10 + *fmt++ - '0';
+        int longs = 0;
+        while (*fmt == 'l' || *fmt == 'z')
+            longs += *fmt++ == 'z' ? 2 : 1;
+
+        switch (*fmt)
+        {
+        case 'd':
+        case 'i':
+        {
+            long long n = longs ? va_arg(args, long long) : va_arg(args, int);
+            unsigned long long abs = n < 0 ? 0ull - (unsigned long long)n : (unsigned long long)n;
+            put_number(&out, abs, 10, false, n < 0, width, pad);
+            break;
+        }
+        case 'u':
+        case 'x':
+        case 'X':
+        {
+            unsigned long long n =
+                longs ? va_arg(args, unsigned long long) : va_arg(args, unsigned int);
+            put_number(&out, n, *fmt == 'u' ? 10 : 16, *fmt == 'X', false, width, pad);
+            break;
+        }
+        case 'p':
+            put(&out, '0');
+            put(&out, 'x');
+            put_number(&out, (uintptr_t)va_arg(args, void *), 16, false, false, width, pad);
+            break;
+        case 's':
+        {
+            const char *s = va_arg(args, const char *);
+            if (!s)
+                s = "(null)";
+            for (int i = strlen(s); i < width; i++)
+                put(&out, ' ');
+            while (*s)
+                put(&out, *s++);
+            break;
+        }
+        case 'c':
+            put(&out, (char)va_arg(args, int));
+            break;
+        case '%':
+            put(&out, '%');
+            break;
+        case '\0':
+            fmt--;
+            break;
+        default:
+            put(&out, '%');
+            put(&out, *fmt);
+        }
+    }
+    if (cap)
+        buf[out.len < cap ? out.len : cap - 1] = '\0';
+    return (int)out.len;
+}
+
+int snprintf(char *buf, size_t cap, const char *fmt, ...)
+{
+    va_list args;
+    va_start(args, fmt);
+    int len = vsnprintf(buf, cap, fmt, args);
+    va_end(args);
+    return len;
+}
+
+int printf(const char *fmt, ...)
+{
+    char buf[512];
+    va_list args;
+    va_start(args, fmt);
+    int len = vsnprintf(buf, sizeof(buf), fmt, args);
+    va_end(args);
+    if (current)
+        current->log(buf, len < (int)sizeof(buf) ? len : (int)sizeof(buf) - 1);
+    return len;
+}
@@ -0,0 +1,33 @@
+// Tiny freestanding libc over the Context: memory, strings and printf to `log`.
+// Call `fomos_init(ctx)` first thing in `_start`, the Context changes from call to call.
+#ifndef LIBC_LITE_H
+#define LIBC_LITE_H
+
+#include <stdarg.h>
+#include "fomos.h"
+
+void fomos_init(Context *ctx);
+// Context of the current call
+Context *fomos_ctx(void);
+
+// Over `calloc`/`cdalloc`, accounted to the app like Rust allocations.
+// Null without CAP_ALLOC
+void *malloc(size_t size);
+void *calloc(size_t count, size_t size);
+void *realloc(void *ptr, size_t size);
+void free(void *ptr);
+
+void *memcpy(void *dst, const void *src, size_t n);
+void *memmove(void *dst, const void *src, size_t n);
+void *memset(void *dst, int c, size_t n);
+int memcmp(const void *a, const void *b, size_t n);
+size_t strlen(const char *s);
+int strcmp(const char *a, const char *b);
+
+// %d %i %u %x %X %p %s %c %%, with l, ll and z sizes, a width and 0 padding
+int vsnprintf(char *buf, size_t cap, const char *fmt, va_list args);
+int snprintf(char *buf, size_t cap, const char *fmt, ...);
+// One `log` line, cut at 512 bytes
+int printf(const char *fmt, ...);
+
+#endif
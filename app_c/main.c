@@ -1,16 +1,59 @@
+#include "fomos.h"
+#include "libc_lite.h"
+
+FOMOS_MANIFEST(CAP_LOG | CAP_FB | CAP_ALLOC | CAP_STORE | CAP_INPUT,
+               .name = "app_c", .version = "0.1.0");
+
 typedef struct
 {
-    unsigned char version;
-    unsigned long long start_time;
-    void (*log)(const char *, unsigned int);
-    unsigned long long pid;
+    uint64_t calls;
+    // Where the square was drawn, the surface keeps it
+    size_t last_x, last_y;
+} Store;
 
-} Context;
+// Paint an 8x8 square, clipped to the surface
+static void square(FB *fb, size_t x0, size_t y0, RGBA color)
+{
+    for (size_t y = y0; y < y0 + 8 && y < fb->h; y++)
+        for (size_t x = x0; x < x0 + 8 && x < fb->w; x++)
+            fb->pixels[y * fb->w + x] = color;
+}
 
 int _start(Context *ctx)
 {
-    char text[] = "[pid:_] Hello from C!";
-    text[5] = '0' + ctx->pid;
-    ctx->log(text, sizeof(text) - 1);
-    return 0;
-}
\ No newline at end of file
+    fomos_init(ctx);
+    if (!fomos_has(ctx, CAP_ALLOC | CAP_STORE | CAP_INPUT))
+    {
+        printf("[pid:%llu] Hello from C! (old kernel)", (unsigned long long)ctx->pid);
+        return RET_EXIT(0);
+    }
+
+    Store *store = *ctx->store;
+    if (!store)
+    {
+        store = calloc(1, sizeof(Store));
+        if (!store)
+            return RET_EXIT(1);
+        *ctx->store = store;
+        printf("[pid:%llu] Hello from C!", (unsigned long long)ctx->pid);
+    }
+    store->calls++;
+
+    // A small square following the mouse, transparent elsewhere
+    size_t mx = ctx->input->mouse_x, my = ctx->input->mouse_y;
+    square(&ctx->fb, store->last_x, store->last_y, (RGBA){0, 0, 0, 0});
+    square(&ctx->fb, mx, my, (RGBA){255, 128, 0, 255});
+    if (fomos_has(ctx, CAP_DAMAGE))
+    {
+        // Only the two squares changed
+        Rect rects[2] = {{store->last_x, store->last_y, 8, 8}, {mx, my, 8, 8}};
+        ctx->damage(rects, 2);
+    }
+    store->last_x = mx;
+    store->last_y = my;
+
+    if (store->calls % 600 == 0)
+        printf("[pid:%llu] %llu calls, mouse at %zu,%zu", (unsigned long long)ctx->pid,
+               (unsigned long long)store->calls, mx, my);
+    return RET_CONTINUE;
+}
@@ -0,0 +1,12 @@
+use std::path::PathBuf;
+
+fn main() {
+    // The font is the shared library lib_font, linked against instead of bundled
+    let dir = PathBuf::from(std::env::var_os("CARGO_MANIFEST_DIR").unwrap());
+    let lib = dir.join("../lib_font/target/x86_64/release");
+    println!("cargo:rustc-link-search=native={}", lib.display());
+    println!(
+        "cargo:rerun-if-changed={}",
+        lib.join("liblib_font.so").display()
+    );
+}
@@ -0,0 +1,40 @@
+//! Glyphs of `lib_font`, the shared library with the console font.
+
+///Largest glyph `lib_font` can hand out here
+const MAX_GLYPH: usize = 32 * 32;
+
+#[link(name = "lib_font")]
+extern "C" {
+    fn font_width() -> u32;
+    fn font_height() -> u32;
+    fn font_glyph(c: u32, out: *mut u8, cap: usize) -> i32;
+}
+
+///Intensity of each pixel of a char, row by row
+pub struct Glyph {
+    pub w: usize,
+    pub h: usize,
+    pixels: [u8; MAX_GLYPH],
+}
+
+impl Glyph {
+    pub fn rows(&self) -> core::slice::ChunksExact<'_, u8> {
+        self.pixels[..self.w * self.h].chunks_exact(self.w)
+    }
+}
+
+///Glyph of `c`, or of the fallback char if the font does not have it
+pub fn glyph(c: char) -> Glyph {
+    let mut glyph = Glyph {
+        w: unsafe { font_width() } as usize,
+        h: unsafe { font_height() } as usize,
+        pixels: [0; MAX_GLYPH],
+    };
+    if glyph.w * glyph.h > MAX_GLYPH
+        || unsafe { font_glyph(c as u32, glyph.pixels.as_mut_ptr(), MAX_GLYPH) } != 0
+    {
+        // Nothing drawn rather than out of bounds
+        glyph.h = 0;
+    }
+    glyph
+}
@@ -1,17 +1,14 @@
 #![no_std]
 #![no_main]
-#![feature(alloc_error_handler)]
+#![feature(used_with_arg)]
 #![feature(option_get_or_insert_default)]
 extern crate alloc;
+mod font;
 mod st;
 
 use st::*;
 
-use alloc::{boxed::Box, vec::Vec};
-
-use vek::{num_traits::Zero, Vec3};
-
-use vek::num_traits::Float;
+use alloc::vec::Vec;
 
 use taffy::prelude::*;
 
@@ -41,8 +38,8 @@ pub struct Store {
     y2: usize,
     resizing: [bool; 4],
     moving: Option<(usize, usize)>,
-    b1: Vec<RGBA>,
-    b2: Vec<RGBA>,
+    ///Window rectangle drawn by the last call, cleared when the window moves
+    drawn: (usize, usize, usize, usize),
     step: usize,
     taffy: Taffy,
     input_history_last_index: usize,
@@ -50,112 +47,16 @@ pub struct Store {
     active: bool,
     local: Local,
     shift: bool,
+    ctrl: bool,
     altg: bool,
     script_ctx: fomoscript::Ctx,
     mode: Mode,
 }
 
-fn put(pixel: &mut RGBA, v: Vec3<f32>) {
-    pixel.r = v.x as u8;
-    pixel.g = v.y as u8;
-    pixel.b = v.z as u8;
-}
-
-fn get(mut x: isize, mut y: isize, src: &[RGBA], wi: isize, hi: isize) -> Vec3<f32> {
-    if x < 0 {
-        x = 0;
-    } else if x >= wi - 1 {
-        x = wi - 1;
-    }
-    if y < 0 {
-        y = 0;
-    } else if y >= hi - 1 {
-        y = hi - 1;
-    }
-
-    let (x, y) = (x as usize, y as usize);
-    let index = x + y * wi as usize;
-    // st::log(&format!("{} {} {} {}", x, y, wi, hi));
-    let r = src[index];
-    Vec3::new(r.r as f32, r.g as f32, r.b as f32)
-}
-
-fn getf(mut x: f32, mut y: f32, src: &[RGBA], wi: isize, hi: isize) -> RGBA {
-    let x0 = x as isize;
-    let x1 = x0 + 1;
-    let y0 = y as isize;
-    let y1 = y0 + 1;
-
-    let fx = x - x0 as f32;
-    let fy = y - y0 as f32;
-
-    let a = get(x0, y0, src, wi, hi);
-    let b = get(x1, y0, src, wi, hi);
-    let c = get(x1, y1, src, wi, hi);
-    let d = get(x0, y1, src, wi, hi);
-
-    let ax = (1.0 - fx) * a + fx * b;
-    let ay = (1.0 - fx) * d + fx * c;
-
-    let avg = ax * (1.0 - fy) + ay * fy;
-    RGBA {
-        r: avg.x as u8,
-        g: avg.y as u8,
-        b: avg.z as u8,
-        a: 0,
-    }
-}
-
 fn pt_in_rect(px: usize, py: usize, x: usize, y: usize, x2: usize, y2: usize) -> bool {
     px >= x && px <= x2 && py >= y && py <= y2
 }
 
-fn kernel<const S: usize>(sigma: f32) -> [f32; S] {
-    let mut kernel = [0.0; S];
-    let mid = S / 2;
-
-    // calculate the Gaussian distribution
-    let variance = sigma.powi(2);
-    let factor = 1.0 / (2.0 * 3.141592 * variance);
-    let mut sum = 0.0;
-    for i in 0..S {
-        let x = (i as i32 - mid as i32) as f32;
-        let value = factor * (-x.powi(2) / (2.0 * variance)).exp();
-        kernel[i] = value;
-        sum += value;
-    }
-    sum *= 0.997;
-    // normalize the kernel
-    for i in 0..S {
-        kernel[i] /= sum;
-    }
-
-    kernel
-}
-fn blur(
-    fx: isize,
-    fy: isize,
-    src: &[RGBA],
-    dst: &mut Vec<RGBA>,
-    wi: isize,
-    hi: isize,
-    boxes: &[f32; KSIZE],
-) {
-    let mut avg: Vec3<f32> = Vec3::zero();
-
-    for y in 0..hi {
-        for x in 0..wi {
-            avg.set_zero();
-            for (k, &coef) in boxes.iter().enumerate() {
-                let dk = k as isize - KH;
-                let v = get(x + fx * dk, y + fy * dk, src, wi, hi);
-                avg += v * coef * 0.99;
-            }
-            put(&mut dst[(x + y * wi) as usize], avg);
-        }
-    }
-}
-
 fn hash(n: usize) -> usize {
     // integer hash copied from Hugo Elias
     let n = (n << 13) ^ n;
@@ -168,66 +69,258 @@ fn hash2(x: usize) -> usize {
     x = ((x >> 16) ^ x) * 0x45d9f3b;
     (x >> 16) ^ x
 }
-const KSIZE: usize = 5;
-const KH: isize = (KSIZE / 2) as isize;
-const DIV: isize = 4;
 const ORANGE: RGBA = RGBA {
     r: 255,
     g: 128,
     b: 0,
-    a: 0,
+    a: 255,
 };
 const GREY3: RGBA = RGBA {
     r: 150,
     g: 150,
     b: 150,
-    a: 0,
+    a: 255,
 };
 const GREY2: RGBA = RGBA {
     r: 80,
     g: 80,
     b: 80,
+    a: 255,
+};
+///Behind the text, lets the surfaces below show through
+const BACKDROP: RGBA = RGBA {
+    r: 20,
+    g: 20,
+    b: 25,
+    a: 210,
+};
+const CLEAR: RGBA = RGBA {
+    r: 0,
+    g: 0,
+    b: 0,
     a: 0,
 };
 const GREY: RGBA = RGBA {
     r: 50,
     g: 50,
     b: 50,
-    a: 0,
+    a: 255,
 };
-#[no_mangle]
-pub extern "C" fn _start(ctx: &mut Context<Store>) -> i32 {
-    unsafe { ALLOCATOR.swap(ctx) };
-    unsafe { LOGGER.swap(ctx.log) };
-
-    let hi = ctx.fb.h as isize / DIV;
-    let wi = ctx.fb.w as isize / DIV;
-
-    let store = ctx.store.get_or_insert_with(|| {
-        Box::new({
-            Store {
-                x: 100 + ctx.pid as usize * 100,
-                y: 100 + ctx.pid as usize * 100,
-                x2: 1000 + ctx.pid as usize * 100,
-                y2: 700 + ctx.pid as usize * 100,
-                resizing: [false; 4],
-                b1: Vec::with_capacity((wi * hi) as usize),
-                b2: Vec::with_capacity((wi * hi) as usize),
-
-                step: 0,
-                moving: None,
-                taffy: Taffy::new(),
-                input_history_last_index: ctx.input.history_last_index,
-                console_history: ConsoleHistory::new(),
-                active: false,
-                local: Local::En,
-                shift: false,
-                altg: false,
-                script_ctx: fomoscript::Ctx::new(),
-                mode: Mode::Shell,
+manifest!(Manifest::new("app_console")
+    .version(env!("CARGO_PKG_VERSION"))
+    .caps(
+        caps::LOG
+            | caps::FB
+            | caps::ALLOC
+            | caps::STORE
+            | caps::INPUT
+            | caps::PROC
+            | caps::MEM
+            | caps::IPC
+            | caps::CLIPBOARD
+    )
+    .window(900, 600)
+    .autostart(true));
+
+///Process table, one line per process
+fn ps(ps_fn: PsFn) -> alloc::string::String {
+    use core::fmt::Write;
+    let mut procs = [ProcInfo::new(0, ""); 32];
+    let count = ps_fn(procs.as_mut_ptr(), procs.len());
+    let mut out = alloc::format!("pid  name             state    calls  started");
+    for p in procs.iter().take(count) {
+        let state = match p.state {
+            PROC_READY => "ready",
+            PROC_SLEEPING => "sleep",
+            PROC_WAITING_INPUT => "input",
+            PROC_WAITING_MESSAGE => "message",
+            PROC_EXITED => "exited",
+            PROC_CRASHED => "crashed",
+            _ => "?",
+        };
+        let _ = write!(
+            out,
+            "\n{:<4} {:<16} {:<8} {:<6} {} ms",
+            p.pid,
+            p.name(),
+            state,
+            p.calls,
+            p.start_time
+        );
+        if !p.is_alive() {
+            let _ = write!(out, " (status {})", p.exit_status);
+        }
+        if p.flags & PROC_OVER_BUDGET != 0 {
+            let _ = write!(out, " slow");
+        }
+    }
+    if count > procs.len() {
+        let _ = write!(out, "\n.. {} more", count - procs.len());
+    }
+    out
+}
+
+///Memory used by every running process
+fn mem(ps_fn: PsFn, mem_fn: MemFn) -> alloc::string::String {
+    use core::fmt::Write;
+    let mut procs = [ProcInfo::new(0, ""); 32];
+    let count = ps_fn(procs.as_mut_ptr(), procs.len()).min(procs.len());
+    let mut out = alloc::format!("pid  name             live     peak     allocs   quota");
+    for p in procs.iter().take(count) {
+        let mut info = MemInfo::default();
+        if mem_fn(p.pid, &mut info) < 0 {
+            continue;
+        }
+        let quota = if info.quota == 0 {
+            alloc::format!("-")
+        } else {
+            alloc::format!("{}K", info.quota / 1024)
+        };
+        let _ = write!(
+            out,
+            "\n{:<4} {:<16} {:<8} {:<8} {:<8} {}",
+            p.pid,
+            p.name(),
+            alloc::format!("{}K", info.live / 1024),
+            alloc::format!("{}K", info.peak / 1024),
+            info.allocs,
+            quota
+        );
+        if info.failed > 0 {
+            let _ = write!(out, " ({} failed)", info.failed);
+        }
+    }
+    out
+}
+
+///Size of the desktop and the part each screen shows
+fn screens(display: &Display) -> alloc::string::String {
+    use core::fmt::Write;
+    let mut out = alloc::format!("desktop {}x{}", display.w, display.h);
+    for (i, s) in display.screens().iter().enumerate() {
+        let _ = write!(out, "\n{}: {}x{} at {}, {}", i, s.w, s.h, s.x, s.y);
+    }
+    out
+}
+
+///Drain the inbox, one line per message
+fn inbox(recv: RecvFn) -> Vec<alloc::string::String> {
+    let mut lines = Vec::new();
+    let mut data = [0u8; MAX_MESSAGE];
+    let mut msg = Message::default();
+    while recv(&mut msg, data.as_mut_ptr(), data.len() as u32) > 0 {
+        let text = core::str::from_utf8(&data[..msg.len as usize]).unwrap_or("(binary)");
+        lines.push(if msg.channel == 0 {
+            alloc::format!("[pid {}] {}", msg.from, text)
+        } else {
+            alloc::format!("[pid {} on {:#x}] {}", msg.from, msg.channel, text)
+        });
+    }
+    lines
+}
+
+///`send <pid or channel> <text>`, a channel is joined to send to it
+fn send(open: OpenFn, send: SendFn, args: &str) -> alloc::string::String {
+    let Some((to, text)) = args.trim().split_once(' ') else {
+        return alloc::format!("usage: send <pid or channel> <text>");
+    };
+    let to = match to.parse::<u64>() {
+        Ok(pid) => pid,
+        Err(_) => {
+            let id = open(to.as_ptr(), to.len() as u32);
+            if id < 0 {
+                return alloc::format!("bad channel name {}", to);
             }
-        })
-    });
+            id as u64
+        }
+    };
+    match send(to, text.as_ptr(), text.len() as u32) {
+        IPC_NO_TARGET => alloc::format!("no process or channel {}", to),
+        IPC_FULL => alloc::format!("inbox full"),
+        IPC_TOO_LARGE => alloc::format!("message too large"),
+        IPC_NO_MEMORY => alloc::format!("out of memory"),
+        n => alloc::format!("sent to {} inbox(es)", n),
+    }
+}
+
+///Ctrl+C: the line being typed, or the last output if it is empty
+fn copy(clip_set: ClipSetFn, history: &ConsoleHistory) {
+    let line = history
+        .atoms
+        .last()
+        .filter(|atom| atom.is_user)
+        .map(|atom| atom.text.split_once('>').map_or("", |(_, line)| line))
+        .unwrap_or("");
+    let text = if line.is_empty() {
+        history
+            .atoms
+            .iter()
+            .rev()
+            .find(|atom| !atom.is_user)
+            .map_or("", |atom| atom.text.as_str())
+    } else {
+        line
+    };
+    let info = ClipInfo {
+        kind: CLIP_TEXT,
+        len: text.len() as u64,
+        ..Default::default()
+    };
+    clip_set(&info, text.as_ptr());
+}
+
+///Ctrl+V: the clipboard, if it holds text
+fn paste(clip_get: ClipGetFn) -> Option<alloc::string::String> {
+    let mut info = ClipInfo::default();
+    if clip_get(&mut info, core::ptr::null_mut(), 0) == 0 || info.kind != CLIP_TEXT {
+        return None;
+    }
+    let mut data = alloc::vec![0u8; info.len as usize];
+    // The clipboard may have changed in between, then the new entry is too large or not text
+    if clip_get(&mut info, data.as_mut_ptr(), data.len()) != 1 || info.kind != CLIP_TEXT {
+        return None;
+    }
+    alloc::string::String::from_utf8(data).ok()
+}
+
+entry!(Store);
+
+impl App for Store {
+    fn init(ctx: &mut Context<Self>) -> Self {
+        Store {
+            x: 100 + ctx.pid as usize * 100,
+            y: 100 + ctx.pid as usize * 100,
+            x2: 1000 + ctx.pid as usize * 100,
+            y2: 700 + ctx.pid as usize * 100,
+            resizing: [false; 4],
+            drawn: (0, 0, 0, 0),
+
+            step: 0,
+            moving: None,
+            taffy: Taffy::new(),
+            input_history_last_index: ctx.input.history_last_index,
+            console_history: ConsoleHistory::new(),
+            active: false,
+            local: Local::En,
+            shift: false,
+            ctrl: false,
+            altg: false,
+            script_ctx: fomoscript::Ctx::new(),
+            mode: Mode::Shell,
+        }
+    }
+
+    fn update(&mut self, ctx: &mut Context<Self>) -> Ret {
+        update(self, ctx)
+    }
+}
+
+fn update(store: &mut Store, ctx: &mut Context<Store>) -> Ret {
+    let has_proc = ctx.has(caps::PROC);
+    let has_mem = ctx.has(caps::PROC | caps::MEM);
+    let has_ipc = ctx.has(caps::IPC);
+    let has_clip = ctx.has(caps::CLIPBOARD);
+    let has_display = ctx.has(caps::DISPLAY);
 
     if store.step == 0 {
         store.console_history.atoms.push(Atom {
@@ -240,93 +333,53 @@ pub extern "C" fn _start(ctx: &mut Context<Store>) -> i32 {
         });
     }
 
-    store.step += 1;
-    let blured1 = &mut store.b1;
-    let blured2 = &mut store.b2;
-
-    let (src, dst) = if store.step % 2 == 0 {
-        (blured1, blured2)
-    } else {
-        (blured2, blured1)
-    };
-
-    if src.len() == 0 {
-        for y in 0..hi {
-            for x in 0..wi {
-                let v = ctx.fb.pixels[((x * DIV) + (y * DIV) * (wi * DIV)) as usize];
-                src.push(v);
-                dst.push(v);
-            }
-        }
-    } else {
-        for y in 0..hi {
-            for x in 0..wi {
-                if true {
-                    //|| (hash2((x + y * wi) as usize) + store.step) % 2 == 0
-                    let v = ctx.fb.pixels[((x * DIV) + (y * DIV) * (wi * DIV)) as usize];
-                    let s = src[(x + y * wi) as usize];
-                    let a = 0.03;
-                    let b = 1.0 - a;
-                    let r = (v.r as f32 * a + s.r as f32 * b) as u8;
-                    let g = (v.g as f32 * a + s.g as f32 * b) as u8;
-                    let b = (v.b as f32 * a + s.b as f32 * b) as u8;
-
-                    src[(x + y * wi) as usize] = RGBA { r, g, b, a: 0 };
-                }
-            }
+    if has_ipc {
+        let at = store.console_history.atoms.len().saturating_sub(1);
+        for text in inbox(ctx.recv).into_iter().rev() {
+            store.console_history.atoms.insert(
+                at,
+                Atom {
+                    is_user: false,
+                    text,
+                },
+            );
         }
     }
 
-    let boxes = kernel::<KSIZE>(1.0);
-
-    let fx = if store.step % 2 == 0 { 1 } else { 0 };
-    blur(fx, 1 - fx, src, dst, wi, hi, &boxes);
-
-    // blur(1, 0, &blured2, &mut blured1);
-    // for y in 0..hi {
-    //     for x in 0..wi {
-    //         avg.set_zero();
-    //         for (k, coef) in boxes.iter().enumerate() {
-    //             let coef = boxes[k as usize];
-    //             let v = get(x + (k as isize - KH), y);
-    //             avg += v * coef;
-    //         }
-    //         put(&mut blured1[(x + y * wi) as usize], avg);
-    //     }
-    // }
+    store.step += 1;
 
-    if ctx.input.keys[272] < 128 {
+    if !ctx.input.button(MouseButton::Left).is_down() {
         store.resizing = [false; 4];
         store.moving = None;
     }
 
-    if ctx.input.keys[272] == 128 {
-        if (store.x as isize - ctx.input.mx as isize).abs() < 10 {
+    if ctx.input.button(MouseButton::Left) == KeyState::OnFromOff {
+        if (store.x as isize - ctx.input.mouse_x as isize).abs() < 10 {
             store.resizing[3] = true;
         }
-        if (store.x2 as isize - ctx.input.mx as isize).abs() < 10 {
+        if (store.x2 as isize - ctx.input.mouse_x as isize).abs() < 10 {
             store.resizing[1] = true;
         }
-        if (store.y as isize - ctx.input.my as isize).abs() < 10
-            && store.x <= ctx.input.mx
-            && store.x2 >= ctx.input.mx
+        if (store.y as isize - ctx.input.mouse_y as isize).abs() < 10
+            && store.x <= ctx.input.mouse_x
+            && store.x2 >= ctx.input.mouse_x
         {
             store.resizing[0] = true;
         }
-        if (store.y2 as isize - ctx.input.my as isize).abs() < 10
-            && store.x <= ctx.input.mx
-            && store.x2 >= ctx.input.mx
+        if (store.y2 as isize - ctx.input.mouse_y as isize).abs() < 10
+            && store.x <= ctx.input.mouse_x
+            && store.x2 >= ctx.input.mouse_x
         {
             store.resizing[2] = true;
         }
 
         if !store.resizing.iter().any(|&e| e) {
-            if store.x <= ctx.input.mx
-                && store.x2 >= ctx.input.mx
-                && store.y <= ctx.input.my
-                && store.y2 >= ctx.input.my
+            if store.x <= ctx.input.mouse_x
+                && store.x2 >= ctx.input.mouse_x
+                && store.y <= ctx.input.mouse_y
+                && store.y2 >= ctx.input.mouse_y
             {
-                store.moving = Some((ctx.input.mx, ctx.input.my));
+                store.moving = Some((ctx.input.mouse_x, ctx.input.mouse_y));
                 store.active = true;
             } else {
                 store.active = false;
@@ -335,25 +388,25 @@ pub extern "C" fn _start(ctx: &mut Context<Store>) -> i32 {
     }
 
     if let Some((x0, y0)) = store.moving.as_mut() {
-        let dx = ctx.input.mx as isize - *x0 as isize;
-        let dy = ctx.input.my as isize - *y0 as isize;
+        let dx = ctx.input.mouse_x as isize - *x0 as isize;
+        let dy = ctx.input.mouse_y as isize - *y0 as isize;
 
         store.x = (store.x as isize + dx) as usize;
         store.x2 = (store.x2 as isize + dx) as usize;
         store.y = (store.y as isize + dy) as usize;
         store.y2 = (store.y2 as isize + dy) as usize;
 
-        *x0 = ctx.input.mx;
-        *y0 = ctx.input.my;
+        *x0 = ctx.input.mouse_x;
+        *y0 = ctx.input.mouse_y;
     }
 
     for (index, &r) in store.resizing.iter().enumerate() {
         if r {
             match index {
-                0 => store.y = ctx.input.my,
-                1 => store.x2 = ctx.input.mx,
-                2 => store.y2 = ctx.input.my,
-                3 => store.x = ctx.input.mx,
+                0 => store.y = ctx.input.mouse_y,
+                1 => store.x2 = ctx.input.mouse_x,
+                2 => store.y2 = ctx.input.mouse_y,
+                3 => store.x = ctx.input.mouse_x,
                 _ => {}
             }
         }
@@ -461,6 +514,29 @@ pub extern "C" fn _start(ctx: &mut Context<Store>) -> i32 {
     //     st::log(&format!("{}: {:?}", i, (cx, cy, cx2, cy2)));
     // }
 
+    // The surface keeps what was drawn, clear where the window was
+    let rect = (store.x, store.y, store.x2, store.y2);
+    let inclusive = |(x, y, x2, y2): (usize, usize, usize, usize)| {
+        let (w, h) = ((x2 + 1).saturating_sub(x), (y2 + 1).saturating_sub(y));
+        Rect::new(x as u32, y as u32, w as u32, h as u32)
+    };
+    // Only the window changed, and where it was
+    if ctx.has(caps::DAMAGE) {
+        let rects = [inclusive(store.drawn), inclusive(rect)];
+        (ctx.damage)(rects.as_ptr(), rects.len() as u32);
+    }
+    if store.drawn != rect {
+        let old = inclusive(store.drawn);
+        ctx.fb.fill_rect(
+            old.x as usize,
+            old.y as usize,
+            old.w as usize,
+            old.h as usize,
+            CLEAR,
+        );
+        store.drawn = rect;
+    }
+
     for y in store.y..=store.y2 {
         for x in store.x..=store.x2 {
             let p = &mut ctx.fb.pixels[x + y * ctx.fb.w];
@@ -507,27 +583,21 @@ pub extern "C" fn _start(ctx: &mut Context<Store>) -> i32 {
             }
 
             if !drawn {
-                let div = DIV as usize;
-                // *p = getf(x as f32 / div as f32, y as f32 / div as f32, dst, wi, hi);
-                *p = dst[x / div + (y / div) * (ctx.fb.w / div)];
+                *p = BACKDROP;
             }
         }
     }
 
     //Write window title
     {
-        use noto_sans_mono_bitmap::{get_raster, get_raster_width, FontWeight, RasterHeight};
         let s = alloc::format!("app_console [{}]", ctx.pid);
         let mut cursor_x = 0;
         let padding = 2;
-        let weight = FontWeight::Regular;
         for c in s.chars() {
-            let width = get_raster_width(weight, RasterHeight::Size16);
+            let glyph = font::glyph(c);
+            let width = glyph.w;
 
-            let char_raster =
-                get_raster(c, weight, RasterHeight::Size16).expect("unsupported char");
-
-            for (row_i, row) in char_raster.raster().iter().enumerate() {
+            for (row_i, row) in glyph.rows().enumerate() {
                 for (col_i, pixel) in row.iter().enumerate() {
                     let x = store.x + col_i + padding + cursor_x;
                     let y = store.y + row_i + padding + 0;
@@ -551,12 +621,9 @@ pub extern "C" fn _start(ctx: &mut Context<Store>) -> i32 {
     }
     //Write text buffer
     {
-        use noto_sans_mono_bitmap::{get_raster, get_raster_width, FontWeight, RasterHeight};
-
         let mut cursor_x = 0;
         let mut cursor_y = 20;
         let padding = 2;
-        let weight = FontWeight::Regular;
 
         for Atom { is_user, text } in store.console_history.atoms.iter() {
             for c in text.chars() {
@@ -565,12 +632,10 @@ pub extern "C" fn _start(ctx: &mut Context<Store>) -> i32 {
                     cursor_x = 0;
                     continue;
                 }
-                let width = get_raster_width(weight, RasterHeight::Size16);
+                let glyph = font::glyph(c);
+                let width = glyph.w;
 
-                let char_raster =
-                    get_raster(c, weight, RasterHeight::Size16).expect("unsupported char");
-
-                for (row_i, row) in char_raster.raster().iter().enumerate() {
+                for (row_i, row) in glyph.rows().enumerate() {
                     for (col_i, pixel) in row.iter().enumerate() {
                         let x = store.x + col_i + padding + cursor_x;
                         let y = store.y + row_i + padding + cursor_y;
@@ -601,8 +666,12 @@ pub extern "C" fn _start(ctx: &mut Context<Store>) -> i32 {
     }
 
     if store.active {
-        'new_inputs: for i in (store.input_history_last_index + 1)..=ctx.input.history_last_index {
-            let InputEvent { trigger, key } = ctx.input.history_ring[i % HISTORY_SIZE];
+        'new_inputs: for InputEvent { trigger, key } in
+            ctx.input.events_since(store.input_history_last_index)
+        {
+            let Some(key) = Key::from_code(key) else {
+                continue;
+            };
 
             match key {
                 Key::KeyLeftShift => {
@@ -611,9 +680,29 @@ pub extern "C" fn _start(ctx: &mut Context<Store>) -> i32 {
                 Key::KeyRightAlt => {
                     store.altg = trigger;
                 }
+                Key::KeyLeftCtrl | Key::KeyRightCtrl => {
+                    store.ctrl = trigger;
+                }
                 _ => {}
             }
 
+            if trigger && store.ctrl && has_clip {
+                match key {
+                    Key::KeyC => copy(ctx.clip_set, &store.console_history),
+                    Key::KeyV => {
+                        if let (Some(pasted), Some(atom)) =
+                            (paste(ctx.clip_get), store.console_history.atoms.last_mut())
+                        {
+                            if atom.is_user {
+                                atom.text.push_str(&pasted);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+                continue 'new_inputs;
+            }
+
             if (trigger) {
                 let last = store.console_history.atoms.last_mut();
                 match last {
@@ -684,11 +773,30 @@ pub extern "C" fn _start(ctx: &mut Context<Store>) -> i32 {
                                                 text: alloc::format!("ok"),
                                             });
                                         }
+                                        ">ps" if has_proc => {
+                                            store.console_history.atoms.push(Atom {
+                                                is_user: false,
+                                                text: ps(ctx.ps),
+                                            });
+                                        }
+                                        ">mem" if has_mem => {
+                                            store.console_history.atoms.push(Atom {
+                                                is_user: false,
+                                                text: mem(ctx.ps, ctx.mem),
+                                            });
+                                        }
+                                        ">screens" if has_display => {
+                                            store.console_history.atoms.push(Atom {
+                                                is_user: false,
+                                                text: screens(ctx.display),
+                                            });
+                                        }
+                                        ">exit" => {
+                                            return Ret::Exit(0);
+                                        }
                                         ">reset" => {
-                                            drop(store);
-                                            let old = ctx.store.take().unwrap();
-                                            drop(old);
-                                            return 0;
+                                            *store = Store::init(ctx);
+                                            return Ret::Continue;
                                         }
                                         ">help" => {
                                             store.console_history.atoms.push(Atom {
@@ -698,10 +806,19 @@ pub extern "C" fn _start(ctx: &mut Context<Store>) -> i32 {
     - pid       Display the app pid
     - time      Display the kernel time
     - reset     Clear the app memory
+    - exit      Close the console
+    - ps        List the processes
+    - run ..    Start an app by name, options after it (trace=yes)
+    - kill ..   Stop a process by pid
+    - mem       Memory used by each process
+    - screens   Desktop size and screens
+    - open ..   Join a message channel by name
+    - send ..   Send text to a pid or a channel
     - lang ..   Set key locale (en,fr)
     - eval ..   Eval fomoscript
     - repl      launch fomoscript REPL
     - help      You are here
+Ctrl+C copies the line (or the last output), Ctrl+V pastes
     "
                                                 ),
                                             });
@@ -761,6 +878,62 @@ pub extern "C" fn _start(ctx: &mut Context<Store>) -> i32 {
                                                     is_user: false,
                                                     text: alloc::format!("eval: {:?}", res),
                                                 });
+                                            } else if let Some(name) =
+                                                text.strip_prefix(">run ").filter(|_| has_proc)
+                                            {
+                                                let name = name.trim();
+                                                let pid =
+                                                    (ctx.spawn)(name.as_ptr(), name.len() as u32);
+                                                let text = if pid < 0 {
+                                                    alloc::format!("cannot run {}", name)
+                                                } else {
+                                                    alloc::format!(
+                                                        "{} started as pid {}",
+                                                        name,
+                                                        pid
+                                                    )
+                                                };
+                                                store.console_history.atoms.push(Atom {
+                                                    is_user: false,
+                                                    text,
+                                                });
+                                            } else if let Some(name) =
+                                                text.strip_prefix(">open ").filter(|_| has_ipc)
+                                            {
+                                                let name = name.trim();
+                                                let id =
+                                                    (ctx.open)(name.as_ptr(), name.len() as u32);
+                                                let text = if id < 0 {
+                                                    alloc::format!("bad channel name {}", name)
+                                                } else {
+                                                    alloc::format!("joined {} ({:#x})", name, id)
+                                                };
+                                                store.console_history.atoms.push(Atom {
+                                                    is_user: false,
+                                                    text,
+                                                });
+                                            } else if let Some(args) =
+                                                text.strip_prefix(">send ").filter(|_| has_ipc)
+                                            {
+                                                let text = send(ctx.open, ctx.send, args);
+                                                store.console_history.atoms.push(Atom {
+                                                    is_user: false,
+                                                    text,
+                                                });
+                                            } else if let Some(pid) =
+                                                text.strip_prefix(">kill ").filter(|_| has_proc)
+                                            {
+                                                let text = match pid.trim().parse::<u64>() {
+                                                    Ok(pid) if (ctx.kill)(pid) == 0 => {
+                                                        alloc::format!("killed {}", pid)
+                                                    }
+                                                    Ok(pid) => alloc::format!("no process {}", pid),
+                                                    Err(_) => alloc::format!("usage: kill <pid>"),
+                                                };
+                                                store.console_history.atoms.push(Atom {
+                                                    is_user: false,
+                                                    text,
+                                                });
                                             } else {
                                                 store.console_history.atoms.push(Atom {
                                                     is_user: false,
@@ -792,7 +965,10 @@ pub extern "C" fn _start(ctx: &mut Context<Store>) -> i32 {
                             _ => {}
                         }
 
-                        if let Some(c) = key.char(store.local, store.shift, store.altg) {
+                        if let Some(c) = key
+                            .char(store.local, store.shift, store.altg)
+                            .filter(|_| !store.ctrl)
+                        {
                             *text = alloc::format!("{}{}", text, c)
                         } else {
                             match key {
@@ -811,5 +987,5 @@ pub extern "C" fn _start(ctx: &mut Context<Store>) -> i32 {
     }
     store.input_history_last_index = ctx.input.history_last_index;
 
-    return 0;
+    Ret::Continue
 }
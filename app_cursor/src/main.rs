@@ -1,11 +1,9 @@
 #![no_std]
 #![no_main]
-#![feature(alloc_error_handler)]
+#![feature(used_with_arg)]
 
 extern crate alloc;
-mod st;
-use alloc::boxed::Box;
-use st::*;
+use fomos_app::*;
 use vek::Vec2;
 
 pub struct Store {
@@ -15,78 +13,102 @@ pub struct Store {
     ym: usize,
 }
 
-#[no_mangle]
-pub extern "C" fn _start(ctx: &mut Context<Store>) -> i32 {
-    unsafe { ALLOCATOR.swap(ctx) };
-    unsafe { LOGGER.swap(ctx.log) };
-
-    // (ctx.log)("back start");
-    // x86_64::instructions::interrupts::int3();
-
-    let mut store: Box<Store>;
-    if let Some(ptr) = ctx.store.take() {
-        // st::log("store found");
-        store = ptr;
-    } else {
-        st::log("store not found");
-
-        store = Box::new(Store {
-            x: ctx.input.mx,
-            y: ctx.input.my,
-            xm: ctx.input.mx,
-            ym: ctx.input.my,
-        })
-    }
+manifest!(Manifest::new("app_cursor")
+    .version(env!("CARGO_PKG_VERSION"))
+    .caps(caps::FB | caps::ALLOC | caps::STORE | caps::INPUT)
+    .layer(LAYER_OVERLAY)
+    .autostart(true));
 
-    let am = Vec2::new(store.xm as f32 + 0.01, store.ym as f32);
-    let a = Vec2::new(store.x as f32, store.y as f32);
-    let b = Vec2::new(ctx.input.mx as f32 + 0.01, ctx.input.my as f32 + 0.01);
+entry!(Store);
 
-    fn cro(a: Vec2<f32>, b: Vec2<f32>) -> f32 {
-        a.x * b.y - a.y * b.x
-    }
+const CLEAR: RGBA = RGBA {
+    r: 0,
+    g: 0,
+    b: 0,
+    a: 0,
+};
+
+fn cro(a: Vec2<f32>, b: Vec2<f32>) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+fn sd_bezier(p: Vec2<f32>, v0: Vec2<f32>, v1: Vec2<f32>, v2: Vec2<f32>) -> f32 {
+    let mid = (v0 + v2) * 0.5;
+    let to_v1 = v1 - mid;
+    let v1 = v1 + to_v1 * 1.;
 
-    fn sd_bezier(p: Vec2<f32>, v0: Vec2<f32>, v1: Vec2<f32>, v2: Vec2<f32>) -> f32 {
-        let mid = (v0 + v2) * 0.5;
-        let to_v1 = v1 - mid;
-        let v1 = v1 + to_v1 * 1.;
+    let i = v0 - v2;
+    let j = v2 - v1;
+    let k = v1 - v0;
+    let w = j - k;
 
-        let i = v0 - v2;
-        let j = v2 - v1;
-        let k = v1 - v0;
-        let w = j - k;
+    let v0 = v0 - p;
+    let v1 = v1 - p;
+    let v2 = v2 - p;
 
-        let v0 = v0 - p;
-        let v1 = v1 - p;
-        let v2 = v2 - p;
+    let x = cro(v0, v2);
+    let y = cro(v1, v0);
+    let z = cro(v2, v1);
 
-        let x = cro(v0, v2);
-        let y = cro(v1, v0);
-        let z = cro(v2, v1);
+    let s = 2.0 * (y * j + z * k) - x * i;
 
-        let s = 2.0 * (y * j + z * k) - x * i;
+    let r = (y * z - x * x * 0.25) / s.dot(s);
+    let t = ((0.5 * x + y + r * s.dot(w)) / (x + y + z)).clamp(0.0, 1.0);
 
-        let r = (y * z - x * x * 0.25) / s.dot(s);
-        let t = ((0.5 * x + y + r * s.dot(w)) / (x + y + z)).clamp(0.0, 1.0);
+    (v0 + t * (k + k + t * w)).magnitude()
+}
 
-        (v0 + t * (k + k + t * w)).magnitude()
+impl App for Store {
+    fn init(ctx: &mut Context<Self>) -> Self {
+        log("store not found");
+        let (x, y) = ctx.input.mouse();
+        Store { x, y, xm: x, ym: y }
     }
 
-    for y in 0..ctx.fb.h {
-        for x in 0..ctx.fb.w {
-            let p = &mut ctx.fb.pixels[x + y * ctx.fb.w];
-            if (x as i32 - ctx.input.mx as i32).abs() + (y as i32 - ctx.input.my as i32).abs() < 80
-            {
-                let pos = Vec2::new(x as f32, y as f32);
-                let d = sd_bezier(pos, am, a, b);
+    fn update(&mut self, ctx: &mut Context<Self>) -> Ret {
+        let (mouse_x, mouse_y) = ctx.input.mouse();
+        let am = Vec2::new(self.xm as f32 + 0.01, self.ym as f32);
+        let a = Vec2::new(self.x as f32, self.y as f32);
+        let b = Vec2::new(mouse_x as f32 + 0.01, mouse_y as f32 + 0.01);
+
+        let left_click = ctx.input.button(MouseButton::Left);
+        // Only around the old and the new cursor
+        if ctx.has(caps::DAMAGE) {
+            let around = |x: usize, y: usize| {
+                Rect::new(
+                    x.saturating_sub(80) as u32,
+                    y.saturating_sub(80) as u32,
+                    160,
+                    160,
+                )
+            };
+            let rects = [around(self.x, self.y), around(mouse_x, mouse_y)];
+            (ctx.damage)(rects.as_ptr(), rects.len() as u32);
+        }
+        // The surface keeps the last cursor, the compositor shows what is under it
+        ctx.fb.fill_rect(
+            self.x.saturating_sub(80),
+            self.y.saturating_sub(80),
+            160,
+            160,
+            CLEAR,
+        );
+        for y in mouse_y.saturating_sub(80)..mouse_y + 80 {
+            for x in mouse_x.saturating_sub(80)..mouse_x + 80 {
+                if (x as i32 - mouse_x as i32).abs() + (y as i32 - mouse_y as i32).abs() >= 80 {
+                    continue;
+                }
+                let Some(p) = ctx.fb.pixel_mut(x, y) else {
+                    continue;
+                };
+                let d = sd_bezier(Vec2::new(x as f32, y as f32), am, a, b);
                 if d < 3. {
+                    p.a = 255;
                     p.r = 255;
-
-                    let left_click = ctx.input.keys[0x110];
-                    if left_click < 128 {
+                    if !left_click.is_down() {
                         p.g = 255;
                         p.b = 255;
-                    } else if left_click == 128 {
+                    } else if left_click == KeyState::OnFromOff {
                         p.g = 255;
                         p.b = 0;
                     } else {
@@ -94,18 +116,18 @@ pub extern "C" fn _start(ctx: &mut Context<Store>) -> i32 {
                         p.b = 0;
                     }
                 } else if d < 4. {
+                    p.a = 255;
                     p.r = 0;
                     p.g = 0;
                     p.b = 0;
                 }
             }
         }
-    }
-    store.xm = store.x;
-    store.ym = store.y;
-    store.x = ctx.input.mx;
-    store.y = ctx.input.my;
-    *ctx.store = Some(store);
+        self.xm = self.x;
+        self.ym = self.y;
+        self.x = mouse_x;
+        self.y = mouse_y;
 
-    return 0;
+        Ret::Continue
+    }
 }
@@ -7,30 +7,7 @@ fn panic(_info: &core::panic::PanicInfo) -> ! {
 }
 
 const TEXT: &str = "Writting from pid: ";
-#[repr(C)]
-pub struct Context<'a> {
-    pub version: u8,
-    start_time: u64,
-    log: extern "C" fn(s: *const u8, l: u32),
-    pid: u64,
-    fb: FB<'a>,
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[repr(C)]
-pub struct RGBA {
-    pub r: u8,
-    pub g: u8,
-    pub b: u8,
-    pub a: u8,
-}
-
-#[repr(C)]
-pub struct FB<'a> {
-    pub pixels: &'a mut [RGBA],
-    pub w: usize,
-    pub h: usize,
-}
+use fomos_abi::legacy::Context;
 
 #[no_mangle]
 pub extern "C" fn _start(ctx: &mut Context) -> i32 {
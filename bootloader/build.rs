@@ -1,7 +1,52 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use bootloader::BootConfig;
 
+///Must match `kernel/src/ramdisk.rs`
+const RAMDISK_MAGIC: &[u8; 8] = b"FOMOSRD1";
+const APPS_CONF: &str = "apps.conf";
+
+///Built image of every `app_*` and shared library `lib_*` next to the bootloader, by name
+fn find_apps(root: &Path) -> Vec<(String, PathBuf)> {
+    let mut apps = Vec::new();
+    for entry in std::fs::read_dir(root).unwrap() {
+        let dir = entry.unwrap().path();
+        let name = dir.file_name().unwrap().to_string_lossy().into_owned();
+        if !(name.starts_with("app_") || name.starts_with("lib_")) || !dir.is_dir() {
+            continue;
+        }
+        // The whole directory: an app built after this also reruns us
+        println!("cargo:rerun-if-changed={}", dir.display());
+        // Rust apps, the C app, then Rust libraries (cdylib)
+        let candidates = [
+            dir.join("target/x86_64/release/func"),
+            dir.join("target/main"),
+            dir.join(format!("target/x86_64/release/lib{}.so", name)),
+        ];
+        match candidates.into_iter().find(|p| p.is_file()) {
+            Some(image) => apps.push((name, image)),
+            None => println!("cargo:warning={} is not built, not in the ramdisk", name),
+        }
+    }
+    apps.sort();
+    apps
+}
+
+///Pack named files: magic, count, then for each one name length, data length, name, data.
+///All integers are u32 little endian.
+fn write_ramdisk(path: &Path, files: &[(String, Vec<u8>)]) {
+    let mut out = Vec::new();
+    out.extend_from_slice(RAMDISK_MAGIC);
+    out.extend_from_slice(&(files.len() as u32).to_le_bytes());
+    for (name, data) in files {
+        out.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(data);
+    }
+    std::fs::write(path, out).unwrap();
+}
+
 fn main() {
     // set by cargo, build scripts should use this directory for output files
     let out_dir = PathBuf::from(std::env::var_os("OUT_DIR").unwrap());
@@ -9,6 +54,20 @@ fn main() {
     // https://doc.rust-lang.org/nightly/cargo/reference/unstable.html#artifact-dependencies
     let kernel = PathBuf::from(std::env::var_os("CARGO_BIN_FILE_KERNEL_kernel").unwrap());
 
+    // pack the built apps and the autostart config in a ramdisk
+    let manifest_dir = PathBuf::from(std::env::var_os("CARGO_MANIFEST_DIR").unwrap());
+    let conf_path = manifest_dir.join(APPS_CONF);
+    println!("cargo:rerun-if-changed={}", conf_path.display());
+    let mut files = vec![(
+        String::from(APPS_CONF),
+        std::fs::read(&conf_path).unwrap_or_default(),
+    )];
+    for (name, image) in find_apps(manifest_dir.parent().unwrap()) {
+        files.push((name, std::fs::read(image).unwrap()));
+    }
+    let ramdisk_path = out_dir.join("ramdisk.img");
+    write_ramdisk(&ramdisk_path, &files);
+
     // create an UEFI disk image (optional)
     let uefi_path = out_dir.join("uefi.img");
 
@@ -16,12 +75,14 @@ fn main() {
     conf.frame_buffer.minimum_framebuffer_width = Some(1200);
     bootloader::UefiBoot::new(&kernel)
         .set_boot_config(&conf)
+        .set_ramdisk(&ramdisk_path)
         .create_disk_image(&uefi_path)
         .unwrap();
 
     // create a BIOS disk image
     let bios_path = out_dir.join("bios.img");
     bootloader::BiosBoot::new(&kernel)
+        .set_ramdisk(&ramdisk_path)
         .create_disk_image(&bios_path)
         .unwrap();
 
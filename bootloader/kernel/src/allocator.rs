@@ -1,5 +1,16 @@
-use alloc::alloc::{GlobalAlloc, Layout};
-use core::ptr::null_mut;
+use alloc::{
+    alloc::{GlobalAlloc, Layout},
+    vec::Vec,
+};
+use core::{
+    ptr::null_mut,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use hashbrown::HashMap;
+use lazy_static::lazy_static;
+use spin::Mutex;
+
+use crate::app::{current_pid, MemInfo};
 extern "C" fn a_init(l: alloc::alloc::Layout) -> *mut u8 {
     panic!("")
 }
@@ -46,6 +57,201 @@ pub static ALLOCATOR: LockedHeap = LockedHeap::empty();
 pub const HEAP_START: usize = 0x_4444_4444_0000;
 pub const HEAP_SIZE: usize = 128 * 1024 * 1024; // 100 KiB
 
+///What an app allocated through its Context, by pid
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AppMem {
+    pub live: usize,
+    pub peak: usize,
+    pub allocs: u64,
+    pub frees: u64,
+    pub failed: u64,
+    ///Kernel memory kept for the app, the shared memory it created for instance, see `charge`
+    pub held: usize,
+    ///Max `live` and `held`, allocations past it return null
+    pub quota: Option<usize>,
+}
+
+impl AppMem {
+    ///Whether `size` more bytes would go past the quota
+    fn over_quota(&self, size: usize) -> bool {
+        let charged = self.live + self.held;
+        self.quota.map_or(false, |quota| {
+            charged
+                .checked_add(size)
+                .map_or(true, |total| total > quota)
+        })
+    }
+
+    pub fn info(&self) -> MemInfo {
+        MemInfo {
+            live: (self.live + self.held) as u64,
+            peak: self.peak as u64,
+            allocs: self.allocs,
+            frees: self.frees,
+            failed: self.failed,
+            quota: self.quota.unwrap_or(0) as u64,
+        }
+    }
+}
+
+lazy_static! {
+    static ref APP_MEM: Mutex<HashMap<u64, AppMem>> = Mutex::new(HashMap::new());
+    ///Every live pointer and the app it is charged to. Apps free what others gave them:
+    ///a block a service allocated and returned belongs to its caller (see `hand_over`)
+    static ref OWNERS: Mutex<HashMap<usize, Owner>> = Mutex::new(HashMap::new());
+}
+
+///Numbers the blocks in allocation order, see `mark`
+static SERIAL: AtomicU64 = AtomicU64::new(0);
+
+struct Owner {
+    pid: u64,
+    ///`cdalloc` does not trust the layout it is given
+    layout: Layout,
+    serial: u64,
+}
+
+pub fn set_quota(pid: u64, quota: Option<usize>) {
+    APP_MEM.lock().entry(pid).or_default().quota = quota;
+}
+
+pub fn app_mem(pid: u64) -> Option<AppMem> {
+    APP_MEM.lock().get(&pid).copied()
+}
+
+///Forget an app that exited, after freeing what it did not
+pub fn forget_app(pid: u64) {
+    let freed = free_app(pid);
+    if freed > 0 {
+        log::warn!("pid {} leaked {} bytes, freed", pid, freed);
+    }
+    APP_MEM.lock().remove(&pid);
+}
+
+///Free every block charged to `pid`, its store among them: for an app that starts over or
+///exited. Apps keep their state in the store, what pointed there is gone with it.
+///Blocks it got from services are its own, those it gave its callers are not (see `hand_over`).
+///Returns the bytes freed
+pub fn free_app(pid: u64) -> usize {
+    let mut blocks = Vec::new();
+    OWNERS.lock().retain(|&ptr, owner| {
+        if owner.pid != pid {
+            return true;
+        }
+        blocks.push((ptr, owner.layout));
+        false
+    });
+    let mut freed = 0;
+    for &(ptr, layout) in blocks.iter() {
+        unsafe { ALLOCATOR.dealloc(ptr as *mut u8, layout) };
+        freed += layout.size();
+    }
+    if let Some(mem) = APP_MEM.lock().get_mut(&pid) {
+        mem.live = mem.live.saturating_sub(freed);
+        mem.frees += blocks.len() as u64;
+    }
+    freed
+}
+
+///Blocks allocated from now on have a serial of at least this, for `hand_over`
+pub fn mark() -> u64 {
+    SERIAL.load(Ordering::Relaxed)
+}
+
+///Charge the blocks `from` allocated since `since` (a `mark`) and still holds to `to`.
+///Done when a service returns: what it did not free is for its caller, and must outlive
+///the service owner starting over or exiting. Returns the bytes handed over
+pub fn hand_over(from: u64, to: u64, since: u64) -> usize {
+    if from == to {
+        return 0;
+    }
+    let mut size = 0;
+    for owner in OWNERS.lock().values_mut() {
+        if owner.pid == from && owner.serial >= since {
+            owner.pid = to;
+            size += owner.layout.size();
+        }
+    }
+    if size > 0 {
+        let mut all = APP_MEM.lock();
+        if let Some(mem) = all.get_mut(&from) {
+            mem.live = mem.live.saturating_sub(size);
+        }
+        // Even past its quota, the caller has the blocks already
+        let mem = all.entry(to).or_default();
+        mem.live += size;
+        mem.peak = mem.peak.max(mem.live);
+    }
+    size
+}
+
+///Charge `size` bytes of kernel memory kept for `pid` to its quota, before allocating them.
+///False, and nothing charged, if that goes past it. Undone by `uncharge`
+pub fn charge(pid: u64, size: usize) -> bool {
+    let mut all = APP_MEM.lock();
+    let mem = all.entry(pid).or_default();
+    if mem.over_quota(size) {
+        mem.failed += 1;
+        return false;
+    }
+    mem.held += size;
+    true
+}
+
+///Once the memory of a `charge` is freed. Nothing to do if the app exited already
+pub fn uncharge(pid: u64, size: usize) {
+    if let Some(mem) = APP_MEM.lock().get_mut(&pid) {
+        mem.held = mem.held.saturating_sub(size);
+    }
+}
+
+///`calloc` of every app, accounted to the app being called.
+///Charges the block the heap hands out, zero sized allocations are refused
+pub extern "C" fn app_calloc(size: usize, align: usize) -> *mut u8 {
+    let Ok(layout) = Layout::from_size_align(size, align) else {
+        return null_mut();
+    };
+    let layout = layout.pad_to_align();
+    let size = layout.size();
+    let pid = current_pid();
+    let mut all = APP_MEM.lock();
+    let mem = all.entry(pid).or_default();
+    if size == 0 || mem.over_quota(size) {
+        mem.failed += 1;
+        return null_mut();
+    }
+    let ptr = unsafe { ALLOCATOR.alloc(layout) };
+    if ptr.is_null() {
+        mem.failed += 1;
+    } else {
+        mem.live += size;
+        mem.peak = mem.peak.max(mem.live);
+        mem.allocs += 1;
+        let owner = Owner {
+            pid,
+            layout,
+            serial: SERIAL.fetch_add(1, Ordering::Relaxed),
+        };
+        OWNERS.lock().insert(ptr as usize, owner);
+    }
+    ptr
+}
+
+///`cdalloc` of every app, accounted to the app that allocated.
+///Pointers `calloc` did not give are refused
+pub extern "C" fn app_cdalloc(ptr: *mut u8, _size: usize, _align: usize) {
+    let Some(owner) = OWNERS.lock().remove(&(ptr as usize)) else {
+        log::warn!("pid {} freed unknown pointer {:p}", current_pid(), ptr);
+        return;
+    };
+    unsafe { ALLOCATOR.dealloc(ptr, owner.layout) };
+    // Nothing to account if the owner exited already
+    if let Some(mem) = APP_MEM.lock().get_mut(&owner.pid) {
+        mem.live = mem.live.saturating_sub(owner.layout.size());
+        mem.frees += 1;
+    }
+}
+
 use x86_64::{
     structures::paging::{
         mapper::MapToError, FrameAllocator, Mapper, Page, PageTableFlags, PhysFrame, Size4KiB,
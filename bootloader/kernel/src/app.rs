@@ -1,143 +1,235 @@
-use core::{
-    alloc::Layout,
-    sync::atomic::{AtomicU64, Ordering},
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use alloc::{boxed::Box, fmt::format, format, string::String, sync::Arc, vec::Vec};
+use xmas_elf::ElfFile;
+
+use crate::{
+    allocator, clipboard, compositor,
+    display::Display,
+    frame::Frame,
+    framebuffer::FBShare,
+    globals,
+    guard::{self, Budget},
+    interpose::{Interposer, Policy},
+    interrupts::global_time_ms,
+    ipc,
+    library::{self, Library},
+    loader::{self, CodeBuf, LoadError},
+    process, service, shm,
 };
 
-use alloc::{boxed::Box, fmt::format, format, string::String, vec::Vec};
-use xmas_elf::{program, sections::SectionData, ElfFile};
+pub type Context<'a> = fomos_abi::Context<'a, ()>;
+pub use fomos_abi::{
+    caps, context_size, ClipInfo, Manifest, MemInfo, Message, ProcInfo, Rect, Ret, ServiceFn, Shm,
+    SurfaceInfo, CHANNEL_BIT, CLIP_BAD_ENTRY, CLIP_EMPTY, CLIP_IMAGE, CLIP_MAX_SIZE,
+    CLIP_NO_MEMORY, CLIP_TEXT, CLIP_TOO_LARGE, CLIP_TOO_SMALL, CONTEXT_VERSION, DAMAGE_MAX_RECTS,
+    EXIT_FAULT, EXIT_PANIC, EXIT_TIMEOUT, INBOX_CAPACITY, IPC_BAD_NAME, IPC_FULL, IPC_NO_MEMORY,
+    IPC_NO_TARGET, IPC_TOO_LARGE, IPC_TOO_SMALL, LAYER_BACKGROUND, LAYER_NORMAL, LAYER_OVERLAY,
+    MANIFEST_SECTION, MAX_MESSAGE, PROC_CRASHED, PROC_EXITED, PROC_OVER_BUDGET, PROC_READY,
+    PROC_SLEEPING, PROC_WAITING_INPUT, PROC_WAITING_MESSAGE, SERVICE_BAD_ENTRY, SERVICE_BAD_NAME,
+    SERVICE_EXISTS, SERVICE_FAULT, SERVICE_GONE, SHM_BAD_NAME, SHM_BAD_SIZE, SHM_EXISTS,
+    SHM_MAX_SIZE, SHM_NOT_FOUND, SHM_NO_MEMORY, SURFACE_BAD_LAYER, SURFACE_BAD_SIZE,
+    SURFACE_MAX_SCREENS, SURFACE_MAX_SIDE, SURFACE_NO_MEMORY,
+};
 
-use crate::{allocator::ALLOCATOR, framebuffer::FBShare, globals, interrupts::global_time_ms};
+///Everything this kernel puts in a Context
+pub const KERNEL_CAPS: u64 = caps::LOG
+    | caps::FB
+    | caps::ALLOC
+    | caps::STORE
+    | caps::INPUT
+    | caps::PROC
+    | caps::MEM
+    | caps::ABORT
+    | caps::IPC
+    | caps::SHM
+    | caps::SERVICE
+    | caps::CLIPBOARD
+    | caps::SURFACE
+    | caps::DAMAGE
+    | caps::FRAME
+    | caps::DISPLAY;
 
-#[repr(C)]
-pub struct Context<'a> {
-    pub version: u8,
-    pub start_time: u64,
-    pub log: extern "C" fn(*const u8, u32),
-    pub pid: u64,
-    pub fb: FBShare<'a>,
-    pub calloc: extern "C" fn(usize, usize) -> *mut u8,
-    pub cdalloc: extern "C" fn(*mut u8, usize, usize),
-    pub store: &'a mut Option<Box<()>>,
-    pub input: &'a globals::Input,
-}
 static mut none: Option<Box<()>> = None;
-impl<'a> Context<'a> {
-    pub fn new(
-        log: extern "C" fn(*const u8, u32),
-        fb: FBShare<'a>,
-        calloc: extern "C" fn(usize, usize) -> *mut u8,
-        cdalloc: extern "C" fn(*mut u8, usize, usize),
-        input: &'a globals::Input,
-    ) -> Context<'a> {
-        let x = Context {
-            version: 1,
-            start_time: global_time_ms(),
-            log,
-            pid: 0,
-            fb,
-            calloc,
-            cdalloc,
-            store: unsafe { &mut none },
-            input,
-        };
+pub fn new_context<'a>(
+    log: extern "C" fn(*const u8, u32),
+    fb: FBShare<'a>,
+    input: &'a globals::Input,
+    frame: &'a Frame,
+    display: &'a Display,
+) -> Context<'a> {
+    let x = Context {
+        version: CONTEXT_VERSION,
+        _reserved: [0; 3],
+        size: context_size(CONTEXT_VERSION) as u32,
+        start_time: global_time_ms(),
+        log,
+        pid: 0,
+        fb,
+        calloc: allocator::app_calloc,
+        cdalloc: allocator::app_cdalloc,
+        store: unsafe { &mut none },
+        input,
+        caps: KERNEL_CAPS,
+        spawn: process::spawn_fn,
+        kill: process::kill_fn,
+        ps: process::ps_fn,
+        mem: process::mem_fn,
+        abort: guard::abort_fn,
+        open: ipc::open_fn,
+        send: ipc::send_fn,
+        recv: ipc::recv_fn,
+        shm_create: shm::create_fn,
+        shm_map: shm::map_fn,
+        shm_release: shm::release_fn,
+        register: service::register_fn,
+        lookup: service::lookup_fn,
+        call: service::call_fn,
+        clip_set: clipboard::set_fn,
+        clip_get: clipboard::get_fn,
+        clip_serial: clipboard::serial_fn,
+        surface_get: compositor::get_fn,
+        surface_set: compositor::set_fn,
+        damage: compositor::damage_fn,
+        frame,
+        display,
+    };
+
+    return x;
+}
 
-        return x;
-    }
+///Pid given to kernel work done outside of any app call
+pub const KERNEL_PID: u64 = u64::MAX;
+static CURRENT_PID: AtomicU64 = AtomicU64::new(KERNEL_PID);
+
+///Pid of the app being called, so Context functions know who they work for
+pub fn current_pid() -> u64 {
+    CURRENT_PID.load(Ordering::Relaxed)
+}
+
+///Work on behalf of another app for a while (see `service`), returns the previous pid
+pub fn swap_current_pid(pid: u64) -> u64 {
+    CURRENT_PID.swap(pid, Ordering::Relaxed)
 }
 
+///Panics within `PANIC_WINDOW_MS` before an app is given up on
+const MAX_PANICS: u32 = 3;
+const PANIC_WINDOW_MS: u64 = 10_000;
+
 type FuncType = extern "C" fn(arg: &mut Context) -> i32;
 
+///Scheduling state of an app, driven by what its `_start` returns (see `fomos_abi::Ret`).
+///`Crashed` when the kernel aborted a call, see `guard`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AppState {
+    Ready,
+    Sleeping { until_ms: u64 },
+    WaitingInput(InputMark),
+    WaitingMessage,
+    Exited(i32),
+    Crashed(i32),
+}
+
+///The input as an app last saw it, any difference wakes a `WaitingInput` app
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InputMark {
+    history_last_index: usize,
+    mouse_x: usize,
+    mouse_y: usize,
+}
+impl InputMark {
+    pub fn new(input: &globals::Input) -> Self {
+        Self {
+            history_last_index: input.history_last_index,
+            mouse_x: input.mouse_x,
+            mouse_y: input.mouse_y,
+        }
+    }
+}
+
+///Manifest embedded by the app in its `MANIFEST_SECTION`, if any
+pub fn read_manifest(elf: &ElfFile) -> Option<Manifest> {
+    let section = elf.find_section_by_name(MANIFEST_SECTION)?;
+    loader::check_section(elf, &section).ok()?;
+    Manifest::from_bytes(section.raw_data(elf))
+}
+
 pub struct App {
-    pub code: Vec<u8>,
+    ///Loaded image, freed with the app
+    pub code: CodeBuf,
+    ///Shared libraries the app is bound to, loaded as long as it is
+    pub libs: Vec<Arc<Library>>,
     pub func: FuncType,
     pub pid: u64,
+    pub name: String,
+    pub manifest: Option<Manifest>,
     pub store: Option<Box<()>>,
+    ///Context version presented to the app, it never sees fields past it
+    pub context_version: u8,
+    ///Oldest Context version the app can run with
+    pub min_context_version: u8,
+    ///Context version the app was built against
+    pub built_version: u8,
+    pub state: AppState,
+    ///Kernel time of the load, in ms
+    pub start_time: u64,
+    pub calls: u64,
+    ///Wraps the Context entries of this app, see `interpose`
+    pub interposer: Interposer,
+    ///Calls aborted by a panic since `panic_window_ms`
+    pub panics: u32,
+    ///Kernel time of the first panic counted in `panics`
+    pub panic_window_ms: u64,
+    pub budget: Budget,
+    ///Calls that ran past the budget
+    pub overruns: u64,
 }
 impl App {
-    pub fn new(code: &[u8], show: bool) -> App {
-        let code = code.to_vec();
-        let code = &code[..];
-
-        // log::info!("loa efl");
-        let elf = ElfFile::new(code).expect("elf");
-        // log::info!("Elf file loaded at {:#p}", elf.input);
-        // log::info!("{:#?}", elf.header);
-
-        let mut min_virt = u64::MAX;
-        let mut max_virt = 0;
-
-        for program_header in elf.program_iter() {
-            if program_header.mem_size() > 0 {
-                min_virt = min_virt.min(program_header.virtual_addr());
-                max_virt = max_virt.max(program_header.virtual_addr() + program_header.mem_size());
-            }
-        }
-
-        min_virt = 0;
-        let cap = max_virt as usize;
-        use core::alloc::GlobalAlloc;
-        let ptr = unsafe { ALLOCATOR.alloc(Layout::from_size_align_unchecked(cap, 4096)) };
-
-        let mut owned_code: Vec<u8> = unsafe { Vec::from_raw_parts(ptr, 0, cap) }; //Vec::with_capacity((max_virt - min_virt) as usize);
-        for i in min_virt..max_virt {
-            owned_code.push(0);
-        }
-
-        for program_header in elf.program_iter() {
-            // log::info!("{:?}", program_header);
-
-            if let Ok(program::Type::Load) = program_header.get_type() {
-                let mut byte = 0;
-                let seg_offset = (program_header.virtual_addr() - min_virt) as usize;
-                for e in (program_header.offset() as usize)
-                    ..(program_header.offset() as usize + ((program_header.file_size()) as usize))
-                {
-                    owned_code[byte + seg_offset] = code[e];
-                    byte += 1;
-                }
-
-                if let Some(rela) = elf.find_section_by_name(".rela.dyn") {
-                    if let Ok(SectionData::Rela64(arr)) = rela.get_data(&elf) {
-                        for r in &arr[0..] {
-                            let off = r.get_offset() as usize;
-                            let add = r.get_addend() as usize;
-                            let typ = r.get_type() as usize;
-                            if off >= program_header.virtual_addr() as usize
-                                && off
-                                    < program_header.virtual_addr() as usize
-                                        + program_header.mem_size() as usize
-                            {
-                                // log::info!("{:?}", r);
-                                unsafe {
-                                    let global_off = owned_code.as_ptr() as u64;
-                                    let p64 = owned_code.as_ptr().offset(off as isize) as *mut u64;
-                                    p64.write(add as u64 + global_off);
-                                }
-
-                                // for k in 0..8 {
-                                //     owned_code[off + k] = owned_code[add + k];
-                                // }
-                            }
-                        }
-                    }
-                }
+    ///`name` and `context_version` are only used if the app has no manifest,
+    ///an app without manifest is assumed to know nothing past `context_version`.
+    pub fn new(code: &[u8], name: &str, context_version: u8, show: bool) -> Result<App, LoadError> {
+        let elf = ElfFile::new(code).map_err(LoadError::Parse)?;
+        loader::check_header(&elf)?;
+
+        let manifest = read_manifest(&elf);
+        let (name, context_version, min_context_version, built_version) = match &manifest {
+            Some(m) if !m.name().is_empty() => (
+                String::from(m.name()),
+                CONTEXT_VERSION,
+                m.min_context_version,
+                m.context_version,
+            ),
+            Some(m) => (
+                String::from(name),
+                CONTEXT_VERSION,
+                m.min_context_version,
+                m.context_version,
+            ),
+            None => (
+                String::from(name),
+                context_version,
+                context_version,
+                context_version,
+            ),
+        };
+        if let Some(m) = &manifest {
+            let missing = m.caps & !KERNEL_CAPS;
+            if missing != 0 {
+                log::warn!("{} requests missing capabilities {:#x}", name, missing);
             }
         }
 
-        // log::info!("{:?}", owned_code);
-        let codep = unsafe {
-            owned_code
-                .as_mut_ptr()
-                .offset((elf.header.pt2.entry_point() - min_virt) as isize)
-        };
-        // log::info!("ptr {:?}", codep);
-        let codef: FuncType = unsafe { core::intrinsics::transmute(codep) };
+        let libs = library::open(&loader::needed(&elf)?)?;
+        let scope = library::scope(&libs);
+        let image = loader::load(&elf, &|symbol| library::resolve(&scope, symbol))?;
+        let entry = image.entry(&elf)?;
+        let codep = unsafe { image.code.as_ptr().add(entry) };
+        let codef: FuncType = unsafe { core::mem::transmute(codep) };
 
         static NEXT_ID: AtomicU64 = AtomicU64::new(0);
 
         if show {
-            let entry = (elf.header.pt2.entry_point() - min_virt) as usize;
-            let code = &mut owned_code[entry..];
+            let code = &image.code[entry..];
             use iced_x86::{Decoder, DecoderOptions, Formatter, Instruction, NasmFormatter};
             let EXAMPLE_CODE_RIP = entry as u64;
             let HEXBYTES_COLUMN_BYTE_LENGTH = 8;
@@ -193,25 +285,169 @@ impl App {
             }
         }
 
-        App {
-            code: owned_code,
+        Ok(App {
+            code: image.code,
+            libs,
             func: codef,
             pid: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            name,
+            manifest,
             store: None,
-        }
+            context_version,
+            min_context_version,
+            built_version,
+            state: AppState::Ready,
+            start_time: global_time_ms(),
+            calls: 0,
+            interposer: Interposer::new(Policy::default()),
+            panics: 0,
+            panic_window_ms: 0,
+            budget: Budget::default(),
+            overruns: 0,
+        })
     }
     pub fn call(&mut self, arg: &mut Context) -> i32 {
         *arg.store = None;
 
         arg.pid = self.pid;
+        // Present the Context as the version the app knows, truncated
+        let version = self.context_version.min(CONTEXT_VERSION);
+        arg.version = version;
+        arg.size = context_size(version) as u32;
 
         let self_store = self.store.take();
 
         *arg.store = self_store;
-        let res = (self.func)(arg);
+        self.calls += 1;
+        self.interposer.enter(arg);
+        CURRENT_PID.store(self.pid, Ordering::Relaxed);
+        let outcome = guard::call_guarded(self.func, arg, &self.code, self.budget);
+        CURRENT_PID.store(KERNEL_PID, Ordering::Relaxed);
 
         self.store = arg.store.take();
 
-        res
+        if outcome.over_budget {
+            self.overruns += 1;
+            if self.overruns == 1 || self.overruns % 100 == 0 {
+                log::warn!(
+                    "{} (pid {}) ran {} ms, over its {} ms budget ({} times)",
+                    self.name,
+                    self.pid,
+                    outcome.elapsed_ms,
+                    self.budget.ms,
+                    self.overruns
+                );
+            }
+        }
+        if outcome.res == Err(EXIT_TIMEOUT) {
+            log::error!(
+                "{} (pid {}) aborted by the watchdog after {} ms",
+                self.name,
+                self.pid,
+                outcome.elapsed_ms
+            );
+        }
+        match outcome.res {
+            Ok(res) => res,
+            // Start over with an empty store. The kernel does not know the type of the old
+            // state, it frees the blocks of the app instead of dropping it
+            Err(EXIT_PANIC) if self.count_panic() < MAX_PANICS => {
+                core::mem::forget(self.store.take());
+                let freed = allocator::free_app(self.pid);
+                log::warn!(
+                    "{} (pid {}) restarts from scratch, {} bytes freed",
+                    self.name,
+                    self.pid,
+                    freed
+                );
+                Ret::Continue.encode()
+            }
+            Err(status) => {
+                // The state may be corrupt, leak it rather than touch it
+                core::mem::forget(self.store.take());
+                self.state = AppState::Crashed(status);
+                Ret::Exit(status).encode()
+            }
+        }
+    }
+
+    ///Count a panic, returns the panics within the current window
+    fn count_panic(&mut self) -> u32 {
+        let now = global_time_ms();
+        if self.panics == 0 || now - self.panic_window_ms > PANIC_WINDOW_MS {
+            self.panics = 0;
+            self.panic_window_ms = now;
+        }
+        self.panics += 1;
+        self.panics
+    }
+
+    ///Whether the scheduler should call the app this loop
+    pub fn is_ready(&self, now: u64, input: &globals::Input) -> bool {
+        match self.state {
+            AppState::Ready => true,
+            AppState::Sleeping { until_ms } => now >= until_ms,
+            AppState::WaitingInput(mark) => mark != InputMark::new(input),
+            AppState::WaitingMessage | AppState::Exited(_) | AppState::Crashed(_) => false,
+        }
+    }
+
+    ///Update the state from the code returned by `call`
+    pub fn handle_ret(&mut self, code: i32, now: u64, input: &globals::Input) {
+        if let AppState::Crashed(_) = self.state {
+            return;
+        }
+        self.state = match Ret::decode(code) {
+            Ret::Continue => AppState::Ready,
+            Ret::Exit(status) => {
+                log::info!(
+                    "{} (pid {}) exited with status {}",
+                    self.name,
+                    self.pid,
+                    status
+                );
+                AppState::Exited(status)
+            }
+            Ret::Sleep(ms) => AppState::Sleeping {
+                until_ms: now + ms as u64,
+            },
+            Ret::WaitInput => AppState::WaitingInput(InputMark::new(input)),
+            Ret::WaitMessage => AppState::WaitingMessage,
+        }
+    }
+
+    ///Wake the app if it waits for a message
+    pub fn notify(&mut self) {
+        if self.state == AppState::WaitingMessage {
+            self.state = AppState::Ready;
+        }
+    }
+
+    pub fn has_exited(&self) -> bool {
+        matches!(self.state, AppState::Exited(_) | AppState::Crashed(_))
+    }
+
+    ///Process table entry, as seen by `ps`
+    pub fn info(&self) -> ProcInfo {
+        let (state, exit_status) = match self.state {
+            AppState::Ready => (PROC_READY, 0),
+            AppState::Sleeping { .. } => (PROC_SLEEPING, 0),
+            AppState::WaitingInput(_) => (PROC_WAITING_INPUT, 0),
+            AppState::WaitingMessage => (PROC_WAITING_MESSAGE, 0),
+            AppState::Exited(status) => (PROC_EXITED, status),
+            AppState::Crashed(status) => (PROC_CRASHED, status),
+        };
+        ProcInfo {
+            start_time: self.start_time,
+            calls: self.calls,
+            exit_status,
+            state,
+            flags: if self.overruns > 0 {
+                PROC_OVER_BUDGET
+            } else {
+                0
+            },
+            ..ProcInfo::new(self.pid, &self.name)
+        }
     }
 }
@@ -0,0 +1,113 @@
+//! System clipboard: one typed entry (text or RGBA image) held by the kernel, so it
+//! survives the app that copied it.
+//!
+//! Every `clip_set` bumps a serial, apps poll `clip_serial` to notice a change without
+//! copying the data.
+//!
+//! The entry is charged to the quota of the app that set it (see `allocator::charge`) until it
+//! is replaced.
+use alloc::vec::Vec;
+use lazy_static::lazy_static;
+use spin::Mutex;
+
+use crate::{
+    allocator,
+    app::{
+        current_pid, ClipInfo, CLIP_BAD_ENTRY, CLIP_EMPTY, CLIP_IMAGE, CLIP_MAX_SIZE,
+        CLIP_NO_MEMORY, CLIP_TEXT, CLIP_TOO_LARGE, CLIP_TOO_SMALL,
+    },
+};
+
+#[derive(Default)]
+struct Clipboard {
+    info: ClipInfo,
+    data: Vec<u8>,
+    ///Pid `data` is charged to
+    owner: u64,
+}
+
+lazy_static! {
+    static ref CLIPBOARD: Mutex<Clipboard> = Mutex::new(Clipboard::default());
+}
+
+///Whether `data` is a valid entry of the kind and size in `info`
+fn check(info: &ClipInfo, data: &[u8]) -> Result<(), i32> {
+    if data.len() > CLIP_MAX_SIZE {
+        return Err(CLIP_TOO_LARGE);
+    }
+    let ok = match info.kind {
+        CLIP_EMPTY => data.is_empty(),
+        CLIP_TEXT => core::str::from_utf8(data).is_ok(),
+        CLIP_IMAGE => (info.w as usize)
+            .checked_mul(info.h as usize)
+            .and_then(|pixels| pixels.checked_mul(4))
+            .is_some_and(|size| size == data.len()),
+        _ => false,
+    };
+    ok.then_some(()).ok_or(CLIP_BAD_ENTRY)
+}
+
+pub extern "C" fn set_fn(info: *const ClipInfo, data: *const u8) -> i64 {
+    let mut info = unsafe { *info };
+    if info.len as usize > CLIP_MAX_SIZE {
+        return CLIP_TOO_LARGE as i64;
+    }
+    let data = if info.len == 0 {
+        &[][..]
+    } else {
+        unsafe { core::slice::from_raw_parts(data, info.len as usize) }
+    };
+    if let Err(err) = check(&info, data) {
+        return err as i64;
+    }
+    if info.kind != CLIP_IMAGE {
+        info.w = 0;
+        info.h = 0;
+    }
+    info._reserved = 0;
+    let pid = current_pid();
+    if !allocator::charge(pid, data.len()) {
+        return CLIP_NO_MEMORY as i64;
+    }
+    let mut copy = Vec::new();
+    if copy.try_reserve_exact(data.len()).is_err() {
+        allocator::uncharge(pid, data.len());
+        return CLIP_NO_MEMORY as i64;
+    }
+    copy.extend_from_slice(data);
+    let mut clipboard = CLIPBOARD.lock();
+    info.serial = clipboard.info.serial + 1;
+    clipboard.info = info;
+    let old = core::mem::replace(&mut clipboard.data, copy);
+    let old_owner = core::mem::replace(&mut clipboard.owner, pid);
+    drop(clipboard);
+    allocator::uncharge(old_owner, old.len());
+    log::info!(
+        "pid {} set the clipboard (kind {}, {} bytes)",
+        pid,
+        info.kind,
+        info.len
+    );
+    info.serial as i64
+}
+
+pub extern "C" fn get_fn(info: *mut ClipInfo, data: *mut u8, cap: usize) -> i32 {
+    let clipboard = CLIPBOARD.lock();
+    unsafe { info.write(clipboard.info) };
+    if clipboard.info.kind == CLIP_EMPTY {
+        return 0;
+    }
+    if cap < clipboard.data.len() {
+        return CLIP_TOO_SMALL;
+    }
+    if !clipboard.data.is_empty() {
+        unsafe {
+            core::ptr::copy_nonoverlapping(clipboard.data.as_ptr(), data, clipboard.data.len())
+        };
+    }
+    1
+}
+
+pub extern "C" fn serial_fn() -> u64 {
+    CLIPBOARD.lock().info.serial
+}
@@ -0,0 +1,441 @@
+//! Compositor: every app draws into its own surface (its `Context::fb`), the kernel stacks
+//! the surfaces and blends them into the scanout buffer the GPU driver transfers.
+//!
+//! Stacking is a kernel decision: layers first (see `LAYER_*`), then in the normal layer
+//! the surface clicked last is on top. Apps only choose their position, size and opacity.
+//! Surfaces are only reallocated by `share`, between calls, so the `fb` of a call stays valid.
+//! Their pixels are charged to the quota of the app (see `allocator::charge`).
+//!
+//! Only the damaged parts of the screen are blended again (see `damage`): what the apps
+//! reported they drew, and where surfaces moved, faded, were raised or went away.
+use alloc::vec::Vec;
+use hashbrown::HashMap;
+use lazy_static::lazy_static;
+use spin::Mutex;
+
+use crate::{
+    allocator,
+    app::{
+        current_pid, App, SurfaceInfo, DAMAGE_MAX_RECTS, LAYER_BACKGROUND, LAYER_NORMAL,
+        LAYER_OVERLAY, SURFACE_BAD_LAYER, SURFACE_BAD_SIZE, SURFACE_MAX_SCREENS, SURFACE_MAX_SIDE,
+        SURFACE_NO_MEMORY,
+    },
+    damage::{self, Damage, Rect},
+    framebuffer::{FBShare, FB, RGBA},
+    globals::{Input, KeyState},
+};
+use fomos_abi::MouseButton;
+
+///First Context version whose apps draw with alpha, surfaces of older ones are opaque
+const ALPHA_VERSION: u8 = 10;
+
+const TRANSPARENT: RGBA = RGBA {
+    r: 0,
+    g: 0,
+    b: 0,
+    a: 0,
+};
+///Shown where no surface is
+const DESKTOP: RGBA = RGBA {
+    r: 0,
+    g: 0,
+    b: 0,
+    a: 255,
+};
+
+struct Surface {
+    pixels: Vec<RGBA>,
+    x: i32,
+    y: i32,
+    w: usize,
+    h: usize,
+    layer: u32,
+    opacity: u8,
+    ///When it was last raised, the highest is on top of its layer
+    raised: u64,
+    ///Follows the screen size until the app picks one
+    fullscreen: bool,
+    ///Size asked by `surface_set`, applied by the next `share`
+    resize: Option<(usize, usize)>,
+    ///Pixel alpha ignored, the app predates it
+    opaque: bool,
+    ///Called since the last `compose`
+    called: bool,
+    ///Reported its damage during that call, otherwise all of it changed
+    reported: bool,
+    ///What the app reported, in surface coordinates
+    damage: Damage,
+}
+
+impl Surface {
+    ///Alpha of the screen pixel `x`, `y`, 0 outside of the surface
+    fn alpha_at(&self, x: usize, y: usize) -> u8 {
+        let sx = x as i64 - self.x as i64;
+        let sy = y as i64 - self.y as i64;
+        if sx < 0 || sy < 0 || sx >= self.w as i64 || sy >= self.h as i64 {
+            return 0;
+        }
+        if self.opaque {
+            return self.opacity;
+        }
+        let a = self.pixels[sx as usize + sy as usize * self.w].a;
+        mul(a, self.opacity)
+    }
+
+    ///`rect` of the surface in screen coordinates, clipped to a `screen` sized screen
+    fn to_screen(&self, rect: Rect, screen: (usize, usize)) -> Rect {
+        let (sw, sh) = (screen.0 as i64, screen.1 as i64);
+        let x0 = (self.x as i64 + rect.x as i64).clamp(0, sw);
+        let y0 = (self.y as i64 + rect.y as i64).clamp(0, sh);
+        let x1 = (self.x as i64 + rect.right() as i64).clamp(0, sw);
+        let y1 = (self.y as i64 + rect.bottom() as i64).clamp(0, sh);
+        Rect::new(x0 as u32, y0 as u32, (x1 - x0) as u32, (y1 - y0) as u32)
+    }
+
+    ///Where the surface is on screen
+    fn bounds(&self, screen: (usize, usize)) -> Rect {
+        self.to_screen(Rect::new(0, 0, self.w as u32, self.h as u32), screen)
+    }
+}
+
+#[derive(Default)]
+struct Compositor {
+    surfaces: HashMap<u64, Surface>,
+    raises: u64,
+    ///Screen parts to blend again, besides what the called apps drew
+    damage: Damage,
+    ///Screen size of the last `compose`
+    screen: (usize, usize),
+    ///Screen size of the last `share`, kept by `invalidate`
+    desktop: (usize, usize),
+}
+
+lazy_static! {
+    static ref COMPOSITOR: Mutex<Compositor> = Mutex::new(Compositor::default());
+}
+
+///Unknown layers are stacked with the normal ones
+fn layer_rank(layer: u32) -> u32 {
+    match layer {
+        LAYER_BACKGROUND => 0,
+        LAYER_OVERLAY => 2,
+        _ => 1,
+    }
+}
+
+impl Compositor {
+    ///What is under the surface of `pid` has to be blended again
+    fn damage_surface(&mut self, pid: u64) {
+        if let Some(surface) = self.surfaces.get(&pid) {
+            let bounds = surface.bounds(self.screen);
+            self.damage.add(bounds);
+        }
+    }
+
+    ///Pids, bottom to top
+    fn stack(&self) -> Vec<u64> {
+        let mut pids: Vec<u64> = self.surfaces.keys().copied().collect();
+        pids.sort_by_key(|pid| {
+            let surface = &self.surfaces[pid];
+            (layer_rank(surface.layer), surface.raised, *pid)
+        });
+        pids
+    }
+
+    fn info(&self, pid: u64) -> Option<SurfaceInfo> {
+        let surface = self.surfaces.get(&pid)?;
+        let (w, h) = surface.resize.unwrap_or((surface.w, surface.h));
+        let z = self.stack().iter().position(|&p| p == pid).unwrap_or(0);
+        Some(SurfaceInfo {
+            x: surface.x,
+            y: surface.y,
+            w: w as u32,
+            h: h as u32,
+            layer: surface.layer,
+            z: z as u32,
+            opacity: surface.opacity,
+            _reserved: [0; 7],
+        })
+    }
+}
+
+///Transparent pixels charged to `pid`, `None` if its quota or the kernel heap cannot hold them
+fn alloc_pixels(pid: u64, w: usize, h: usize) -> Option<Vec<RGBA>> {
+    let len = w.checked_mul(h)?;
+    let size = len.checked_mul(core::mem::size_of::<RGBA>())?;
+    if !allocator::charge(pid, size) {
+        return None;
+    }
+    let mut pixels = Vec::new();
+    if pixels.try_reserve_exact(len).is_err() {
+        allocator::uncharge(pid, size);
+        return None;
+    }
+    pixels.resize(len, TRANSPARENT);
+    Some(pixels)
+}
+
+///Surface of `app` for one call, created on its first one, sized like the screen.
+///A pending resize, or a screen size change for a full screen surface, happens here
+pub fn share(app: &App, screen_w: usize, screen_h: usize) -> FBShare<'static> {
+    let mut compositor = COMPOSITOR.lock();
+    let compositor = &mut *compositor;
+    let screen = compositor.screen;
+    compositor.desktop = (screen_w, screen_h);
+    let raises = &mut compositor.raises;
+    let surface = compositor.surfaces.entry(app.pid).or_insert_with(|| {
+        *raises += 1;
+        let layer = app
+            .manifest
+            .map(|m| m.layer)
+            .filter(|&layer| layer <= LAYER_OVERLAY)
+            .unwrap_or(LAYER_NORMAL);
+        Surface {
+            pixels: Vec::new(),
+            x: 0,
+            y: 0,
+            w: 0,
+            h: 0,
+            layer,
+            opacity: 255,
+            raised: *raises,
+            fullscreen: true,
+            resize: None,
+            opaque: app.built_version < ALPHA_VERSION,
+            called: false,
+            reported: false,
+            damage: Damage::new(),
+        }
+    });
+    // The app is about to draw
+    surface.called = true;
+    surface.reported = false;
+    let resize = match surface.resize.take() {
+        Some(size) => Some(size),
+        None if surface.fullscreen && (surface.w, surface.h) != (screen_w, screen_h) => {
+            Some((screen_w, screen_h))
+        }
+        None => None,
+    };
+    if let Some((w, h)) = resize {
+        match alloc_pixels(app.pid, w, h) {
+            Some(pixels) => {
+                compositor.damage.add(surface.bounds(screen));
+                let old = core::mem::replace(&mut surface.pixels, pixels);
+                allocator::uncharge(app.pid, old.len() * core::mem::size_of::<RGBA>());
+                surface.w = w;
+                surface.h = h;
+                compositor.damage.add(surface.bounds(screen));
+            }
+            None => {
+                // Keep the current size, the app can see it with `surface_get`
+                log::warn!(
+                    "{} (pid {}) has no memory for a {}x{} surface, kept at {}x{}",
+                    app.name,
+                    app.pid,
+                    w,
+                    h,
+                    surface.w,
+                    surface.h
+                );
+                surface.fullscreen = false;
+            }
+        }
+    }
+    // Safety: the pixels are only reallocated above, before a call of the app,
+    // and freed by `forget` once it exited
+    let pixels = unsafe {
+        core::slice::from_raw_parts_mut(surface.pixels.as_mut_ptr(), surface.pixels.len())
+    };
+    FBShare {
+        pixels,
+        w: surface.w,
+        h: surface.h,
+    }
+}
+
+///Drop the surface of an exited app
+pub fn forget(pid: u64) {
+    let mut compositor = COMPOSITOR.lock();
+    compositor.damage_surface(pid);
+    if let Some(surface) = compositor.surfaces.remove(&pid) {
+        allocator::uncharge(pid, surface.pixels.len() * core::mem::size_of::<RGBA>());
+    }
+}
+
+///To call before every scheduler pass: a click raises the normal surface under the mouse
+pub fn update(input: &Input) {
+    if input.button(MouseButton::Left) != KeyState::OnFromOff {
+        return;
+    }
+    let (x, y) = input.mouse();
+    let mut compositor = COMPOSITOR.lock();
+    let hit = compositor.stack().into_iter().rev().find(|pid| {
+        let surface = &compositor.surfaces[pid];
+        surface.layer == LAYER_NORMAL && surface.alpha_at(x, y) > 0
+    });
+    if let Some(pid) = hit {
+        compositor.raises += 1;
+        let raised = compositor.raises;
+        compositor.surfaces.get_mut(&pid).unwrap().raised = raised;
+        compositor.damage_surface(pid);
+    }
+}
+
+///The display driver swapped `FB::pixels` for a new buffer: compose all of it next time
+pub fn invalidate() {
+    COMPOSITOR.lock().screen = (0, 0);
+}
+
+///Blend the damaged parts of every surface into `fb`, bottom to top, and hand them to
+///the display driver. Nothing to do if no app drew and no surface changed since the last time
+pub fn compose(fb: &mut FB) {
+    let mut compositor = COMPOSITOR.lock();
+    let compositor = &mut *compositor;
+    let screen = (fb.w, fb.h);
+    if compositor.screen != screen {
+        compositor.screen = screen;
+        compositor.damage.clear();
+        compositor
+            .damage
+            .add(Rect::new(0, 0, fb.w as u32, fb.h as u32));
+    }
+    for surface in compositor.surfaces.values_mut() {
+        if !surface.called {
+            continue;
+        }
+        if surface.reported {
+            for &rect in surface.damage.rects() {
+                compositor.damage.add(surface.to_screen(rect, screen));
+            }
+        } else {
+            compositor.damage.add(surface.bounds(screen));
+        }
+        surface.called = false;
+        surface.reported = false;
+        surface.damage.clear();
+    }
+    if compositor.damage.is_empty() {
+        return;
+    }
+    let stack = compositor.stack();
+    for &rect in compositor.damage.rects() {
+        for y in rect.y as usize..rect.bottom() as usize {
+            fb.pixels[rect.x as usize + y * fb.w..][..rect.w as usize].fill(DESKTOP);
+        }
+        for pid in stack.iter() {
+            blend(fb, &compositor.surfaces[pid], rect);
+        }
+    }
+    damage::submit(&compositor.damage);
+    compositor.damage.clear();
+}
+
+fn mul(a: u8, b: u8) -> u8 {
+    ((a as u32 * b as u32 + 127) / 255) as u8
+}
+
+fn mix(src: u8, dst: u8, a: u8) -> u8 {
+    ((src as u32 * a as u32 + dst as u32 * (255 - a as u32) + 127) / 255) as u8
+}
+
+///Blend the part of `surface` inside `clip`, a rectangle of the screen
+fn blend(fb: &mut FB, surface: &Surface, clip: Rect) {
+    if surface.opacity == 0 {
+        return;
+    }
+    // Visible part, in screen coordinates
+    let visible = surface.bounds((fb.w, fb.h)).intersect(&clip);
+    if visible.is_empty() {
+        return;
+    }
+    let (x0, y0) = (visible.x as usize, visible.y as usize);
+    let (x1, y1) = (visible.right() as usize, visible.bottom() as usize);
+    let sx = (x0 as i64 - surface.x as i64) as usize;
+    for y in y0..y1 {
+        let sy = (y as i64 - surface.y as i64) as usize;
+        let src = &surface.pixels[sx + sy * surface.w..][..x1 - x0];
+        let dst = &mut fb.pixels[x0 + y * fb.w..][..x1 - x0];
+        for (d, s) in dst.iter_mut().zip(src) {
+            let a = if surface.opaque {
+                surface.opacity
+            } else {
+                mul(s.a, surface.opacity)
+            };
+            match a {
+                0 => {}
+                255 => *d = RGBA { a: 255, ..*s },
+                a => {
+                    d.r = mix(s.r, d.r, a);
+                    d.g = mix(s.g, d.g, a);
+                    d.b = mix(s.b, d.b, a);
+                }
+            }
+        }
+    }
+}
+
+pub extern "C" fn get_fn(info: *mut SurfaceInfo) {
+    let compositor = COMPOSITOR.lock();
+    let surface = compositor.info(current_pid()).unwrap_or_default();
+    unsafe { info.write(surface) };
+}
+
+pub extern "C" fn set_fn(info: *const SurfaceInfo) -> i32 {
+    let info = unsafe { *info };
+    if info.w == 0 || info.h == 0 || info.w > SURFACE_MAX_SIDE || info.h > SURFACE_MAX_SIDE {
+        return SURFACE_BAD_SIZE;
+    }
+    if info.layer > LAYER_OVERLAY {
+        return SURFACE_BAD_LAYER;
+    }
+    let mut compositor = COMPOSITOR.lock();
+    let compositor = &mut *compositor;
+    let (dw, dh) = compositor.desktop;
+    if info.w as u64 * info.h as u64 > SURFACE_MAX_SCREENS as u64 * dw as u64 * dh as u64 {
+        return SURFACE_NO_MEMORY;
+    }
+    // Every app got its surface before its first call
+    let Some(surface) = compositor.surfaces.get_mut(&current_pid()) else {
+        return 0;
+    };
+    let moved = (info.x, info.y, info.opacity, info.layer)
+        != (surface.x, surface.y, surface.opacity, surface.layer);
+    if moved {
+        compositor.damage.add(surface.bounds(compositor.screen));
+    }
+    let size = (info.w as usize, info.h as usize);
+    if size != surface.resize.unwrap_or((surface.w, surface.h)) {
+        surface.resize = Some(size);
+        surface.fullscreen = false;
+    }
+    if info.layer != surface.layer {
+        compositor.raises += 1;
+        surface.raised = compositor.raises;
+        surface.layer = info.layer;
+    }
+    surface.x = info.x;
+    surface.y = info.y;
+    surface.opacity = info.opacity;
+    if moved {
+        compositor.damage.add(surface.bounds(compositor.screen));
+    }
+    0
+}
+
+pub extern "C" fn damage_fn(rects: *const Rect, n: u32) {
+    let mut compositor = COMPOSITOR.lock();
+    let Some(surface) = compositor.surfaces.get_mut(&current_pid()) else {
+        return;
+    };
+    surface.reported = true;
+    if rects.is_null() || n == 0 {
+        return;
+    }
+    let rects = unsafe { core::slice::from_raw_parts(rects, n.min(DAMAGE_MAX_RECTS) as usize) };
+    for rect in rects {
+        surface
+            .damage
+            .add(rect.clip(surface.w as u32, surface.h as u32));
+    }
+}
@@ -0,0 +1,24 @@
+//! Damage: the parts of the screen that changed since they were last shown.
+//!
+//! Apps report what they drew (`Context::damage`), the compositor turns it into screen
+//! rectangles, only blends those, and hands them to the display driver through `submit`.
+//! The driver `take`s them and only transfers and flushes these rectangles.
+use lazy_static::lazy_static;
+use spin::Mutex;
+
+pub use fomos_abi::{Damage, Rect};
+
+lazy_static! {
+    ///Composed but not shown yet
+    static ref PENDING: Mutex<Damage> = Mutex::new(Damage::new());
+}
+
+///Called by the compositor once `frame` is blended in the scanout buffer
+pub fn submit(frame: &Damage) {
+    PENDING.lock().extend(frame);
+}
+
+///Called by the display driver: what changed on screen since its last call
+pub fn take() -> Damage {
+    core::mem::take(&mut *PENDING.lock())
+}
@@ -0,0 +1,39 @@
+//! Screens the frames are presented on: set by the display driver when it starts and on
+//! every hotplug or resize, given to the apps in the Context.
+use lazy_static::lazy_static;
+use spin::Mutex;
+
+use crate::damage::Rect;
+pub use fomos_abi::{Display, DISPLAY_MAX_SCREENS};
+
+lazy_static! {
+    static ref DISPLAY: Mutex<Display> = Mutex::new(Display::default());
+}
+
+///Called by the display driver: each screen shows its part of the framebuffer
+pub fn set(screens: &[Rect]) {
+    let mut display = DISPLAY.lock();
+    let screens = &screens[..screens.len().min(DISPLAY_MAX_SCREENS)];
+    let count = screens.len();
+    let mut new = Display {
+        serial: display.serial + 1,
+        count: count as u32,
+        ..Default::default()
+    };
+    new.screens[..count].copy_from_slice(screens);
+    let bounds = screens.iter().fold(Rect::default(), |b, s| b.union(s));
+    new.w = bounds.right();
+    new.h = bounds.bottom();
+    log::info!(
+        "display {}x{}, {} screen(s) {:?}",
+        new.w,
+        new.h,
+        count,
+        new.screens()
+    );
+    *display = new;
+}
+
+pub fn current() -> Display {
+    *DISPLAY.lock()
+}
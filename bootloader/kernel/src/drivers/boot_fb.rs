@@ -0,0 +1,43 @@
+use bootloader_api::info::FrameBufferInfo;
+
+use crate::{
+    damage::{self, Rect},
+    display, frame,
+    framebuffer::{self, FB},
+};
+
+///Present the frames on the framebuffer set up by the bootloader (UEFI GOP or VBE),
+///for machines without a virtio-gpu device (`-vga std`, `bochs-display`)
+pub async fn drive(framebuffer: &'static mut [u8], info: FrameBufferInfo, fb: *mut FB) {
+    if !framebuffer::supports(&info) {
+        log::error!(
+            "boot framebuffer: {:?} with {} bytes per pixel not supported, nothing will be shown",
+            info.pixel_format,
+            info.bytes_per_pixel
+        );
+        return;
+    }
+    log::info!(
+        "boot framebuffer: presenting {}x{} {:?}, stride {}",
+        info.width,
+        info.height,
+        info.pixel_format,
+        info.stride
+    );
+    display::set(&[Rect::new(0, 0, info.width as u32, info.height as u32)]);
+    frame::attach();
+    loop {
+        frame::pace().await;
+        let fb = unsafe { &*fb };
+        // The app framebuffer is sized after this one, but do not trust it
+        let w = fb.w.min(info.width) as u32;
+        let h = fb.h.min(info.height) as u32;
+        for rect in damage::take().rects() {
+            let rect = rect.clip(w, h);
+            if !rect.is_empty() {
+                fb.flush(framebuffer, &info, rect);
+            }
+        }
+        frame::presented();
+    }
+}
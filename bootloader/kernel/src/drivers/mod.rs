@@ -1,2 +1,3 @@
+pub mod boot_fb;
 pub mod virtio_gpu;
 pub mod virtio_input;
@@ -1,22 +1,23 @@
 use core::{
     alloc::{GlobalAlloc, Layout},
     ptr::{read_volatile, write_volatile},
-    sync::atomic::{AtomicU64, Ordering},
 };
 
 use alloc::{sync::Arc, vec::Vec};
 use futures::task::AtomicWaker;
 use lazy_static::lazy_static;
 use x86_64::{
-    structures::paging::{frame, FrameAllocator, Mapper, Size4KiB},
+    structures::paging::{mapper::MapToError, FrameAllocator, Mapper, Page, Size4KiB},
     VirtAddr,
 };
 
 use crate::{
     allocator::{self, ALLOCATOR},
-    create_identity_virt_from_phys_n,
+    compositor, create_identity_virt_from_phys_n,
+    damage::{self, Rect},
+    display, frame,
     framebuffer::{FB, RGBA},
-    interrupts::global_time_ms,
+    free_identity_virt_from_phys_n,
     task::{
         executor::{yield_once, Spawner},
         Task,
@@ -80,29 +81,12 @@ pub async fn drive(mut virtio: Virtio, spawner: Spawner, fb: *mut FB) {
             yield_once().await;
         }
 
-        let response_desc = request(
-            Arc::clone(&virtio),
-            VirtioGpuCtrlHdr {
-                type_: VirtioGpuCtrlType::VirtioGpuCmdGetDisplayInfo,
-                ..Default::default()
-            },
-        )
-        .await;
-        let mut display_info =
-            (response_desc.addr as *const VirtioGpuRespOkDisplayInfo).read_volatile();
+        let display_info = display_info(&virtio).await;
         log::info!("{:?}", display_info);
 
+        let conf_ptr: *mut VirtioGpuConfig =
+            core::intrinsics::transmute((virtio.lock().device.cap) as *const ());
         {
-            #[repr(C)]
-            #[derive(Clone, Debug)]
-            struct VirtioGpuConfig {
-                events_read: u32,
-                events_clear: u32,
-                num_scanouts: u32,
-                num_capsets: u32,
-            }
-            let conf_ptr: *mut VirtioGpuConfig =
-                core::intrinsics::transmute((virtio.lock().device.cap) as *const ());
             let mut rconf = conf_ptr.read_volatile();
 
             for i in 0..rconf.num_capsets {
@@ -125,11 +109,6 @@ pub async fn drive(mut virtio: Virtio, spawner: Spawner, fb: *mut FB) {
             yield_once().await;
         }
 
-        // for capn in 0..display_info.pmodes.
-
-        display_info.pmodes.rect.w = 1600;
-        display_info.pmodes.rect.h = 900;
-
         let response_desc = request(
             Arc::clone(&virtio),
             VirtioGpuCtrlHdr {
@@ -141,116 +120,29 @@ pub async fn drive(mut virtio: Virtio, spawner: Spawner, fb: *mut FB) {
         let edid = (response_desc.addr as *const VirtioGpuRespEdid).read_volatile();
         // log::info!("{:?}", edid);
 
-        let response_desc = request(
-            Arc::clone(&virtio),
-            VirtioGpuCmdResourceCreate2d {
-                header: VirtioGpuCtrlHdr {
-                    type_: VirtioGpuCtrlType::VirtioGpuCmdResourceCreate2d,
-                    ..Default::default()
-                },
-                resource_id: 1,
-                format: VirtioGpuFormats::VIRTIO_GPU_FORMAT_R8G8B8A8_UNORM,
-                width: display_info.pmodes.rect.w,
-                height: display_info.pmodes.rect.h,
-            },
-        )
-        .await;
-        let nodata = (response_desc.addr as *const VirtioGpuCtrlHdr).read_volatile();
-        log::info!("{:?}", nodata.type_);
-
-        let capacity = (display_info.pmodes.rect.w * display_info.pmodes.rect.h) as usize;
-
-        let framebuffer_bytes = capacity * 4;
-        let pages_needed = 1 + framebuffer_bytes / 4096;
-        let pages = create_identity_virt_from_phys_n(pages_needed).unwrap();
-        let addr = pages.start_address().as_u64();
-        // let framebuffer_ptr =
-        //     ALLOCATOR.alloc(Layout::from_size_align_unchecked(capacity * 4, 4096));
-        let mut framebuffer: Vec<RGBA> = Vec::from_raw_parts(addr as *mut RGBA, capacity, capacity);
-        log::info!("(*fb).update {:?}", addr as *mut RGBA);
-        (*fb).update(
-            addr as *mut RGBA,
-            display_info.pmodes.rect.w as usize,
-            display_info.pmodes.rect.h as usize,
-        );
-
-        let response_desc = request(
-            Arc::clone(&virtio),
-            VirtioGpuCmdResourceAttachBacking {
-                header: VirtioGpuCtrlHdr {
-                    type_: VirtioGpuCtrlType::VirtioGpuCmdResourceAttachBacking,
-                    ..Default::default()
-                },
-                resource_id: 1,
-                nr_entries: 1,
-                //mem
-                addr,
-                length: (core::mem::size_of::<RGBA>() * framebuffer.len()) as u32,
-                padding: 0,
-            },
-        )
-        .await;
-        let nodata = (response_desc.addr as *const VirtioGpuCtrlHdr).read_volatile();
-        log::info!("{:?}", nodata.type_);
-
-        let response_desc = request(
-            Arc::clone(&virtio),
-            VirtioGpuCmdSetScanout {
-                header: VirtioGpuCtrlHdr {
-                    type_: VirtioGpuCtrlType::VirtioGpuCmdSetScanout,
-                    ..Default::default()
-                },
-                r: display_info.pmodes.rect,
-                resource_id: 1,
-                scanout_id: 0,
-            },
-        )
-        .await;
-        let nodata = (response_desc.addr as *const VirtioGpuCtrlHdr).read_volatile();
-        log::info!("{:?}", nodata.type_);
-
-        for i in 0..capacity {
-            framebuffer[i] = (RGBA {
-                r: 100,
-                g: 120,
-                b: 140,
-                a: 125,
-            });
-        }
-
-        //FIRST TRANSFER AND FLUSH
-        let response_desc = request(
-            Arc::clone(&virtio),
-            VirtioGpuCmdTransferToHost2d {
-                header: VirtioGpuCtrlHdr {
-                    type_: VirtioGpuCtrlType::VirtioGpuCmdTransferToHost2d,
-                    ..Default::default()
-                },
-                r: display_info.pmodes.rect,
-                resource_id: 1,
-                padding: 0,
-                offset: 0,
-            },
-        )
-        .await;
-        let nodata = (response_desc.addr as *const VirtioGpuCtrlHdr).read_volatile();
-        log::info!("{:?}", nodata.type_);
-
-        let response_desc = request(
-            Arc::clone(&virtio),
-            VirtioGpuCmdResourceFlush {
-                header: VirtioGpuCtrlHdr {
-                    type_: VirtioGpuCtrlType::VirtioGpuCmdResourceFlush,
-                    ..Default::default()
-                },
-                r: display_info.pmodes.rect,
-                resource_id: 1,
-                padding: 0,
-            },
+        let mut pool: Vec<Backing> = Vec::new();
+        let mut generation = 0;
+        let created = Desktop::create(
+            &virtio,
+            layout(&display_info),
+            SCANOUT_RESOURCES[generation],
+            &mut pool,
         )
         .await;
-        let nodata = (response_desc.addr as *const VirtioGpuCtrlHdr).read_volatile();
-        log::info!("{:?}", nodata.type_);
+        let (mut desktop, back) = match created {
+            Ok(created) => created,
+            Err(err) => {
+                log::error!(
+                    "virtio-gpu: no memory for the desktop, nothing will be shown: {:?}",
+                    err
+                );
+                return;
+            }
+        };
+        log::info!("(*fb).update {:?}", back.as_ptr());
+        // The heap pixels the compositor drew in until now
+        drop((*fb).update(back, desktop.w as usize, desktop.h as usize));
+        display::set(&desktop.screens());
 
         let mut debug_name: [char; 64] = ['1'; 64];
         let name = "Debug\0";
@@ -445,72 +337,379 @@ pub async fn drive(mut virtio: Virtio, spawner: Spawner, fb: *mut FB) {
         //     }
         // });
 
-        if true {
-            let virtio_2 = Arc::clone(&virtio);
-            spawner.run(async move {
-                loop {
-                    request(
-                        Arc::clone(&virtio_2),
-                        VirtioGpuCmdTransferToHost2d {
-                            header: VirtioGpuCtrlHdr {
-                                type_: VirtioGpuCtrlType::VirtioGpuCmdTransferToHost2d,
-                                ..Default::default()
-                            },
-                            r: display_info.pmodes.rect,
-                            resource_id: 1,
-                            padding: 0,
-                            offset: 0,
-                        },
-                    )
-                    .await;
+        // One frame per tick of the frame clock, only what the compositor changed goes to the host
+        frame::attach();
+        loop {
+            frame::pace().await;
+            let conf = conf_ptr.read_volatile();
+            if conf.events_read & VIRTIO_GPU_EVENT_DISPLAY != 0 {
+                write_volatile(&mut (*conf_ptr).events_clear, VIRTIO_GPU_EVENT_DISPLAY);
+                let heads = layout(&display_info(&virtio).await);
+                if heads != desktop.heads {
+                    // Hotplug or resize: new resources, the compositor draws everything again
+                    let resources = SCANOUT_RESOURCES[1 - generation];
+                    match Desktop::create(&virtio, heads, resources, &mut pool).await {
+                        Ok((new, back)) => {
+                            generation = 1 - generation;
+                            let old_back = (*fb).update(back, new.w as usize, new.h as usize);
+                            let old = core::mem::replace(&mut desktop, new);
+                            old.destroy(&virtio, old_back, &desktop.heads, &mut pool)
+                                .await;
+                            compositor::invalidate();
+                            display::set(&desktop.screens());
+                            // Whatever was composed was for the old size
+                            damage::take();
+                            frame::presented();
+                            continue;
+                        }
+                        // Until the next change, the heads show what they can of the old one
+                        Err(err) => log::error!(
+                            "virtio-gpu: no memory for the new desktop, kept at {}x{}: {:?}",
+                            desktop.w,
+                            desktop.h,
+                            err
+                        ),
+                    }
                 }
-            });
+            }
+            desktop.present(&virtio, &mut (*fb).pixels).await;
+            frame::presented();
         }
-        loop {
-            use futures::join;
-            join!(
-                // request(
-                //     Arc::clone(&virtio),
-                //     VirtioGpuCmdTransferToHost2d {
-                //         header: VirtioGpuCtrlHdr {
-                //             type_: VirtioGpuCtrlType::VirtioGpuCmdTransferToHost2d,
-                //             ..Default::default()
-                //         },
-                //         r: display_info.pmodes.rect,
-                //         resource_id: 1,
-                //         padding: 0,
-                //         offset: 0,
-                //     },
-                //
-                // ),
-                request(
-                    Arc::clone(&virtio),
-                    VirtioGpuCmdResourceFlush {
-                        header: VirtioGpuCtrlHdr {
-                            type_: VirtioGpuCtrlType::VirtioGpuCmdResourceFlush,
-                            ..Default::default()
-                        },
-                        r: display_info.pmodes.rect,
-                        resource_id: 1,
-                        padding: 0,
+    }
+}
+
+///2d resources of the desktop, a hotplug or a resize switches to the other pair.
+///2 is the virgl example
+const SCANOUT_RESOURCES: [[u32; 2]; 2] = [[1, 3], [4, 5]];
+
+///`VirtioGpuConfig::events_read`: the display info changed
+const VIRTIO_GPU_EVENT_DISPLAY: u32 = 1 << 0;
+const VIRTIO_GPU_MAX_SCANOUTS: usize = 16;
+///Used if the device reports no enabled scanout
+const DEFAULT_MODE: (u32, u32) = (1600, 900);
+
+#[repr(C)]
+#[derive(Clone, Debug)]
+struct VirtioGpuConfig {
+    events_read: u32,
+    events_clear: u32,
+    num_scanouts: u32,
+    num_capsets: u32,
+}
+
+async fn display_info(virtio: &Arc<Mutex<Virtio>>) -> VirtioGpuRespOkDisplayInfo {
+    let response_desc = request(
+        Arc::clone(virtio),
+        VirtioGpuCtrlHdr {
+            type_: VirtioGpuCtrlType::VirtioGpuCmdGetDisplayInfo,
+            ..Default::default()
+        },
+    )
+    .await;
+    unsafe { (response_desc.addr as *const VirtioGpuRespOkDisplayInfo).read_volatile() }
+}
+
+///Enabled scanouts and their part of the desktop, side by side from left to right
+fn layout(info: &VirtioGpuRespOkDisplayInfo) -> Vec<(u32, Rect)> {
+    let mut heads = Vec::new();
+    let mut x = 0;
+    for (scanout_id, mode) in info.pmodes.iter().enumerate() {
+        if mode.enabled == 0 || mode.rect.w == 0 || mode.rect.h == 0 {
+            continue;
+        }
+        heads.push((scanout_id as u32, Rect::new(x, 0, mode.rect.w, mode.rect.h)));
+        x += mode.rect.w;
+    }
+    if heads.is_empty() {
+        heads.push((0, Rect::new(0, 0, DEFAULT_MODE.0, DEFAULT_MODE.1)));
+    }
+    heads
+}
+
+///Identity mapped memory behind a resource. Resources created later reuse it instead of
+///mapping more, unless it is too small for them (see `create_buffer`)
+#[derive(Clone, Copy, Debug)]
+struct Backing {
+    addr: u64,
+    pages: usize,
+}
+
+///What the device shows: a double buffered pair of resources the size of the desktop.
+///The host shows the front one while the compositor draws in the back one (`FB::pixels`),
+///each head scans out its part of it
+struct Desktop {
+    heads: Vec<(u32, Rect)>,
+    w: u32,
+    h: u32,
+    front_id: u32,
+    back_id: u32,
+    front: Vec<RGBA>,
+    backings: [Backing; 2],
+}
+
+impl Desktop {
+    ///Create the resources, show the front one on every head.
+    ///Returns the back pixels too, for the compositor. Nothing is created if memory is missing
+    async fn create(
+        virtio: &Arc<Mutex<Virtio>>,
+        heads: Vec<(u32, Rect)>,
+        [front_id, back_id]: [u32; 2],
+        pool: &mut Vec<Backing>,
+    ) -> Result<(Desktop, Vec<RGBA>), MapToError<Size4KiB>> {
+        let bounds = heads.iter().fold(Rect::default(), |b, (_, r)| b.union(r));
+        let (w, h) = (bounds.right(), bounds.bottom());
+        let (mut front, front_backing) = create_buffer(virtio, front_id, w, h, pool).await?;
+        let (back, back_backing) = match create_buffer(virtio, back_id, w, h, pool).await {
+            Ok(buffer) => buffer,
+            Err(err) => {
+                unref(virtio, front_id).await;
+                // Not from the heap
+                core::mem::forget(front);
+                pool.push(front_backing);
+                return Err(err);
+            }
+        };
+        front.fill(RGBA {
+            r: 100,
+            g: 120,
+            b: 140,
+            a: 125,
+        });
+        let desktop = Desktop {
+            heads,
+            w,
+            h,
+            front_id,
+            back_id,
+            front,
+            backings: [front_backing, back_backing],
+        };
+        let all = Rect::new(0, 0, w, h);
+        desktop.transfer(virtio, desktop.front_id, &[all]).await;
+        desktop.scan_out(virtio, desktop.front_id).await;
+        desktop.flush(virtio, desktop.front_id, &[all]).await;
+        Ok((desktop, back))
+    }
+
+    ///Parts of the desktop shown by each head, for `display`
+    fn screens(&self) -> Vec<Rect> {
+        self.heads.iter().map(|&(_, rect)| rect).collect()
+    }
+
+    ///Show what the compositor changed in `back`, then swap: `back` becomes the old front,
+    ///brought up to date
+    async fn present(&mut self, virtio: &Arc<Mutex<Virtio>>, back: &mut Vec<RGBA>) {
+        let damage = damage::take();
+        let rects: Vec<Rect> = damage
+            .rects()
+            .iter()
+            .map(|rect| rect.clip(self.w, self.h))
+            .filter(|rect| !rect.is_empty())
+            .collect();
+        if rects.is_empty() {
+            // The host keeps showing the front buffer
+            return;
+        }
+        self.transfer(virtio, self.back_id, &rects).await;
+        self.scan_out(virtio, self.back_id).await;
+        self.flush(virtio, self.back_id, &rects).await;
+        core::mem::swap(&mut self.front, back);
+        core::mem::swap(&mut self.front_id, &mut self.back_id);
+        let w = self.w as usize;
+        for rect in rects.iter() {
+            for y in rect.y as usize..rect.bottom() as usize {
+                let start = rect.x as usize + y * w;
+                let row = start..start + rect.w as usize;
+                back[row.clone()].copy_from_slice(&self.front[row]);
+            }
+        }
+    }
+
+    async fn transfer(&self, virtio: &Arc<Mutex<Virtio>>, resource_id: u32, rects: &[Rect]) {
+        for &rect in rects {
+            request(
+                Arc::clone(virtio),
+                VirtioGpuCmdTransferToHost2d {
+                    header: VirtioGpuCtrlHdr {
+                        type_: VirtioGpuCtrlType::VirtioGpuCmdTransferToHost2d,
+                        ..Default::default()
                     },
-                )
-            );
-            let now = global_time_ms();
-            let elapsed = now - LAST_FLUSH_MS.load(Ordering::Relaxed);
-            LAST_FLUSH_MS.store(now, Ordering::Relaxed);
-            // log::info!("gpu start {} elapsed {}", now, elapsed);
-            // while elapsed < 10 {
-            //     yield_once().await;
-            //     elapsed = get_time_ms() - start;
-            // }
-
-            // yield_once().await;
+                    r: rect.into(),
+                    resource_id,
+                    padding: 0,
+                    // Where the rectangle starts in the backing, rows are desktop wide
+                    offset: (rect.y as u64 * self.w as u64 + rect.x as u64)
+                        * core::mem::size_of::<RGBA>() as u64,
+                },
+            )
+            .await;
         }
     }
+
+    ///Every head shows its part of `resource_id`
+    async fn scan_out(&self, virtio: &Arc<Mutex<Virtio>>, resource_id: u32) {
+        for &(scanout_id, rect) in self.heads.iter() {
+            set_scanout(virtio, scanout_id, resource_id, rect).await;
+        }
+    }
+
+    async fn flush(&self, virtio: &Arc<Mutex<Virtio>>, resource_id: u32, rects: &[Rect]) {
+        for &rect in rects {
+            request(
+                Arc::clone(virtio),
+                VirtioGpuCmdResourceFlush {
+                    header: VirtioGpuCtrlHdr {
+                        type_: VirtioGpuCtrlType::VirtioGpuCmdResourceFlush,
+                        ..Default::default()
+                    },
+                    r: rect.into(),
+                    resource_id,
+                    padding: 0,
+                },
+            )
+            .await;
+        }
+    }
+
+    ///Once a new desktop is shown: turn off the heads it does not use, free the resources
+    ///and keep their memory in `pool`. `back` are the pixels the compositor had
+    async fn destroy(
+        self,
+        virtio: &Arc<Mutex<Virtio>>,
+        back: Vec<RGBA>,
+        heads: &[(u32, Rect)],
+        pool: &mut Vec<Backing>,
+    ) {
+        for &(scanout_id, _) in self.heads.iter() {
+            if !heads.iter().any(|&(id, _)| id == scanout_id) {
+                set_scanout(virtio, scanout_id, 0, Rect::default()).await;
+            }
+        }
+        for resource_id in [self.front_id, self.back_id] {
+            unref(virtio, resource_id).await;
+        }
+        // Not from the heap, the memory goes back to the pool
+        core::mem::forget(self.front);
+        core::mem::forget(back);
+        pool.extend(self.backings);
+    }
+}
+
+///Show `rect` of `resource_id` on a head, resource 0 turns it off
+async fn set_scanout(virtio: &Arc<Mutex<Virtio>>, scanout_id: u32, resource_id: u32, rect: Rect) {
+    let response_desc = request(
+        Arc::clone(virtio),
+        VirtioGpuCmdSetScanout {
+            header: VirtioGpuCtrlHdr {
+                type_: VirtioGpuCtrlType::VirtioGpuCmdSetScanout,
+                ..Default::default()
+            },
+            r: rect.into(),
+            scanout_id,
+            resource_id,
+        },
+    )
+    .await;
+    let nodata = unsafe { (response_desc.addr as *const VirtioGpuCtrlHdr).read_volatile() };
+    if !matches!(nodata.type_, VirtioGpuCtrlType::VirtioGpuRespOkNodata) {
+        log::error!("set scanout {} {:?}", scanout_id, nodata.type_);
+    }
 }
 
-static LAST_FLUSH_MS: AtomicU64 = AtomicU64::new(0);
+///Detach the backing of `resource_id` and free it, the backing memory stays mapped
+async fn unref(virtio: &Arc<Mutex<Virtio>>, resource_id: u32) {
+    request(
+        Arc::clone(virtio),
+        VirtioGpuCmdResourceDetachBacking {
+            header: VirtioGpuCtrlHdr {
+                type_: VirtioGpuCtrlType::VirtioGpuCmdResourceDetachBacking,
+                ..Default::default()
+            },
+            resource_id,
+            padding: 0,
+        },
+    )
+    .await;
+    request(
+        Arc::clone(virtio),
+        VirtioGpuCmdResourceUnref {
+            header: VirtioGpuCtrlHdr {
+                type_: VirtioGpuCtrlType::VirtioGpuCmdResourceUnref,
+                ..Default::default()
+            },
+            resource_id,
+            padding: 0,
+        },
+    )
+    .await;
+}
+
+///Create a `w` x `h` 2d resource, backed by memory of `pool` if some is large enough.
+///Otherwise the backings of `pool` too small for it are freed and new memory is mapped
+async fn create_buffer(
+    virtio: &Arc<Mutex<Virtio>>,
+    resource_id: u32,
+    w: u32,
+    h: u32,
+    pool: &mut Vec<Backing>,
+) -> Result<(Vec<RGBA>, Backing), MapToError<Size4KiB>> {
+    let capacity = (w * h) as usize;
+    let framebuffer_bytes = capacity * core::mem::size_of::<RGBA>();
+    let pages_needed = 1 + framebuffer_bytes / 4096;
+    let backing = match pool.iter().position(|b| b.pages >= pages_needed) {
+        Some(i) => pool.swap_remove(i),
+        None => {
+            // A larger desktop, they would only be used again if it shrinks
+            for small in pool.drain(..) {
+                let page = Page::containing_address(VirtAddr::new(small.addr));
+                free_identity_virt_from_phys_n(page, small.pages);
+            }
+            let pages = create_identity_virt_from_phys_n(pages_needed)?;
+            Backing {
+                addr: pages.start_address().as_u64(),
+                pages: pages_needed,
+            }
+        }
+    };
+    let buffer = unsafe { Vec::from_raw_parts(backing.addr as *mut RGBA, capacity, capacity) };
+
+    let response_desc = request(
+        Arc::clone(virtio),
+        VirtioGpuCmdResourceCreate2d {
+            header: VirtioGpuCtrlHdr {
+                type_: VirtioGpuCtrlType::VirtioGpuCmdResourceCreate2d,
+                ..Default::default()
+            },
+            resource_id,
+            format: VirtioGpuFormats::VIRTIO_GPU_FORMAT_R8G8B8A8_UNORM,
+            width: w,
+            height: h,
+        },
+    )
+    .await;
+    let nodata = unsafe { (response_desc.addr as *const VirtioGpuCtrlHdr).read_volatile() };
+    log::info!("{:?}", nodata.type_);
+
+    let response_desc = request(
+        Arc::clone(virtio),
+        VirtioGpuCmdResourceAttachBacking {
+            header: VirtioGpuCtrlHdr {
+                type_: VirtioGpuCtrlType::VirtioGpuCmdResourceAttachBacking,
+                ..Default::default()
+            },
+            resource_id,
+            nr_entries: 1,
+            //mem
+            addr: backing.addr,
+            length: framebuffer_bytes as u32,
+            padding: 0,
+        },
+    )
+    .await;
+    let nodata = unsafe { (response_desc.addr as *const VirtioGpuCtrlHdr).read_volatile() };
+    log::info!("{:?}", nodata.type_);
+    Ok((buffer, backing))
+}
 
 pub async fn request<T>(virtio: Arc<Mutex<Virtio>>, data: T) -> Desc {
     let twice = { virtio.lock().get_free_twice_desc_id() };
@@ -671,6 +870,17 @@ struct VirtioGpuRect {
     h: u32,
 }
 
+impl From<Rect> for VirtioGpuRect {
+    fn from(rect: Rect) -> Self {
+        Self {
+            x: rect.x,
+            y: rect.y,
+            w: rect.w,
+            h: rect.h,
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(Clone, Debug, Copy)]
 struct VirtioGpuDisplay {
@@ -683,7 +893,7 @@ struct VirtioGpuDisplay {
 #[derive(Clone, Debug)]
 struct VirtioGpuRespOkDisplayInfo {
     header: VirtioGpuCtrlHdr,
-    pmodes: VirtioGpuDisplay,
+    pmodes: [VirtioGpuDisplay; VIRTIO_GPU_MAX_SCANOUTS],
 }
 
 #[repr(C)]
@@ -744,6 +954,22 @@ struct VirtioGpuCmdSetScanout {
     resource_id: u32,
 }
 
+#[repr(C)]
+#[derive(Clone, Debug)]
+struct VirtioGpuCmdResourceDetachBacking {
+    header: VirtioGpuCtrlHdr,
+    resource_id: u32,
+    padding: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Debug)]
+struct VirtioGpuCmdResourceUnref {
+    header: VirtioGpuCtrlHdr,
+    resource_id: u32,
+    padding: u32,
+}
+
 #[repr(C)]
 #[derive(Clone, Debug)]
 struct VirtioGpuCmdTransferToHost2d {
@@ -0,0 +1,87 @@
+//! Kernel symbols apps can import, see `fomos_abi::exports`.
+//!
+//! The same entries as the Context, bound once by the loader. Imported `log`, `alloc` and
+//! `free` go through the policy of the app (`interpose`) like their Context counterparts.
+use alloc::vec::Vec;
+use lazy_static::lazy_static;
+
+use crate::{
+    app::current_pid, clipboard, compositor, guard, interpose, interrupts::global_time_ms, ipc,
+    process, service, shm,
+};
+pub use fomos_abi::exports::EXPORTS_VERSION;
+
+static EXPORTS_VERSION_SYMBOL: u32 = EXPORTS_VERSION;
+
+pub struct Export {
+    pub name: &'static str,
+    pub addr: u64,
+}
+
+///`(name, address)` pairs, checked at compile time against `fomos_abi::exports::EXPORTS`
+macro_rules! exports {
+    ($(($name:literal, $addr:expr)),* $(,)?) => {{
+        const NAMES: &[&str] = &[$($name),*];
+        const _: () = assert!(
+            fomos_abi::exports::same_symbols(NAMES),
+            "the kernel exports differ from fomos_abi::exports::EXPORTS"
+        );
+        [$(($name, $addr)),*]
+    }};
+}
+
+lazy_static! {
+    static ref EXPORTS: Vec<Export> = {
+        let table = exports![
+            (
+                "fomos_exports_version",
+                &EXPORTS_VERSION_SYMBOL as *const u32 as u64,
+            ),
+            ("fomos_log", interpose::log_fn as usize as u64),
+            ("fomos_alloc", interpose::calloc_fn as usize as u64),
+            ("fomos_free", interpose::cdalloc_fn as usize as u64),
+            ("fomos_time_ms", time_ms_fn as usize as u64),
+            ("fomos_pid", pid_fn as usize as u64),
+            ("fomos_spawn", process::spawn_fn as usize as u64),
+            ("fomos_kill", process::kill_fn as usize as u64),
+            ("fomos_ps", process::ps_fn as usize as u64),
+            ("fomos_mem", process::mem_fn as usize as u64),
+            ("fomos_abort", guard::abort_fn as usize as u64),
+            ("fomos_open", ipc::open_fn as usize as u64),
+            ("fomos_send", ipc::send_fn as usize as u64),
+            ("fomos_recv", ipc::recv_fn as usize as u64),
+            ("fomos_shm_create", shm::create_fn as usize as u64),
+            ("fomos_shm_map", shm::map_fn as usize as u64),
+            ("fomos_shm_release", shm::release_fn as usize as u64),
+            ("fomos_register", service::register_fn as usize as u64),
+            ("fomos_lookup", service::lookup_fn as usize as u64),
+            ("fomos_call", service::call_fn as usize as u64),
+            ("fomos_clip_set", clipboard::set_fn as usize as u64),
+            ("fomos_clip_get", clipboard::get_fn as usize as u64),
+            ("fomos_clip_serial", clipboard::serial_fn as usize as u64),
+            ("fomos_surface_get", compositor::get_fn as usize as u64),
+            ("fomos_surface_set", compositor::set_fn as usize as u64),
+            ("fomos_damage", compositor::damage_fn as usize as u64),
+        ];
+        table
+            .into_iter()
+            .map(|(name, addr)| Export { name, addr })
+            .collect()
+    };
+}
+
+///Address of a kernel export
+pub fn lookup(name: &str) -> Option<u64> {
+    EXPORTS
+        .iter()
+        .find(|export| export.name == name)
+        .map(|export| export.addr)
+}
+
+extern "C" fn time_ms_fn() -> u64 {
+    global_time_ms()
+}
+
+extern "C" fn pid_fn() -> u64 {
+    current_pid()
+}
@@ -0,0 +1,68 @@
+//! Frame clock: the pace at which frames are presented, and the app loop with it.
+//!
+//! The display driver `attach`es to the clock, presents at most `REFRESH_HZ` frames per
+//! second (`pace`) and calls `presented` once the host showed one. The app loop composes a
+//! frame, then waits in `next` for it to be presented before calling the apps again.
+//! Without a display driver, the clock ticks on its own.
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use crate::{
+    interrupts::{a_sleep, global_time_ms},
+    task::executor::yield_once,
+};
+pub use fomos_abi::Frame;
+
+///Frames presented per second, at most
+pub const REFRESH_HZ: u32 = 60;
+const FRAME_MS: u64 = 1000 / REFRESH_HZ as u64;
+
+static NUMBER: AtomicU64 = AtomicU64::new(0);
+static PRESENTED_MS: AtomicU64 = AtomicU64::new(0);
+static DELTA_MS: AtomicU64 = AtomicU64::new(0);
+///A display driver calls `presented`
+static ATTACHED: AtomicBool = AtomicBool::new(false);
+
+///The frame the apps draw now, given to them in the Context
+pub fn current() -> Frame {
+    Frame {
+        number: NUMBER.load(Ordering::Relaxed),
+        presented_ms: PRESENTED_MS.load(Ordering::Relaxed),
+        delta_ms: DELTA_MS.load(Ordering::Relaxed) as u32,
+        refresh_hz: REFRESH_HZ,
+    }
+}
+
+///Called by a display driver before it presents its first frame
+pub fn attach() {
+    ATTACHED.store(true, Ordering::Relaxed);
+}
+
+///Called by the display driver: wait until it is time to present the next frame
+pub async fn pace() {
+    let due = PRESENTED_MS.load(Ordering::Relaxed) + FRAME_MS;
+    let now = global_time_ms();
+    if now < due {
+        a_sleep(due - now).await;
+    }
+}
+
+///Called by the display driver once the frame is on screen, wakes up the app loop
+pub fn presented() {
+    let now = global_time_ms();
+    let last = PRESENTED_MS.swap(now, Ordering::Relaxed);
+    DELTA_MS.store(now - last, Ordering::Relaxed);
+    NUMBER.fetch_add(1, Ordering::Relaxed);
+}
+
+///Called by the app loop once its frame is composed: wait until it is presented
+pub async fn next() {
+    if !ATTACHED.load(Ordering::Relaxed) {
+        pace().await;
+        presented();
+        return;
+    }
+    let number = NUMBER.load(Ordering::Relaxed);
+    while NUMBER.load(Ordering::Relaxed) == number {
+        yield_once().await;
+    }
+}
@@ -67,6 +67,11 @@ impl FrameBufferWriter {
         logger
     }
 
+    /// Gives back the framebuffer it was logging to.
+    pub fn into_buffer(self) -> &'static mut [u8] {
+        self.framebuffer
+    }
+
     fn newline(&mut self) {
         self.y_pos += font_constants::CHAR_RASTER_HEIGHT.val() + LINE_SPACING;
         self.carriage_return()
@@ -160,18 +165,34 @@ impl fmt::Write for FrameBufferWriter {
         Ok(())
     }
 }
-use alloc::{slice, vec::Vec};
+use alloc::vec::Vec;
+
+use crate::damage::Rect;
+pub use fomos_abi::{FB as FBShare, RGBA};
+
+///Framebuffers `FB::flush` can copy to: a pixel format it converts to, pixels large enough
+///for it and a buffer holding `stride * height` of them
+pub fn supports(info: &FrameBufferInfo) -> bool {
+    let min_bpp = match info.pixel_format {
+        PixelFormat::Rgb | PixelFormat::Bgr => 3,
+        PixelFormat::U8 => 1,
+        _ => return false,
+    };
+    info.bytes_per_pixel >= min_bpp
+        && info.width <= info.stride
+        && info
+            .stride
+            .checked_mul(info.height)
+            .and_then(|pixels| pixels.checked_mul(info.bytes_per_pixel))
+            .is_some_and(|len| len <= info.byte_len)
+}
 
-use crate::interrupts::global_time_ms;
-// extern crate alloc;
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[repr(C)]
-pub struct RGBA {
-    pub r: u8,
-    pub g: u8,
-    pub b: u8,
-    pub a: u8,
+///Gray level of a pixel, for `PixelFormat::U8` screens
+fn luma(p: &RGBA) -> u8 {
+    ((p.r as u32 * 77 + p.g as u32 * 150 + p.b as u32 * 29) >> 8) as u8
 }
+
+// extern crate alloc;
 #[derive(Clone)]
 #[repr(C)]
 pub struct FB {
@@ -180,13 +201,6 @@ pub struct FB {
     pub w: usize,
     pub h: usize,
 }
-
-#[repr(C)]
-pub struct FBShare<'a> {
-    pub pixels: &'a mut [RGBA],
-    pub w: usize,
-    pub h: usize,
-}
 impl FB {
     pub fn new(info: &FrameBufferInfo) -> Self {
         let w = info.width;
@@ -212,68 +226,43 @@ impl FB {
         }
     }
 
-    pub fn update(&mut self, vec: *mut RGBA, w: usize, h: usize) {
-        self.pixels = unsafe { Vec::from_raw_parts(vec, h * w, w * h) };
+    ///Draw into `pixels` from now on, returns the previous ones
+    pub fn update(&mut self, pixels: Vec<RGBA>, w: usize, h: usize) -> Vec<RGBA> {
+        debug_assert!(pixels.len() == w * h);
         self.w = w;
         self.h = h;
+        core::mem::replace(&mut self.pixels, pixels)
     }
 
-    pub fn share(&mut self) -> FBShare {
-        FBShare {
-            pixels: &mut self.pixels[..],
-            w: self.w,
-            h: self.h,
+    ///Copy `rect` to the boot framebuffer, converted to its pixel format and stride.
+    ///`rect` must be inside both
+    pub fn flush(&self, framebuffer: &mut [u8], info: &FrameBufferInfo, rect: Rect) {
+        let bpp = info.bytes_per_pixel;
+        let (x, w) = (rect.x as usize, rect.w as usize);
+        for y in rect.y as usize..rect.bottom() as usize {
+            let src = &self.pixels[x + y * self.w..][..w];
+            let dst = &mut framebuffer[(x + y * info.stride) * bpp..][..w * bpp];
+            let dst = dst.chunks_exact_mut(bpp);
+            match info.pixel_format {
+                PixelFormat::Rgb => {
+                    for (d, s) in dst.zip(src) {
+                        d[..3].copy_from_slice(&[s.r, s.g, s.b]);
+                    }
+                }
+                PixelFormat::Bgr => {
+                    for (d, s) in dst.zip(src) {
+                        d[..3].copy_from_slice(&[s.b, s.g, s.r]);
+                    }
+                }
+                PixelFormat::U8 => {
+                    for (d, s) in dst.zip(src) {
+                        d[0] = luma(s);
+                    }
+                }
+                _ => {}
+            }
         }
     }
 
-    pub fn flush(&mut self, framebuffer: &mut [u8], info: &FrameBufferInfo) {
-        let mut todraw = &self.pixels;
-
-        let start = global_time_ms();
-
-        // match info.pixel_format {
-        //     PixelFormat::Bgr => {
-        //         for (idx, &i) in self.pixels.iter().enumerate() {
-        //             self.backbuffer[idx].r = i.b;
-        //             self.backbuffer[idx].g = i.g;
-        //             self.backbuffer[idx].b = i.r;
-        //         }
-        //         // todraw = &self.backbuffer;
-        //     }
-        //     _ => {}
-        // }
-
-        // let time0 = get_time_ms() - start;
-
-        // let start = get_time_ms();
-        framebuffer.copy_from_slice(unsafe {
-            slice::from_raw_parts(todraw.as_ptr() as *const u8, framebuffer.len())
-        });
-        // log::info!("step 0 FB {}ms", time0);
-        // log::info!("step 1 FB {}ms", get_time_ms() - start);
-        // for y in 0..self.h {
-        //     for x in 0..self.w {
-        //         let RGBA { r, g, b, a } = self.pixels[x + self.w * y];
-        //         let pixel_offset = y * info.stride + x;
-
-        //         let color = match info.pixel_format {
-        //             PixelFormat::Rgb => [r, g, b, 0],
-        //             PixelFormat::Bgr => [b, g, r, 0],
-        //             PixelFormat::U8 => [if (g + b + r) as usize > 200 { 0xf } else { 0 }, 0, 0, 0],
-        //             other => {
-        //                 // set a supported (but invalid) pixel format before panicking to avoid a double
-        //                 // panic; it might not be readable though
-        //                 // info.pixel_format = PixelFormat::Rgb;
-        //                 panic!("pixel format {:?} not supported in logger", other)
-        //             }
-        //         };
-        //         let bytes_per_pixel = info.bytes_per_pixel;
-        //         let byte_offset = pixel_offset * bytes_per_pixel;
-        //         framebuffer[byte_offset..(byte_offset + bytes_per_pixel)]
-        //             .copy_from_slice(&color[..bytes_per_pixel]);
-        //     }
-        // }
-    }
-
     // pub fn set(x: usize, y: usize)
 }
@@ -0,0 +1,372 @@
+//! Crash containment: an app call that faults is unwound back to the scheduler loop.
+//!
+//! `call_guarded` saves the callee-saved registers and the stack pointer before calling the
+//! app, like `setjmp`. When a fault handler sees that the faulting instruction lies in the
+//! code of the app being called, it resumes at `fomos_recover` instead of returning to the
+//! app: the stack and registers are restored, and `call_guarded` returns `Err`.
+//! An app can also give up by itself through `Context::abort`, from its panic handler,
+//! and the watchdog (`watchdog`, run by the LAPIC timer) can abort a call running past its
+//! time budget: optimistically cooperative, pessimistically preemptive.
+//! Calls nest (`call_nested`): an app calling the service of another one is guarded against
+//! faults of that service separately.
+//! The app stack frames are simply dropped, nothing of the kernel lives in them.
+//! The fault handlers are entered through a `fault_stub!`, which saves the general purpose
+//! registers of the app for the crash log.
+use core::{
+    arch::global_asm,
+    fmt,
+    sync::atomic::{AtomicBool, AtomicI32, AtomicU64, Ordering},
+};
+
+use alloc::string::String;
+use x86_64::{structures::idt::InterruptStackFrame, VirtAddr};
+
+use crate::{
+    app::{current_pid, Context, EXIT_FAULT, EXIT_PANIC, EXIT_TIMEOUT},
+    interrupts::global_time_ms,
+    library,
+};
+
+///Returned by `fomos_call_guarded` when the call was aborted, out of the `i32` range
+const RECOVERED: i64 = 1 << 32;
+///RFLAGS the app may have set that the kernel code must not inherit: TF, DF and AC.
+///The ABI wants DF clear (`memcpy` copies forward)
+const APP_FLAGS: u64 = (1 << 8) | (1 << 10) | (1 << 18);
+
+///Stack pointer saved by `fomos_call_guarded`, restored by `fomos_recover`
+#[no_mangle]
+static mut FOMOS_JMP_RSP: u64 = 0;
+
+global_asm!(
+    ".global fomos_call_guarded",
+    "fomos_call_guarded:",
+    "push rbp",
+    "push rbx",
+    "push r12",
+    "push r13",
+    "push r14",
+    "push r15",
+    "mov [rip + FOMOS_JMP_RSP], rsp",
+    // 6 pushes and the return address, realign on 16 bytes for the call
+    "sub rsp, 8",
+    "mov rax, rdi",
+    "mov rdi, rsi",
+    "call rax",
+    "add rsp, 8",
+    "movsxd rax, eax",
+    "pop r15",
+    "pop r14",
+    "pop r13",
+    "pop r12",
+    "pop rbx",
+    "pop rbp",
+    "ret",
+    ".global fomos_recover",
+    "fomos_recover:",
+    "mov rsp, [rip + FOMOS_JMP_RSP]",
+    // Reached from `abort_fn` too, without an interrupt return to reset the flags
+    "cld",
+    "pushfq",
+    "and qword ptr [rsp], {keep_flags}",
+    "popfq",
+    "mov rax, {recovered}",
+    "pop r15",
+    "pop r14",
+    "pop r13",
+    "pop r12",
+    "pop rbx",
+    "pop rbp",
+    "ret",
+    recovered = const RECOVERED,
+    // Signed, to fit the sign extended immediate of `and`
+    keep_flags = const !(APP_FLAGS as i64),
+);
+
+///General purpose registers at the last fault, in the order `fault_stub!` saves them
+#[no_mangle]
+static mut FOMOS_FAULT_REGS: [u64; 15] = [0; 15];
+const REGISTER_NAMES: [&str; 15] = [
+    "rax", "rbx", "rcx", "rdx", "rsi", "rdi", "rbp", "r8", "r9", "r10", "r11", "r12", "r13", "r14",
+    "r15",
+];
+
+///Defines the entry `$stub` of a fault: saves the registers in `FOMOS_FAULT_REGS` and jumps
+///to `$handler`, which sees the stack the CPU pushed. Set it with `set_handler_addr`
+macro_rules! fault_stub {
+    ($stub:ident, $handler:ident) => {
+        core::arch::global_asm!(
+            concat!(".global ", stringify!($stub)),
+            concat!(stringify!($stub), ":"),
+            "mov [rip + FOMOS_FAULT_REGS], rax",
+            "mov [rip + FOMOS_FAULT_REGS + 8], rbx",
+            "mov [rip + FOMOS_FAULT_REGS + 16], rcx",
+            "mov [rip + FOMOS_FAULT_REGS + 24], rdx",
+            "mov [rip + FOMOS_FAULT_REGS + 32], rsi",
+            "mov [rip + FOMOS_FAULT_REGS + 40], rdi",
+            "mov [rip + FOMOS_FAULT_REGS + 48], rbp",
+            "mov [rip + FOMOS_FAULT_REGS + 56], r8",
+            "mov [rip + FOMOS_FAULT_REGS + 64], r9",
+            "mov [rip + FOMOS_FAULT_REGS + 72], r10",
+            "mov [rip + FOMOS_FAULT_REGS + 80], r11",
+            "mov [rip + FOMOS_FAULT_REGS + 88], r12",
+            "mov [rip + FOMOS_FAULT_REGS + 96], r13",
+            "mov [rip + FOMOS_FAULT_REGS + 104], r14",
+            "mov [rip + FOMOS_FAULT_REGS + 112], r15",
+            "jmp {handler}",
+            handler = sym $handler,
+        );
+        extern "C" {
+            fn $stub();
+        }
+    };
+}
+pub(crate) use fault_stub;
+
+///`FOMOS_FAULT_REGS`, four per line
+struct Registers([u64; 15]);
+
+impl fmt::Display for Registers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, (name, value)) in REGISTER_NAMES.iter().zip(self.0).enumerate() {
+            let sep = if i % 4 == 3 { "\n" } else { " " };
+            write!(f, "{:>3} {:#018x}{}", name, value, sep)?;
+        }
+        Ok(())
+    }
+}
+
+extern "C" {
+    ///Calls `func(arg)`
+    fn fomos_call_guarded(func: usize, arg: *mut u8) -> i64;
+    fn fomos_recover() -> !;
+}
+
+static IN_CALL: AtomicBool = AtomicBool::new(false);
+static CODE_START: AtomicU64 = AtomicU64::new(0);
+static CODE_END: AtomicU64 = AtomicU64::new(0);
+///Why the call was aborted
+static STATUS: AtomicI32 = AtomicI32::new(0);
+static CALL_START_MS: AtomicU64 = AtomicU64::new(0);
+static BUDGET_MS: AtomicU64 = AtomicU64::new(0);
+static BUDGET_ABORT: AtomicBool = AtomicBool::new(false);
+static OVER_BUDGET: AtomicBool = AtomicBool::new(false);
+
+pub const DEFAULT_BUDGET_MS: u64 = 100;
+///Past this many times its budget, a call is taken as hung and aborted even if the budget
+///only flags: it would never return to be logged, and the kernel would be stuck with it
+const HUNG_FACTOR: u64 = 50;
+
+///How long one call of an app may run
+#[derive(Clone, Copy, Debug)]
+pub struct Budget {
+    pub ms: u64,
+    ///Abort the call past the budget, instead of only flagging the app
+    pub abort: bool,
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Self {
+            ms: DEFAULT_BUDGET_MS,
+            abort: false,
+        }
+    }
+}
+
+pub struct Outcome {
+    ///`Err` with the status if the call was aborted (`EXIT_FAULT`, `EXIT_PANIC`, `EXIT_TIMEOUT`)
+    pub res: Result<i32, i32>,
+    pub elapsed_ms: u64,
+    pub over_budget: bool,
+}
+
+///Call an app, watched over by the fault handlers and the watchdog
+pub fn call_guarded(
+    func: extern "C" fn(&mut Context) -> i32,
+    ctx: &mut Context,
+    code: &[u8],
+    budget: Budget,
+) -> Outcome {
+    let start = code.as_ptr() as u64;
+    CODE_START.store(start, Ordering::Relaxed);
+    CODE_END.store(start + code.len() as u64, Ordering::Relaxed);
+    BUDGET_MS.store(budget.ms, Ordering::Relaxed);
+    BUDGET_ABORT.store(budget.abort, Ordering::Relaxed);
+    OVER_BUDGET.store(false, Ordering::Relaxed);
+    let start_ms = global_time_ms();
+    CALL_START_MS.store(start_ms, Ordering::Relaxed);
+    IN_CALL.store(true, Ordering::SeqCst);
+    let res = unsafe { fomos_call_guarded(func as usize, ctx as *mut Context as *mut u8) };
+    IN_CALL.store(false, Ordering::SeqCst);
+    Outcome {
+        res: if res == RECOVERED {
+            Err(STATUS.load(Ordering::Relaxed))
+        } else {
+            Ok(res as i32)
+        },
+        elapsed_ms: global_time_ms() - start_ms,
+        over_budget: OVER_BUDGET.load(Ordering::Relaxed),
+    }
+}
+
+///Call code of another app from within an app call, like a service (see `service`),
+///guarded the same way: a fault in `code` or an abort only ends this nested call.
+///The time budget stays the one of the outer call
+pub fn call_nested(
+    func: extern "C" fn(*mut u8) -> i32,
+    arg: *mut u8,
+    code: (u64, u64),
+) -> Result<i32, i32> {
+    if !IN_CALL.load(Ordering::SeqCst) {
+        return Err(EXIT_FAULT);
+    }
+    let outer_rsp = unsafe { FOMOS_JMP_RSP };
+    let outer_code = current_code();
+    CODE_START.store(code.0, Ordering::Relaxed);
+    CODE_END.store(code.1, Ordering::Relaxed);
+    let res = unsafe { fomos_call_guarded(func as usize, arg) };
+    unsafe { FOMOS_JMP_RSP = outer_rsp };
+    CODE_START.store(outer_code.0, Ordering::Relaxed);
+    CODE_END.store(outer_code.1, Ordering::Relaxed);
+    // Cleared if the nested call was aborted, the outer one goes on
+    IN_CALL.store(true, Ordering::SeqCst);
+    if res == RECOVERED {
+        Err(STATUS.load(Ordering::Relaxed))
+    } else {
+        Ok(res as i32)
+    }
+}
+
+///Run the init function `func` of a library being loaded, whose code is `code`, guarded
+///like app code: a fault in it or an abort fails the load, not the kernel. Loading happens
+///at boot, or within the call of an app spawning another: the call is then nested in it
+pub fn call_init(func: u64, code: (u64, u64)) -> Result<(), i32> {
+    let nested = IN_CALL.load(Ordering::SeqCst);
+    let outer_rsp = unsafe { FOMOS_JMP_RSP };
+    let outer_code = current_code();
+    CODE_START.store(code.0, Ordering::Relaxed);
+    CODE_END.store(code.1, Ordering::Relaxed);
+    if !nested {
+        BUDGET_MS.store(DEFAULT_BUDGET_MS, Ordering::Relaxed);
+        BUDGET_ABORT.store(false, Ordering::Relaxed);
+        OVER_BUDGET.store(false, Ordering::Relaxed);
+        CALL_START_MS.store(global_time_ms(), Ordering::Relaxed);
+    }
+    IN_CALL.store(true, Ordering::SeqCst);
+    // Init functions take nothing and return nothing, the argument and result are ignored
+    let res = unsafe { fomos_call_guarded(func as usize, core::ptr::null_mut()) };
+    unsafe { FOMOS_JMP_RSP = outer_rsp };
+    CODE_START.store(outer_code.0, Ordering::Relaxed);
+    CODE_END.store(outer_code.1, Ordering::Relaxed);
+    IN_CALL.store(nested, Ordering::SeqCst);
+    if res == RECOVERED {
+        Err(STATUS.load(Ordering::Relaxed))
+    } else {
+        Ok(())
+    }
+}
+
+///Code range of the app being called, start and end addresses
+pub fn current_code() -> (u64, u64) {
+    (
+        CODE_START.load(Ordering::Relaxed),
+        CODE_END.load(Ordering::Relaxed),
+    )
+}
+
+///Whether `rip` is in the code of the app being called
+fn in_app_code(rip: u64) -> bool {
+    rip >= CODE_START.load(Ordering::Relaxed) && rip < CODE_END.load(Ordering::Relaxed)
+}
+
+///Whether `rip` is in code run for the app being called: its own, or a shared library's
+fn in_app(rip: u64) -> bool {
+    IN_CALL.load(Ordering::SeqCst) && (in_app_code(rip) || library::contains(rip))
+}
+
+///Make the interrupt return into `fomos_recover`, with `status` as the outcome of the call
+fn recover_on_return(stack_frame: &mut InterruptStackFrame, status: i32) {
+    IN_CALL.store(false, Ordering::SeqCst);
+    STATUS.store(status, Ordering::Relaxed);
+    unsafe {
+        stack_frame.as_mut().update(|frame| {
+            frame.instruction_pointer = VirtAddr::new(fomos_recover as usize as u64);
+            frame.stack_pointer = VirtAddr::new(FOMOS_JMP_RSP);
+            frame.cpu_flags &= !APP_FLAGS;
+        });
+    }
+}
+
+///Called by the fault handlers. If the fault comes from the code of the app being called,
+///log it and make the handler return into `fomos_recover`. Otherwise the kernel itself
+///faulted, `false`, and the handler should panic as before.
+pub fn contain_fault(
+    stack_frame: &mut InterruptStackFrame,
+    what: &str,
+    detail: fmt::Arguments,
+) -> bool {
+    let rip = stack_frame.instruction_pointer.as_u64();
+    if !in_app(rip) {
+        return false;
+    }
+    let (image, offset) = if in_app_code(rip) {
+        (
+            String::from("app"),
+            rip - CODE_START.load(Ordering::Relaxed),
+        )
+    } else {
+        library::locate(rip).unwrap_or_default()
+    };
+    let registers = Registers(unsafe { FOMOS_FAULT_REGS });
+    log::error!(
+        "pid {} crashed: {} at {} offset {:#x}, {}\n{:#?}\n{}",
+        current_pid(),
+        what,
+        image,
+        offset,
+        detail,
+        stack_frame,
+        registers
+    );
+    recover_on_return(stack_frame, EXIT_FAULT);
+    true
+}
+
+///Called by the LAPIC timer, every ms. Flags the call once past its budget, and aborts it
+///if the budget says so, or if it looks hung (`HUNG_FACTOR`). Only app code is interrupted
+///that way: inside a Context function the kernel may hold locks, the call is aborted at a
+///later tick.
+///Nothing is logged here, the interrupted code may hold the logger: `App::call` logs the
+///overrun once the call returned or was aborted.
+pub fn watchdog(stack_frame: &mut InterruptStackFrame) {
+    if !IN_CALL.load(Ordering::SeqCst) {
+        return;
+    }
+    let elapsed = global_time_ms() - CALL_START_MS.load(Ordering::Relaxed);
+    let budget = BUDGET_MS.load(Ordering::Relaxed);
+    if elapsed <= budget {
+        return;
+    }
+    OVER_BUDGET.store(true, Ordering::Relaxed);
+    let abort = BUDGET_ABORT.load(Ordering::Relaxed) || elapsed > budget * HUNG_FACTOR;
+    if abort && in_app(stack_frame.instruction_pointer.as_u64()) {
+        recover_on_return(stack_frame, EXIT_TIMEOUT);
+    }
+}
+
+///`Context::abort`. Outside of an app call there is nothing to abort, it returns
+pub extern "C" fn abort_fn(msg: *const u8, len: u32) {
+    let msg = if len == 0 {
+        &[][..]
+    } else {
+        unsafe { core::slice::from_raw_parts(msg, len as usize) }
+    };
+    let msg = core::str::from_utf8(msg).unwrap_or("(invalid utf8)");
+    log::error!("pid {} panicked: {}", current_pid(), msg);
+    if !IN_CALL.swap(false, Ordering::SeqCst) {
+        log::error!("abort outside of an app call, ignored");
+        return;
+    }
+    STATUS.store(EXIT_PANIC, Ordering::Relaxed);
+    unsafe { fomos_recover() }
+}
@@ -0,0 +1,387 @@
+//! Per-app policy: wraps or replaces the Context entries given to one app, without the app
+//! knowing. Tracing, log rate limiting, framebuffer denial and allocation fault injection.
+//!
+//! Context functions carry no user data, so the wrappers find the policy in `POLICIES` by
+//! `current_pid`: the app being called, or the owner of a service while it runs (see
+//! `service::call_fn`). An app keeps the wrapped entries it saved (`fomos_app` does), so they
+//! work outside of its own calls too, and go straight to the kernel for a pid without a policy.
+use alloc::vec::Vec;
+use hashbrown::HashMap;
+use lazy_static::lazy_static;
+use spin::Mutex;
+
+use crate::{
+    allocator,
+    app::{
+        current_pid, ClipInfo, Context, MemInfo, Message, ProcInfo, Rect, ServiceFn, Shm,
+        SurfaceInfo,
+    },
+    clipboard, compositor,
+    framebuffer::RGBA,
+    interrupts::global_time_ms,
+    ipc, process,
+    ramdisk::AppConf,
+    service, shm,
+};
+
+///Set at launch time from the app options (`apps.conf`, or `run` in the console)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Policy {
+    ///`trace=yes` logs every Context call
+    pub trace: bool,
+    ///`log_rate=N` keeps at most N log lines per second, 0 for unlimited
+    pub log_rate: u32,
+    ///`fb=deny` gives a scratch framebuffer, never shown
+    pub deny_fb: bool,
+    ///`fail_alloc=N` fails one allocation out of N, 0 never
+    pub fail_alloc: u32,
+}
+
+impl Policy {
+    pub fn from_conf(conf: &AppConf) -> Self {
+        Self {
+            trace: conf.flag("trace"),
+            log_rate: conf.number("log_rate").unwrap_or(0),
+            deny_fb: conf.option("fb") == Some("deny"),
+            fail_alloc: conf.number("fail_alloc").unwrap_or(0),
+        }
+    }
+}
+
+///What the policy remembers from one call to the next
+#[derive(Clone, Copy, Debug, Default)]
+struct State {
+    log_window_ms: u64,
+    logged_in_window: u32,
+    dropped: u64,
+    allocs: u64,
+}
+
+struct Active {
+    policy: Policy,
+    state: State,
+}
+
+impl Active {
+    ///Whether one more log line fits in the rate of `pid`
+    fn log_passes(&mut self, pid: u64) -> bool {
+        if self.policy.log_rate == 0 {
+            return true;
+        }
+        let now = global_time_ms();
+        if now >= self.state.log_window_ms + 1000 {
+            if self.state.dropped > 0 {
+                log::warn!("pid {}: {} log lines dropped", pid, self.state.dropped);
+            }
+            self.state.log_window_ms = now;
+            self.state.logged_in_window = 0;
+            self.state.dropped = 0;
+        }
+        self.state.logged_in_window += 1;
+        let pass = self.state.logged_in_window <= self.policy.log_rate;
+        if !pass {
+            self.state.dropped += 1;
+        }
+        pass
+    }
+}
+
+lazy_static! {
+    ///Apps with a policy, by pid
+    static ref POLICIES: Mutex<HashMap<u64, Active>> = Mutex::new(HashMap::new());
+}
+
+///Policy of `pid` until it exits, see `forget`
+pub fn set_policy(pid: u64, policy: Policy) {
+    let mut policies = POLICIES.lock();
+    if policy == Policy::default() {
+        policies.remove(&pid);
+    } else {
+        policies.insert(
+            pid,
+            Active {
+                policy,
+                state: State::default(),
+            },
+        );
+    }
+}
+
+///Drop the policy of an exited app
+pub fn forget(pid: u64) {
+    POLICIES.lock().remove(&pid);
+}
+
+///Pid being worked for, if its policy traces Context calls
+fn traced() -> Option<u64> {
+    let pid = current_pid();
+    POLICIES
+        .lock()
+        .get(&pid)
+        .filter(|active| active.policy.trace)
+        .map(|_| pid)
+}
+
+///Policy of one app, as given to its Context
+pub struct Interposer {
+    pub policy: Policy,
+    ///Drawn into instead of the screen with `fb=deny`
+    scratch: Vec<RGBA>,
+}
+
+impl Interposer {
+    pub fn new(policy: Policy) -> Self {
+        Self {
+            policy,
+            scratch: Vec::new(),
+        }
+    }
+
+    ///Wrap the Context entries covered by the policy for one call of the app
+    pub fn enter(&mut self, ctx: &mut Context) {
+        if self.policy == Policy::default() {
+            return;
+        }
+        if self.policy.trace || self.policy.log_rate > 0 {
+            ctx.log = log_fn;
+        }
+        if self.policy.trace || self.policy.fail_alloc > 0 {
+            ctx.calloc = calloc_fn;
+            ctx.cdalloc = cdalloc_fn;
+        }
+        if self.policy.trace {
+            ctx.spawn = spawn_fn;
+            ctx.kill = kill_fn;
+            ctx.ps = ps_fn;
+            ctx.mem = mem_fn;
+            ctx.open = open_fn;
+            ctx.send = send_fn;
+            ctx.recv = recv_fn;
+            ctx.shm_create = shm_create_fn;
+            ctx.shm_map = shm_map_fn;
+            ctx.shm_release = shm_release_fn;
+            ctx.register = register_fn;
+            ctx.lookup = lookup_fn;
+            ctx.call = call_fn;
+            ctx.clip_set = clip_set_fn;
+            ctx.clip_get = clip_get_fn;
+            ctx.clip_serial = clip_serial_fn;
+            ctx.surface_get = surface_get_fn;
+            ctx.surface_set = surface_set_fn;
+            ctx.damage = damage_fn;
+        }
+        if self.policy.deny_fb {
+            let len = ctx.fb.pixels.len();
+            self.scratch.resize(
+                len,
+                RGBA {
+                    r: 0,
+                    g: 0,
+                    b: 0,
+                    a: 0,
+                },
+            );
+            // Safety: the scratch buffer outlives the call, and is not touched during it
+            ctx.fb.pixels =
+                unsafe { core::slice::from_raw_parts_mut(self.scratch.as_mut_ptr(), len) };
+        }
+    }
+}
+
+pub extern "C" fn log_fn(s: *const u8, l: u32) {
+    let pid = current_pid();
+    let pass = POLICIES
+        .lock()
+        .get_mut(&pid)
+        .map_or(true, |active| active.log_passes(pid));
+    if pass {
+        crate::log_fn(s, l);
+    }
+}
+
+pub extern "C" fn calloc_fn(size: usize, align: usize) -> *mut u8 {
+    let pid = current_pid();
+    let (fail, trace) = POLICIES.lock().get_mut(&pid).map_or((false, false), |a| {
+        a.state.allocs += 1;
+        let fail = a.policy.fail_alloc > 0 && a.state.allocs % a.policy.fail_alloc as u64 == 0;
+        (fail, a.policy.trace)
+    });
+    let ptr = if fail {
+        core::ptr::null_mut()
+    } else {
+        allocator::app_calloc(size, align)
+    };
+    if trace || fail {
+        log::info!("[pid {}] calloc({}, {}) -> {:?}", pid, size, align, ptr);
+    }
+    ptr
+}
+
+pub extern "C" fn cdalloc_fn(ptr: *mut u8, size: usize, align: usize) {
+    if let Some(pid) = traced() {
+        log::info!("[pid {}] cdalloc({:?}, {}, {})", pid, ptr, size, align);
+    }
+    allocator::app_cdalloc(ptr, size, align)
+}
+
+extern "C" fn spawn_fn(name: *const u8, len: u32) -> i64 {
+    let res = process::spawn_fn(name, len);
+    if let Some(pid) = traced() {
+        log::info!("[pid {}] spawn(len {}) -> {}", pid, len, res);
+    }
+    res
+}
+
+extern "C" fn kill_fn(target: u64) -> i32 {
+    let res = process::kill_fn(target);
+    if let Some(pid) = traced() {
+        log::info!("[pid {}] kill({}) -> {}", pid, target, res);
+    }
+    res
+}
+
+extern "C" fn ps_fn(out: *mut ProcInfo, cap: usize) -> usize {
+    let res = process::ps_fn(out, cap);
+    if let Some(pid) = traced() {
+        log::info!("[pid {}] ps({}) -> {}", pid, cap, res);
+    }
+    res
+}
+
+extern "C" fn mem_fn(target: u64, out: *mut MemInfo) -> i32 {
+    let res = process::mem_fn(target, out);
+    if let Some(pid) = traced() {
+        log::info!("[pid {}] mem({}) -> {}", pid, target, res);
+    }
+    res
+}
+
+extern "C" fn open_fn(name: *const u8, len: u32) -> i64 {
+    let res = ipc::open_fn(name, len);
+    if let Some(pid) = traced() {
+        log::info!("[pid {}] open(len {}) -> {:#x}", pid, len, res);
+    }
+    res
+}
+
+extern "C" fn send_fn(to: u64, data: *const u8, len: u32) -> i32 {
+    let res = ipc::send_fn(to, data, len);
+    if let Some(pid) = traced() {
+        log::info!("[pid {}] send({:#x}, len {}) -> {}", pid, to, len, res);
+    }
+    res
+}
+
+extern "C" fn recv_fn(msg: *mut Message, data: *mut u8, cap: u32) -> i32 {
+    let res = ipc::recv_fn(msg, data, cap);
+    if let Some(pid) = traced() {
+        log::info!("[pid {}] recv({}) -> {}", pid, cap, res);
+    }
+    res
+}
+
+extern "C" fn shm_create_fn(name: *const u8, len: u32, size: usize, out: *mut Shm) -> i32 {
+    let res = shm::create_fn(name, len, size, out);
+    if let Some(pid) = traced() {
+        log::info!("[pid {}] shm_create(len {}, {}) -> {}", pid, len, size, res);
+    }
+    res
+}
+
+extern "C" fn shm_map_fn(name: *const u8, len: u32, out: *mut Shm) -> i32 {
+    let res = shm::map_fn(name, len, out);
+    if let Some(pid) = traced() {
+        log::info!("[pid {}] shm_map(len {}) -> {}", pid, len, res);
+    }
+    res
+}
+
+extern "C" fn shm_release_fn(id: u64) -> i32 {
+    let res = shm::release_fn(id);
+    if let Some(pid) = traced() {
+        log::info!("[pid {}] shm_release({}) -> {}", pid, id, res);
+    }
+    res
+}
+
+extern "C" fn register_fn(name: *const u8, len: u32, entry: ServiceFn) -> i32 {
+    let res = service::register_fn(name, len, entry);
+    if let Some(pid) = traced() {
+        log::info!("[pid {}] register(len {}) -> {}", pid, len, res);
+    }
+    res
+}
+
+extern "C" fn lookup_fn(name: *const u8, len: u32) -> i64 {
+    let res = service::lookup_fn(name, len);
+    if let Some(pid) = traced() {
+        log::info!("[pid {}] lookup(len {}) -> {}", pid, len, res);
+    }
+    res
+}
+
+extern "C" fn call_fn(id: u64, arg: *const u8, arg_len: u32, out: *mut u8, out_cap: u32) -> i32 {
+    let res = service::call_fn(id, arg, arg_len, out, out_cap);
+    if let Some(pid) = traced() {
+        log::info!("[pid {}] call({}, len {}) -> {}", pid, id, arg_len, res);
+    }
+    res
+}
+
+extern "C" fn clip_set_fn(info: *const ClipInfo, data: *const u8) -> i64 {
+    let res = clipboard::set_fn(info, data);
+    let (kind, len) = unsafe { ((*info).kind, (*info).len) };
+    if let Some(pid) = traced() {
+        log::info!(
+            "[pid {}] clip_set(kind {}, len {}) -> {}",
+            pid,
+            kind,
+            len,
+            res
+        );
+    }
+    res
+}
+
+extern "C" fn clip_get_fn(info: *mut ClipInfo, data: *mut u8, cap: usize) -> i32 {
+    let res = clipboard::get_fn(info, data, cap);
+    if let Some(pid) = traced() {
+        log::info!("[pid {}] clip_get(cap {}) -> {}", pid, cap, res);
+    }
+    res
+}
+
+extern "C" fn clip_serial_fn() -> u64 {
+    let res = clipboard::serial_fn();
+    if let Some(pid) = traced() {
+        log::info!("[pid {}] clip_serial() -> {}", pid, res);
+    }
+    res
+}
+
+extern "C" fn surface_get_fn(info: *mut SurfaceInfo) {
+    compositor::get_fn(info);
+    if let Some(pid) = traced() {
+        log::info!("[pid {}] surface_get() -> {:?}", pid, unsafe { *info });
+    }
+}
+
+extern "C" fn surface_set_fn(info: *const SurfaceInfo) -> i32 {
+    let res = compositor::set_fn(info);
+    if let Some(pid) = traced() {
+        log::info!(
+            "[pid {}] surface_set({:?}) -> {}",
+            pid,
+            unsafe { *info },
+            res
+        );
+    }
+    res
+}
+
+extern "C" fn damage_fn(rects: *const Rect, n: u32) {
+    compositor::damage_fn(rects, n);
+    if let Some(pid) = traced() {
+        log::info!("[pid {}] damage({} rects)", pid, n);
+    }
+}
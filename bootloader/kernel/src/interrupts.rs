@@ -4,22 +4,40 @@ use core::task::Waker;
 
 use crate::gdt;
 use crate::globals::GLOBAL;
+use crate::guard::{contain_fault, fault_stub};
 use crate::ioapic;
 
 use conquer_once::spin::OnceCell;
 use crossbeam::queue::ArrayQueue;
 use lazy_static::lazy_static;
 use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame};
+use x86_64::VirtAddr;
+
+// Faults an app can cause, see `guard`
+fault_stub!(divide_error_stub, divide_error);
+fault_stub!(invalid_opcode_stub, invalid_opcode);
+fault_stub!(bound_range_exceeded_stub, bound_range_exceeded);
+fault_stub!(general_protection_fault_stub, general_protection_fault);
+fault_stub!(stack_segment_fault_stub, stack_segment_fault_handler);
+fault_stub!(alignment_check_stub, alignment_check_handler);
+fault_stub!(page_fault_stub, page_fault_handler);
+
+///Address of a `fault_stub!` entry, for the IDT
+fn stub_addr(stub: unsafe extern "C" fn()) -> VirtAddr {
+    VirtAddr::new(stub as usize as u64)
+}
+
 lazy_static! {
     static ref IDT: InterruptDescriptorTable = {
         let mut idt = InterruptDescriptorTable::new();
         idt.breakpoint.set_handler_fn(breakpoint_handler);
-        idt.alignment_check.set_handler_fn(alignment_check);
-
-
-        idt.invalid_opcode.set_handler_fn(invalid_opcode);
-        idt.bound_range_exceeded.set_handler_fn(bound_range_exceeded);
-        idt.general_protection_fault.set_handler_fn(general_protection_fault);
+        unsafe {
+            idt.divide_error.set_handler_addr(stub_addr(divide_error_stub));
+            idt.invalid_opcode.set_handler_addr(stub_addr(invalid_opcode_stub));
+            idt.bound_range_exceeded.set_handler_addr(stub_addr(bound_range_exceeded_stub));
+            idt.general_protection_fault
+                .set_handler_addr(stub_addr(general_protection_fault_stub));
+        }
         unsafe {
             idt.double_fault.set_handler_fn(double_fault_handler)
                 .set_stack_index(gdt::DOUBLE_FAULT_IST_INDEX); // new
@@ -31,12 +49,11 @@ lazy_static! {
 
         idt.invalid_tss.set_handler_fn(invalid_tss_handler);
         idt.segment_not_present.set_handler_fn(segment_not_present_handler);
-        idt.stack_segment_fault.set_handler_fn(stack_segment_fault_handler);
-        idt.alignment_check.set_handler_fn(alignment_check_handler);
-
-
-
-        idt.page_fault.set_handler_fn(page_fault_handler);
+        unsafe {
+            idt.stack_segment_fault.set_handler_addr(stub_addr(stack_segment_fault_stub));
+            idt.alignment_check.set_handler_addr(stub_addr(alignment_check_stub));
+            idt.page_fault.set_handler_addr(stub_addr(page_fault_stub));
+        }
 
 
         for i in 32..=255{
@@ -76,11 +93,12 @@ lazy_static! {
 }
 
 pub static TIME_MS: AtomicU64 = AtomicU64::new(0);
-extern "x86-interrupt" fn lapic_timer(stack_frame: InterruptStackFrame) {
+extern "x86-interrupt" fn lapic_timer(mut stack_frame: InterruptStackFrame) {
     unsafe {
         crate::local_apic::LOCAL_APIC.get().unwrap().eoi();
     };
     let ms = 1 + TIME_MS.fetch_add(1, Ordering::Relaxed);
+    crate::guard::watchdog(&mut stack_frame);
 
     let mut arr = WAKERS.lock();
     for w in arr.iter_mut() {
@@ -208,9 +226,16 @@ extern "x86-interrupt" fn segment_not_present_handler(
     panic!("");
 }
 extern "x86-interrupt" fn stack_segment_fault_handler(
-    stack_frame: InterruptStackFrame,
+    mut stack_frame: InterruptStackFrame,
     error_code: u64,
 ) {
+    if contain_fault(
+        &mut stack_frame,
+        "stack_segment_fault",
+        format_args!("error code {}", error_code),
+    ) {
+        return;
+    }
     log::error!(
         "EXCEPTION: stack_segment_fault {}\n{:#?}",
         error_code,
@@ -219,9 +244,16 @@ extern "x86-interrupt" fn stack_segment_fault_handler(
     panic!("");
 }
 extern "x86-interrupt" fn alignment_check_handler(
-    stack_frame: InterruptStackFrame,
+    mut stack_frame: InterruptStackFrame,
     error_code: u64,
 ) {
+    if contain_fault(
+        &mut stack_frame,
+        "alignment_check",
+        format_args!("error code {}", error_code),
+    ) {
+        return;
+    }
     log::error!(
         "EXCEPTION: alignment_check {}\n{:#?}",
         error_code,
@@ -236,20 +268,36 @@ extern "x86-interrupt" fn double_fault_handler(
     panic!("EXCEPTION: DOUBLE FAULT\n{:#?}", stack_frame);
 }
 
-extern "x86-interrupt" fn alignment_check(stack_frame: InterruptStackFrame, _error_code: u64) {
-    panic!("EXCEPTION: alignment_check{:#?}", stack_frame);
+extern "x86-interrupt" fn divide_error(mut stack_frame: InterruptStackFrame) {
+    if contain_fault(&mut stack_frame, "divide_error", format_args!("")) {
+        return;
+    }
+    panic!("EXCEPTION: divide_error{:#?}", stack_frame);
 }
-extern "x86-interrupt" fn invalid_opcode(stack_frame: InterruptStackFrame) {
+extern "x86-interrupt" fn invalid_opcode(mut stack_frame: InterruptStackFrame) {
+    if contain_fault(&mut stack_frame, "invalid_opcode", format_args!("")) {
+        return;
+    }
     panic!("EXCEPTION: invalid_opcode{:#?}", stack_frame);
 }
-extern "x86-interrupt" fn bound_range_exceeded(stack_frame: InterruptStackFrame) {
+extern "x86-interrupt" fn bound_range_exceeded(mut stack_frame: InterruptStackFrame) {
+    if contain_fault(&mut stack_frame, "bound_range_exceeded", format_args!("")) {
+        return;
+    }
     panic!("EXCEPTION: bound_range_exceeded{:#?}", stack_frame);
 }
 
 extern "x86-interrupt" fn general_protection_fault(
-    stack_frame: InterruptStackFrame,
+    mut stack_frame: InterruptStackFrame,
     _error_code: u64,
 ) {
+    if contain_fault(
+        &mut stack_frame,
+        "general_protection_fault",
+        format_args!("error code {}", _error_code),
+    ) {
+        return;
+    }
     panic!(
         "EXCEPTION: general_protection_fault {}\n{:#?}",
         _error_code, stack_frame
@@ -259,11 +307,19 @@ extern "x86-interrupt" fn general_protection_fault(
 use x86_64::structures::idt::PageFaultErrorCode;
 
 extern "x86-interrupt" fn page_fault_handler(
-    stack_frame: InterruptStackFrame,
+    mut stack_frame: InterruptStackFrame,
     error_code: PageFaultErrorCode,
 ) {
     use x86_64::registers::control::Cr2;
 
+    if contain_fault(
+        &mut stack_frame,
+        "page_fault",
+        format_args!("accessed address {:?}, {:?}", Cr2::read(), error_code),
+    ) {
+        return;
+    }
+
     log::error!("EXCEPTION: PAGE FAULT");
     log::error!("Accessed Address: {:?}", Cr2::read());
     log::error!("Error Code: {:?}", error_code);
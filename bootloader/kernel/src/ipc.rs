@@ -0,0 +1,192 @@
+//! Message passing between apps: every pid has a bounded inbox held by the kernel, and apps
+//! can join named channels to reach each other without knowing pids.
+//!
+//! Messages are copied in and out, apps never share memory this way. A queued message is
+//! charged to the quota of its sender (see `allocator::charge`) until it is received.
+//! An app returning `Ret::WaitMessage` is woken by `ProcessTable::update` once its inbox
+//! is not empty.
+use alloc::{collections::VecDeque, string::String, vec::Vec};
+use hashbrown::HashMap;
+use lazy_static::lazy_static;
+use spin::Mutex;
+
+use crate::{
+    allocator,
+    app::{
+        current_pid, Message, CHANNEL_BIT, INBOX_CAPACITY, IPC_BAD_NAME, IPC_FULL, IPC_NO_MEMORY,
+        IPC_NO_TARGET, IPC_TOO_LARGE, IPC_TOO_SMALL, MAX_MESSAGE,
+    },
+    process,
+};
+
+///Longest channel name
+const MAX_NAME: usize = 64;
+
+struct Queued {
+    header: Message,
+    ///Charged to `header.from`
+    data: Vec<u8>,
+}
+
+impl Drop for Queued {
+    fn drop(&mut self) {
+        allocator::uncharge(self.header.from, self.data.len());
+    }
+}
+
+struct Channel {
+    id: u64,
+    members: Vec<u64>,
+}
+
+#[derive(Default)]
+struct Ipc {
+    inboxes: HashMap<u64, VecDeque<Queued>>,
+    channels: HashMap<String, Channel>,
+    next_channel: u64,
+}
+
+impl Ipc {
+    ///Queue a copy of `data` for `to`, or `IPC_FULL` or `IPC_NO_MEMORY`
+    fn deliver(&mut self, to: u64, header: Message, data: &[u8]) -> Result<(), i32> {
+        let inbox = self.inboxes.entry(to).or_default();
+        if inbox.len() >= INBOX_CAPACITY {
+            return Err(IPC_FULL);
+        }
+        if !allocator::charge(header.from, data.len()) {
+            return Err(IPC_NO_MEMORY);
+        }
+        let mut copy = Vec::new();
+        if copy.try_reserve_exact(data.len()).is_err() {
+            allocator::uncharge(header.from, data.len());
+            return Err(IPC_NO_MEMORY);
+        }
+        copy.extend_from_slice(data);
+        inbox.push_back(Queued { header, data: copy });
+        Ok(())
+    }
+}
+
+lazy_static! {
+    static ref IPC: Mutex<Ipc> = Mutex::new(Ipc::default());
+}
+
+///Whether `pid` has messages waiting
+pub fn has_mail(pid: u64) -> bool {
+    IPC.lock()
+        .inboxes
+        .get(&pid)
+        .is_some_and(|inbox| !inbox.is_empty())
+}
+
+///Drop the inbox and the channel memberships of an exited app
+pub fn forget(pid: u64) {
+    let mut ipc = IPC.lock();
+    if let Some(inbox) = ipc.inboxes.remove(&pid) {
+        if !inbox.is_empty() {
+            log::info!("pid {}: {} unread messages dropped", pid, inbox.len());
+        }
+    }
+    for channel in ipc.channels.values_mut() {
+        channel.members.retain(|&member| member != pid);
+    }
+    ipc.channels
+        .retain(|_, channel| !channel.members.is_empty());
+}
+
+pub extern "C" fn open_fn(name: *const u8, len: u32) -> i64 {
+    let name = if len == 0 {
+        &[][..]
+    } else {
+        unsafe { core::slice::from_raw_parts(name, len as usize) }
+    };
+    let Ok(name) = core::str::from_utf8(name) else {
+        return IPC_BAD_NAME as i64;
+    };
+    if name.is_empty() || name.len() > MAX_NAME {
+        return IPC_BAD_NAME as i64;
+    }
+    let pid = current_pid();
+    let mut ipc = IPC.lock();
+    let ipc = &mut *ipc;
+    let channel = ipc.channels.entry(String::from(name)).or_insert_with(|| {
+        ipc.next_channel += 1;
+        Channel {
+            id: CHANNEL_BIT | ipc.next_channel,
+            members: Vec::new(),
+        }
+    });
+    if !channel.members.contains(&pid) {
+        channel.members.push(pid);
+    }
+    channel.id as i64
+}
+
+pub extern "C" fn send_fn(to: u64, data: *const u8, len: u32) -> i32 {
+    if len as usize > MAX_MESSAGE {
+        return IPC_TOO_LARGE;
+    }
+    let data = if len == 0 {
+        &[][..]
+    } else {
+        unsafe { core::slice::from_raw_parts(data, len as usize) }
+    };
+    let from = current_pid();
+    let mut header = Message {
+        from,
+        channel: 0,
+        len,
+        _reserved: 0,
+    };
+    if to & CHANNEL_BIT == 0 {
+        if !process::is_running(to) {
+            return IPC_NO_TARGET;
+        }
+        return match IPC.lock().deliver(to, header, data) {
+            Ok(()) => 1,
+            Err(err) => err,
+        };
+    }
+
+    let mut ipc = IPC.lock();
+    let Some(members) = ipc
+        .channels
+        .values()
+        .find(|channel| channel.id == to)
+        .map(|channel| channel.members.clone())
+    else {
+        return IPC_NO_TARGET;
+    };
+    header.channel = to;
+    let mut delivered = 0;
+    let mut failed = None;
+    for pid in members.into_iter().filter(|&pid| pid != from) {
+        match ipc.deliver(pid, header, data) {
+            Ok(()) => delivered += 1,
+            Err(err) => failed = Some(err),
+        }
+    }
+    match failed {
+        Some(err) if delivered == 0 => err,
+        _ => delivered,
+    }
+}
+
+pub extern "C" fn recv_fn(msg: *mut Message, data: *mut u8, cap: u32) -> i32 {
+    let mut ipc = IPC.lock();
+    let Some(inbox) = ipc.inboxes.get_mut(&current_pid()) else {
+        return 0;
+    };
+    let Some(next) = inbox.front() else {
+        return 0;
+    };
+    unsafe { msg.write(next.header) };
+    if next.data.len() > cap as usize {
+        return IPC_TOO_SMALL;
+    }
+    let next = inbox.pop_front().unwrap();
+    if !next.data.is_empty() {
+        unsafe { core::ptr::copy_nonoverlapping(next.data.as_ptr(), data, next.data.len()) };
+    }
+    1
+}
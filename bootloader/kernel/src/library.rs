@@ -0,0 +1,180 @@
+//! Shared libraries: ELF shared objects of the ramdisk (`lib_*`), loaded and relocated once,
+//! whose exported symbols are bound by every app importing them.
+//!
+//! An app names its libraries like any dynamically linked program does (`DT_NEEDED`).
+//! They are loaded with the first app needing them, and unloaded once no app holds them.
+//! Symbols are looked up in the libraries of the image, then in theirs, breadth first.
+//! Once relocated, a library runs its init functions (`DT_INIT`, `DT_INIT_ARRAY`).
+//! Library code runs on behalf of the app calling it: it is watched by `guard` the same way.
+use alloc::{string::String, sync::Arc, vec::Vec};
+use hashbrown::HashMap;
+use lazy_static::lazy_static;
+use spin::Mutex;
+use xmas_elf::ElfFile;
+
+use crate::{
+    app::{swap_current_pid, KERNEL_PID},
+    exports, guard,
+    loader::{self, Image, LoadError},
+    ramdisk::{LIB_PREFIX, RAMDISK},
+};
+
+///Libraries needing libraries needing libraries... past that, it is most likely a cycle
+const MAX_DEPTH: usize = 8;
+
+pub struct Library {
+    pub name: String,
+    image: Image,
+    symbols: HashMap<String, u64>,
+    ///Libraries this one needs, kept loaded as long as it is
+    deps: Vec<Arc<Library>>,
+}
+
+impl Library {
+    pub fn symbol(&self, name: &str) -> Option<u64> {
+        self.symbols.get(name).copied()
+    }
+
+    fn contains(&self, addr: u64) -> bool {
+        let start = self.image.base();
+        addr >= start && addr < start + self.image.code.len() as u64
+    }
+}
+
+lazy_static! {
+    static ref LIBRARIES: Mutex<Vec<Arc<Library>>> = Mutex::new(Vec::new());
+}
+
+///`libs` and every library they need, directly or not, each once and breadth first:
+///where the symbols of an image linked against `libs` are looked up, in that order
+pub fn scope(libs: &[Arc<Library>]) -> Vec<Arc<Library>> {
+    fn add(scope: &mut Vec<Arc<Library>>, lib: &Arc<Library>) {
+        if !scope.iter().any(|known| Arc::ptr_eq(known, lib)) {
+            scope.push(lib.clone());
+        }
+    }
+    let mut scope = Vec::new();
+    for lib in libs {
+        add(&mut scope, lib);
+    }
+    let mut next = 0;
+    while next < scope.len() {
+        let lib = scope[next].clone();
+        for dep in lib.deps.iter() {
+            add(&mut scope, dep);
+        }
+        next += 1;
+    }
+    scope
+}
+
+///Address of `name` for an image whose libraries are `scope` (see `scope`).
+///Kernel exports first: a library cannot stand in for the kernel
+pub fn resolve(scope: &[Arc<Library>], name: &str) -> Option<u64> {
+    exports::lookup(name).or_else(|| scope.iter().find_map(|lib| lib.symbol(name)))
+}
+
+///The libraries `needed` by an image, loaded if they are not already
+pub fn open(needed: &[&str]) -> Result<Vec<Arc<Library>>, LoadError> {
+    open_at(needed, 0)
+}
+
+fn open_at(needed: &[&str], depth: usize) -> Result<Vec<Arc<Library>>, LoadError> {
+    needed
+        .iter()
+        .map(|name| {
+            // Linkers record the file name (`liblib_font.so`), the ramdisk has the crate name
+            let name = name.trim_end_matches(".so");
+            let name = name
+                .strip_prefix("lib")
+                .filter(|name| name.starts_with(LIB_PREFIX))
+                .unwrap_or(name);
+            let loaded = LIBRARIES
+                .lock()
+                .iter()
+                .find(|lib| lib.name == name)
+                .cloned();
+            match loaded {
+                Some(lib) => Ok(lib),
+                None => load(name, depth),
+            }
+        })
+        .collect()
+}
+
+fn load(name: &str, depth: usize) -> Result<Arc<Library>, LoadError> {
+    if depth >= MAX_DEPTH {
+        return Err(LoadError::LibraryCycle(String::from(name)));
+    }
+    let file = RAMDISK
+        .get()
+        .and_then(|ramdisk| ramdisk.get(name))
+        .ok_or_else(|| LoadError::MissingLibrary(String::from(name)))?;
+    let elf = ElfFile::new(file).map_err(LoadError::Parse)?;
+    loader::check_header(&elf)?;
+    let deps = open_at(&loader::needed(&elf)?, depth + 1)?;
+    let scope = scope(&deps);
+    let image = loader::load(&elf, &|symbol| resolve(&scope, symbol))?;
+    let symbols = image.exports(&elf)?.into_iter().collect();
+    // Its dependencies were loaded first, they are initialized already
+    let code = (image.base(), image.base() + image.code.len() as u64);
+    // As the kernel: what they allocate stays with the library, not with the app loading it
+    let loader_pid = swap_current_pid(KERNEL_PID);
+    let inits = loader::init_functions(&elf, &image).and_then(|inits| {
+        inits.into_iter().try_for_each(|init| {
+            guard::call_init(init, code)
+                .map_err(|status| LoadError::InitFailed(String::from(name), status))
+        })
+    });
+    swap_current_pid(loader_pid);
+    inits?;
+    let lib = Arc::new(Library {
+        name: String::from(name),
+        image,
+        symbols,
+        deps,
+    });
+    log::info!(
+        "loaded library {} at {:#x}, {} symbols",
+        lib.name,
+        lib.image.base(),
+        lib.symbols.len()
+    );
+    LIBRARIES.lock().push(lib.clone());
+    Ok(lib)
+}
+
+///Unload the libraries no app holds anymore. To call once apps were reaped
+pub fn collect() {
+    let mut libs = LIBRARIES.lock();
+    // Unloading a library can release the last hold on its dependencies
+    loop {
+        let before = libs.len();
+        libs.retain(|lib| {
+            let used = Arc::strong_count(lib) > 1;
+            if !used {
+                log::info!("unloaded library {}", lib.name);
+            }
+            used
+        });
+        if libs.len() == before {
+            break;
+        }
+    }
+}
+
+///Library whose code `addr` is in, with the offset in it.
+///Called from fault handlers: gives up rather than spin if the list is locked
+pub fn locate(addr: u64) -> Option<(String, u64)> {
+    let libs = LIBRARIES.try_lock()?;
+    libs.iter()
+        .find(|lib| lib.contains(addr))
+        .map(|lib| (lib.name.clone(), addr - lib.image.base()))
+}
+
+///Whether `addr` is in the code of a library, see `locate`
+pub fn contains(addr: u64) -> bool {
+    LIBRARIES
+        .try_lock()
+        .is_some_and(|libs| libs.iter().any(|lib| lib.contains(addr)))
+}
@@ -0,0 +1,538 @@
+//! ELF loader for app images: position independent x86_64 executables (ET_DYN).
+//!
+//! Everything read from the image is checked, a corrupt image gives a `LoadError`
+//! instead of taking the kernel down.
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    fmt,
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+};
+
+use alloc::{string::String, vec::Vec};
+use x86_64::{
+    structures::paging::{Mapper, Page, PageTableFlags, Size4KiB},
+    VirtAddr,
+};
+use xmas_elf::{
+    dynamic::{Dynamic, Tag},
+    header::{Class, Machine, Type},
+    program::{self, Flags},
+    sections::{SectionData, SectionHeader},
+    symbol_table::{Binding, DynEntry64, Entry, Type as SymbolType},
+    ElfFile,
+};
+
+use crate::{allocator::ALLOCATOR, MAPPER};
+
+const PAGE_SIZE: usize = 4096;
+///Refuse images asking for more memory than that
+const MAX_IMAGE_SIZE: usize = 256 * 1024 * 1024;
+
+const R_X86_64_NONE: u32 = 0;
+const R_X86_64_64: u32 = 1;
+const R_X86_64_GLOB_DAT: u32 = 6;
+const R_X86_64_JUMP_SLOT: u32 = 7;
+const R_X86_64_RELATIVE: u32 = 8;
+
+const SHN_UNDEF: u16 = 0;
+
+#[derive(Debug)]
+pub enum LoadError {
+    ///Rejected by the ELF parser
+    Parse(&'static str),
+    NotElf64,
+    WrongMachine,
+    ///Only position independent executables can be loaded anywhere
+    NotPie,
+    NoLoadSegment,
+    ///A segment reads past the end of the image file
+    SegmentOutOfFile {
+        vaddr: u64,
+    },
+    ///A segment has a file size larger than its memory size, or overflows
+    BadSegment {
+        vaddr: u64,
+    },
+    TooLarge(usize),
+    OutOfMemory(usize),
+    UnsupportedRelocation {
+        typ: u32,
+        offset: u64,
+    },
+    RelocationOutOfBounds {
+        offset: u64,
+    },
+    EntryOutOfBounds(u64),
+    ///An init function outside of the executable segments
+    InitOutOfBounds(u64),
+    ///A library init function faulted or aborted, with the status of the call
+    InitFailed(String, i32),
+    ///Imported by the image, but exported by neither the kernel nor its libraries
+    UnresolvedSymbol(String),
+    ///Needed by the image, but not in the ramdisk
+    MissingLibrary(String),
+    LibraryCycle(String),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Parse(e) => write!(f, "invalid elf: {}", e),
+            LoadError::NotElf64 => write!(f, "not a 64 bit elf"),
+            LoadError::WrongMachine => write!(f, "not an x86_64 elf"),
+            LoadError::NotPie => write!(f, "not a position independent executable"),
+            LoadError::NoLoadSegment => write!(f, "nothing to load"),
+            LoadError::SegmentOutOfFile { vaddr } => {
+                write!(f, "segment {:#x} is out of the file", vaddr)
+            }
+            LoadError::BadSegment { vaddr } => write!(f, "segment {:#x} is invalid", vaddr),
+            LoadError::TooLarge(size) => write!(f, "image needs {} bytes", size),
+            LoadError::OutOfMemory(size) => write!(f, "cannot allocate {} bytes", size),
+            LoadError::UnsupportedRelocation { typ, offset } => {
+                write!(f, "unsupported relocation type {} at {:#x}", typ, offset)
+            }
+            LoadError::RelocationOutOfBounds { offset } => {
+                write!(f, "relocation at {:#x} is out of the image", offset)
+            }
+            LoadError::EntryOutOfBounds(entry) => {
+                write!(f, "entry point {:#x} is out of the image", entry)
+            }
+            LoadError::InitOutOfBounds(init) => {
+                write!(f, "init function {:#x} is out of the image", init)
+            }
+            LoadError::InitFailed(name, status) => {
+                write!(f, "library {} failed to initialize ({})", name, status)
+            }
+            LoadError::UnresolvedSymbol(name) => write!(f, "undefined symbol {}", name),
+            LoadError::MissingLibrary(name) => write!(f, "library {} not found", name),
+            LoadError::LibraryCycle(name) => write!(f, "library {} depends on itself", name),
+        }
+    }
+}
+
+///Page aligned memory an image is loaded in.
+///Pages of read-only segments are mapped read-only until it is dropped.
+pub struct CodeBuf {
+    ptr: NonNull<u8>,
+    layout: Layout,
+    ///Pages made read-only by `protect`
+    protected: Vec<Page>,
+}
+
+// Owned by its App, which the kernel moves between the process queues
+unsafe impl Send for CodeBuf {}
+// Libraries are shared through an Arc: once loaded and protected their code is only
+// executed, `&CodeBuf` gives no way to write to it
+unsafe impl Sync for CodeBuf {}
+
+impl CodeBuf {
+    fn zeroed(size: usize) -> Result<Self, LoadError> {
+        let size = size.max(1);
+        let layout =
+            Layout::from_size_align(size, PAGE_SIZE).map_err(|_| LoadError::TooLarge(size))?;
+        let ptr = unsafe { ALLOCATOR.alloc_zeroed(layout) };
+        let ptr = NonNull::new(ptr).ok_or(LoadError::OutOfMemory(size))?;
+        Ok(Self {
+            ptr,
+            layout,
+            protected: Vec::new(),
+        })
+    }
+
+    ///Write protect the pages fully inside `range`, the ones on the edges may be shared
+    fn protect(&mut self, range: core::ops::Range<usize>) {
+        let base = self.ptr.as_ptr() as u64;
+        let start = VirtAddr::new(base + range.start as u64).align_up(PAGE_SIZE as u64);
+        let end = VirtAddr::new(base + range.end as u64).align_down(PAGE_SIZE as u64);
+        if start >= end {
+            return;
+        }
+        let mut mapper = MAPPER.get().unwrap().lock();
+        let pages = Page::<Size4KiB>::range(
+            Page::containing_address(start),
+            Page::containing_address(end),
+        );
+        for page in pages {
+            // Same flags as the rest of the heap, see `allocator::init_heap`, minus WRITABLE
+            let flags = PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE;
+            if let Ok(flush) = unsafe { mapper.update_flags(page, flags) } {
+                flush.flush();
+                self.protected.push(page);
+            }
+        }
+    }
+}
+
+impl Deref for CodeBuf {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self.ptr.as_ptr(), self.layout.size()) }
+    }
+}
+impl DerefMut for CodeBuf {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.layout.size()) }
+    }
+}
+
+impl Drop for CodeBuf {
+    fn drop(&mut self) {
+        if !self.protected.is_empty() {
+            let mut mapper = MAPPER.get().unwrap().lock();
+            let flags = PageTableFlags::PRESENT
+                | PageTableFlags::WRITABLE
+                | PageTableFlags::USER_ACCESSIBLE;
+            for page in self.protected.drain(..) {
+                if let Ok(flush) = unsafe { mapper.update_flags(page, flags) } {
+                    flush.flush();
+                }
+            }
+        }
+        unsafe { ALLOCATOR.dealloc(self.ptr.as_ptr(), self.layout) };
+    }
+}
+
+///A `PT_LOAD` segment, as offsets in the `CodeBuf`
+#[derive(Clone, Copy, Debug)]
+pub struct Segment {
+    pub start: usize,
+    pub end: usize,
+    pub flags: Flags,
+}
+
+pub struct Image {
+    pub code: CodeBuf,
+    ///Virtual address of the start of `code`
+    pub min_virt: u64,
+    pub segments: Vec<Segment>,
+}
+
+impl Image {
+    ///Load address of the image, what its virtual addresses are relative to
+    pub fn base(&self) -> u64 {
+        self.code.as_ptr() as u64
+    }
+
+    ///Offset in `code` of the entry point, which must be in an executable segment
+    pub fn entry(&self, elf: &ElfFile) -> Result<usize, LoadError> {
+        let entry = elf.header.pt2.entry_point();
+        let offset = entry.wrapping_sub(self.min_virt) as usize;
+        let in_exec_segment = self
+            .segments
+            .iter()
+            .any(|s| s.flags.is_execute() && offset >= s.start && offset < s.end);
+        if entry < self.min_virt || !in_exec_segment {
+            return Err(LoadError::EntryOutOfBounds(entry));
+        }
+        Ok(offset)
+    }
+
+    ///Whether the loaded address `addr` is in an executable segment
+    fn is_code(&self, addr: u64) -> bool {
+        let offset = addr.wrapping_sub(self.base()) as usize;
+        addr >= self.base()
+            && self
+                .segments
+                .iter()
+                .any(|s| s.flags.is_execute() && offset >= s.start && offset < s.end)
+    }
+
+    ///Symbols the image defines for others to import, with their loaded address
+    pub fn exports(&self, elf: &ElfFile) -> Result<Vec<(String, u64)>, LoadError> {
+        let bias = self.base().wrapping_sub(self.min_virt);
+        let syms = DynSyms::new(elf)?;
+        let mut exports = Vec::new();
+        for sym in syms.syms {
+            let public = matches!(sym.get_binding(), Ok(Binding::Global | Binding::Weak));
+            let kind = matches!(sym.get_type(), Ok(SymbolType::Func | SymbolType::Object));
+            if sym.shndx() == SHN_UNDEF || !public || !kind {
+                continue;
+            }
+            exports.push((
+                String::from(syms.name(sym)?),
+                bias.wrapping_add(sym.value()),
+            ));
+        }
+        Ok(exports)
+    }
+}
+
+pub fn check_header(elf: &ElfFile) -> Result<(), LoadError> {
+    if elf.header.pt1.class() != Class::SixtyFour {
+        return Err(LoadError::NotElf64);
+    }
+    if elf.header.pt2.machine().as_machine() != Machine::X86_64 {
+        return Err(LoadError::WrongMachine);
+    }
+    if elf.header.pt2.type_().as_type() != Type::SharedObject {
+        return Err(LoadError::NotPie);
+    }
+    Ok(())
+}
+
+///xmas_elf slices sections without checking they are in the file
+pub fn check_section(elf: &ElfFile, section: &SectionHeader) -> Result<(), LoadError> {
+    section
+        .offset()
+        .checked_add(section.size())
+        .filter(|&end| end <= elf.input.len() as u64)
+        .map(|_| ())
+        .ok_or(LoadError::Parse("section out of the file"))
+}
+
+///Entries of the dynamic section, none for a static image
+fn dynamic<'a>(elf: &ElfFile<'a>) -> Result<&'a [Dynamic<u64>], LoadError> {
+    let Some(dynamic) = elf.find_section_by_name(".dynamic") else {
+        return Ok(&[]);
+    };
+    check_section(elf, &dynamic)?;
+    let Ok(SectionData::Dynamic64(entries)) = dynamic.get_data(elf) else {
+        return Err(LoadError::Parse("dynamic section"));
+    };
+    Ok(entries)
+}
+
+///Names of the libraries the image was linked against (`DT_NEEDED`)
+pub fn needed<'a>(elf: &ElfFile<'a>) -> Result<Vec<&'a str>, LoadError> {
+    let entries = dynamic(elf)?;
+    if entries.is_empty() {
+        return Ok(Vec::new());
+    }
+    let syms = DynSyms::new(elf)?;
+    let mut needed = Vec::new();
+    for entry in entries {
+        if let (Ok(Tag::Needed), Ok(at)) = (entry.get_tag(), entry.get_val()) {
+            needed.push(syms.str_at(at as usize)?);
+        }
+    }
+    Ok(needed)
+}
+
+///Loaded addresses of the init functions of a relocated image, in the order to call them:
+///`DT_INIT`, then the entries of `DT_INIT_ARRAY`
+pub fn init_functions(elf: &ElfFile, image: &Image) -> Result<Vec<u64>, LoadError> {
+    let bias = image.base().wrapping_sub(image.min_virt);
+    let (mut init, mut array, mut array_size) = (None, None, 0);
+    for entry in dynamic(elf)? {
+        match entry.get_tag() {
+            Ok(Tag::Init) => init = entry.get_ptr().ok(),
+            Ok(Tag::InitArray) => array = entry.get_ptr().ok(),
+            Ok(Tag::InitArraySize) => array_size = entry.get_val().unwrap_or(0),
+            _ => {}
+        }
+    }
+    let mut functions = Vec::new();
+    functions.extend(init.map(|init| bias.wrapping_add(init)));
+    if let Some(array) = array {
+        for i in 0..array_size / 8 {
+            let at = array.wrapping_add(i * 8);
+            // Relocated already, the entries are loaded addresses
+            let function = read_u64(image, at.wrapping_sub(image.min_virt))
+                .ok_or(LoadError::InitOutOfBounds(at))?;
+            // Both mean nothing to call
+            if function != 0 && function != u64::MAX {
+                functions.push(function);
+            }
+        }
+    }
+    match functions.iter().find(|&&function| !image.is_code(function)) {
+        Some(&function) => Err(LoadError::InitOutOfBounds(function)),
+        None => Ok(functions),
+    }
+}
+
+///Copy the segments of `elf` in fresh memory and relocate them, apps and libraries alike.
+///Symbols the image imports are looked up with `resolve`
+pub fn load(elf: &ElfFile, resolve: &dyn Fn(&str) -> Option<u64>) -> Result<Image, LoadError> {
+    check_header(elf)?;
+    let file = elf.input;
+
+    let mut min_virt = u64::MAX;
+    let mut max_virt = 0u64;
+    for ph in elf.program_iter() {
+        if let Ok(program::Type::Load) = ph.get_type() {
+            let vaddr = ph.virtual_addr();
+            let end = vaddr
+                .checked_add(ph.mem_size())
+                .ok_or(LoadError::BadSegment { vaddr })?;
+            min_virt = min_virt.min(vaddr);
+            max_virt = max_virt.max(end);
+        }
+    }
+    if min_virt > max_virt {
+        return Err(LoadError::NoLoadSegment);
+    }
+    // The image is placed at a page boundary, like the linker expects
+    min_virt -= min_virt % PAGE_SIZE as u64;
+    let size = (max_virt - min_virt) as usize;
+    if size > MAX_IMAGE_SIZE {
+        return Err(LoadError::TooLarge(size));
+    }
+    let mut code = CodeBuf::zeroed(size)?;
+
+    let mut segments = Vec::new();
+    for ph in elf.program_iter() {
+        if !matches!(ph.get_type(), Ok(program::Type::Load)) {
+            continue;
+        }
+        let vaddr = ph.virtual_addr();
+        let file_size = ph.file_size() as usize;
+        let mem_size = ph.mem_size() as usize;
+        if file_size > mem_size {
+            return Err(LoadError::BadSegment { vaddr });
+        }
+        let src = (ph.offset() as usize)
+            .checked_add(file_size)
+            .and_then(|end| file.get(ph.offset() as usize..end))
+            .ok_or(LoadError::SegmentOutOfFile { vaddr })?;
+        let start = (vaddr - min_virt) as usize;
+        code[start..start + file_size].copy_from_slice(src);
+        // .bss, the allocation is zeroed already but say it
+        code[start + file_size..start + mem_size].fill(0);
+        segments.push(Segment {
+            start,
+            end: start + mem_size,
+            flags: ph.flags(),
+        });
+    }
+
+    let mut image = Image {
+        code,
+        min_virt,
+        segments,
+    };
+    relocate(elf, &mut image, min_virt, resolve)?;
+
+    for segment in image.segments.clone() {
+        if segment.flags.is_write() && segment.flags.is_execute() {
+            log::warn!("segment {:#x} is writable and executable", segment.start);
+        }
+        if !segment.flags.is_write() {
+            image.code.protect(segment.start..segment.end);
+        }
+    }
+    Ok(image)
+}
+
+///Dynamic symbols of an image, what its symbol relocations refer to
+struct DynSyms<'a> {
+    syms: &'a [DynEntry64],
+    names: &'a [u8],
+}
+
+impl<'a> DynSyms<'a> {
+    fn new(elf: &ElfFile<'a>) -> Result<Self, LoadError> {
+        let (Some(dynsym), Some(dynstr)) = (
+            elf.find_section_by_name(".dynsym"),
+            elf.find_section_by_name(".dynstr"),
+        ) else {
+            return Ok(Self {
+                syms: &[],
+                names: &[],
+            });
+        };
+        check_section(elf, &dynsym)?;
+        check_section(elf, &dynstr)?;
+        let Ok(SectionData::DynSymbolTable64(syms)) = dynsym.get_data(elf) else {
+            return Err(LoadError::Parse("dynamic symbol table"));
+        };
+        Ok(Self {
+            syms,
+            names: dynstr.raw_data(elf),
+        })
+    }
+
+    fn name(&self, sym: &DynEntry64) -> Result<&'a str, LoadError> {
+        self.str_at(sym.name() as usize)
+    }
+
+    ///NUL terminated string of `.dynstr`
+    fn str_at(&self, at: usize) -> Result<&'a str, LoadError> {
+        let start = self
+            .names
+            .get(at..)
+            .ok_or(LoadError::Parse("symbol name"))?;
+        let len = start
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or(LoadError::Parse("symbol name"))?;
+        core::str::from_utf8(&start[..len]).map_err(|_| LoadError::Parse("symbol name"))
+    }
+
+    ///Address of symbol `index`: in the image if it defines it, from `resolve` otherwise
+    fn address(
+        &self,
+        index: u32,
+        bias: u64,
+        resolve: &dyn Fn(&str) -> Option<u64>,
+    ) -> Result<u64, LoadError> {
+        let sym = self
+            .syms
+            .get(index as usize)
+            .ok_or(LoadError::Parse("symbol index"))?;
+        if sym.shndx() != SHN_UNDEF {
+            return Ok(bias.wrapping_add(sym.value()));
+        }
+        let name = self.name(sym)?;
+        match resolve(name) {
+            Some(addr) => Ok(addr),
+            // Weak imports are optional, the app checks them for null
+            None if matches!(sym.get_binding(), Ok(Binding::Weak)) => Ok(0),
+            None => Err(LoadError::UnresolvedSymbol(String::from(name))),
+        }
+    }
+}
+
+fn relocate(
+    elf: &ElfFile,
+    image: &mut Image,
+    min_virt: u64,
+    resolve: &dyn Fn(&str) -> Option<u64>,
+) -> Result<(), LoadError> {
+    // Virtual address 0 of the image, where its addresses are relative to
+    let bias = image.base().wrapping_sub(min_virt);
+    let syms = DynSyms::new(elf)?;
+    for name in [".rela.dyn", ".rela.plt"] {
+        let Some(section) = elf.find_section_by_name(name) else {
+            continue;
+        };
+        check_section(elf, &section)?;
+        let Ok(SectionData::Rela64(relas)) = section.get_data(elf) else {
+            return Err(LoadError::Parse("relocation section"));
+        };
+        for rela in relas {
+            let offset = rela.get_offset();
+            let addend = rela.get_addend();
+            let value = match rela.get_type() {
+                R_X86_64_NONE => continue,
+                R_X86_64_RELATIVE => bias.wrapping_add(addend),
+                R_X86_64_64 => syms
+                    .address(rela.get_symbol_table_index(), bias, resolve)?
+                    .wrapping_add(addend),
+                R_X86_64_GLOB_DAT | R_X86_64_JUMP_SLOT => {
+                    syms.address(rela.get_symbol_table_index(), bias, resolve)?
+                }
+                typ => return Err(LoadError::UnsupportedRelocation { typ, offset }),
+            };
+            write_u64(image, offset.wrapping_sub(min_virt), value)
+                .ok_or(LoadError::RelocationOutOfBounds { offset })?;
+        }
+    }
+    Ok(())
+}
+
+///Bounds checked read, at an offset of the image
+fn read_u64(image: &Image, at: u64) -> Option<u64> {
+    let at = usize::try_from(at).ok()?;
+    let bytes = image.code.get(at..at.checked_add(8)?)?;
+    Some(u64::from_le_bytes(bytes.try_into().ok()?))
+}
+
+///Bounds checked write of a relocated value, at an offset of the image
+fn write_u64(image: &mut Image, at: u64, value: u64) -> Option<()> {
+    let at = usize::try_from(at).ok()?;
+    let bytes = image.code.get_mut(at..at.checked_add(8)?)?;
+    bytes.copy_from_slice(&value.to_le_bytes());
+    Some(())
+}
@@ -11,7 +11,7 @@ pub static LOGGER: OnceCell<LockedLogger> = OnceCell::uninit();
 
 /// A logger instance protected by a spinlock.
 pub struct LockedLogger {
-    framebuffer: Option<Spinlock<FrameBufferWriter>>,
+    framebuffer: Spinlock<Option<FrameBufferWriter>>,
     serial: Option<Spinlock<SerialPort>>,
 }
 
@@ -24,8 +24,8 @@ impl LockedLogger {
         serial_logger_status: bool,
     ) -> Self {
         let framebuffer = match frame_buffer_logger_status {
-            true => Some(Spinlock::new(FrameBufferWriter::new(framebuffer, info))),
-            false => None,
+            true => Spinlock::new(Some(FrameBufferWriter::new(framebuffer, info))),
+            false => Spinlock::new(None),
         };
 
         let serial = match serial_logger_status {
@@ -44,9 +44,7 @@ impl LockedLogger {
     /// ## Safety
     /// This method is not memory safe and should be only used when absolutely necessary.
     pub unsafe fn force_unlock(&self) {
-        if let Some(framebuffer) = &self.framebuffer {
-            unsafe { framebuffer.force_unlock() };
-        }
+        unsafe { self.framebuffer.force_unlock() };
         if let Some(serial) = &self.serial {
             unsafe { serial.force_unlock() };
         }
@@ -60,9 +58,7 @@ impl log::Log for LockedLogger {
 
     fn log(&self, record: &log::Record) {
         x86_64::instructions::interrupts::without_interrupts(|| {
-            if let Some(framebuffer) = &self.framebuffer {
-                let mut framebuffer = framebuffer.lock();
-
+            if let Some(framebuffer) = self.framebuffer.lock().as_mut() {
                 if record.level() == Level::Error {
                     framebuffer.level = 1;
                 }
@@ -99,6 +95,14 @@ pub fn init_logger(
     log::set_max_level(convert_level(log_level));
     log::info!("Framebuffer info: {:?}", info);
 }
+/// Stops logging to the framebuffer and returns it, to show something else on it.
+/// `None` if the logger was not using it.
+pub fn take_framebuffer() -> Option<&'static mut [u8]> {
+    let logger = LOGGER.get()?;
+    let writer =
+        x86_64::instructions::interrupts::without_interrupts(|| logger.framebuffer.lock().take());
+    writer.map(FrameBufferWriter::into_buffer)
+}
 fn convert_level(level: LevelFilter) -> log::LevelFilter {
     match level {
         LevelFilter::Off => log::LevelFilter::Off,
@@ -37,16 +37,31 @@ use x86_64::{
 use xmas_elf::{header::Type, program, sections::SectionData, ElfFile};
 mod allocator;
 mod app;
+mod clipboard;
+mod compositor;
+mod damage;
+mod display;
 mod drivers;
+mod exports;
+mod frame;
 mod gdt;
 mod globals;
+mod guard;
+mod interpose;
 mod interrupts;
 mod ioapic;
+mod ipc;
+mod library;
+mod loader;
 mod local_apic;
 mod logger;
 mod memory;
 mod pci;
+mod process;
+mod ramdisk;
 mod serial;
+mod service;
+mod shm;
 mod task;
 mod virtio;
 /// This function is called on panic.
@@ -60,18 +75,19 @@ use bootloader_api::config::{BootloaderConfig, Mapping};
 use crate::{
     allocator::{AllocFromCtx, ALLOCATOR},
     framebuffer::FBShare,
-    interrupts::{a_sleep, global_time_ms, wait_block, Timer, TIME_MS},
+    interrupts::{global_time_ms, wait_block, Timer, TIME_MS},
     logger::init_logger,
     memory::BootInfoFrameAllocator,
     pci::Bar,
+    ramdisk::Ramdisk,
     task::{
-        executor::{qpush, yield_once},
+        executor::qpush,
         Task,
     },
     virtio::{DeviceType, Virtio},
 };
 
-extern "C" fn log_fn(s: *const u8, l: u32) {
+pub extern "C" fn log_fn(s: *const u8, l: u32) {
     unsafe {
         let slice = core::slice::from_raw_parts(s, l as usize);
         let str_slice = core::str::from_utf8_unchecked(slice);
@@ -79,20 +95,6 @@ extern "C" fn log_fn(s: *const u8, l: u32) {
     }
 }
 
-extern "C" fn calloc(size: usize, align: usize) -> *mut u8 {
-    // log::info!("alloc {} {}", size, align);
-    unsafe { ALLOCATOR.alloc(core::alloc::Layout::from_size_align(size, align).unwrap()) }
-}
-extern "C" fn cdalloc(ptr: *mut u8, size: usize, align: usize) {
-    // log::info!("dealloc {:?} {} {}", ptr, size, align);
-    unsafe {
-        ALLOCATOR.dealloc(
-            ptr,
-            core::alloc::Layout::from_size_align(size, align).unwrap(),
-        );
-    };
-}
-
 use acpi::AcpiTables;
 #[derive(Clone)]
 struct AcpiHandlerImpl;
@@ -164,17 +166,10 @@ where
 
 pub fn create_identity_virt_from_phys_n(pages: usize) -> Result<Page, MapToError<Size4KiB>> {
     with_mapper_framealloc(|mapper, frame_allocator| {
-        let first_frame = frame_allocator.allocate_frame().unwrap();
+        let first_frame = frame_allocator
+            .allocate_range(pages)
+            .ok_or(MapToError::FrameAllocationFailed)?;
         log::info!("first_frame {}", first_frame.start_address().as_u64());
-        for i in 1..pages {
-            let frame = frame_allocator.allocate_frame().unwrap();
-            let frame_start = frame.start_address().as_u64();
-
-            // log::info!("{} : {}", i, frame_start);
-            if first_frame.start_address().as_u64() + (i as u64) * 4096 != frame_start {
-                panic!("create_identity_virt_from_phys_n NON CONTIGUOUS, {}", i)
-            }
-        }
 
         for i in 0..pages {
             let addr = first_frame.start_address().as_u64() + (i as u64) * 4096;
@@ -192,6 +187,21 @@ pub fn create_identity_virt_from_phys_n(pages: usize) -> Result<Page, MapToError
     })
 }
 
+///Unmap `pages` from `create_identity_virt_from_phys_n` and give their frames back.
+///Nothing may use them anymore
+pub fn free_identity_virt_from_phys_n(first: Page, pages: usize) {
+    with_mapper_framealloc(|mapper, frame_allocator| {
+        for page in Page::range(first, first + pages as u64) {
+            match mapper.unmap(page) {
+                Ok((_, flush)) => flush.flush(),
+                Err(err) => log::error!("free_identity_virt_from_phys_n {:?}: {:?}", page, err),
+            }
+        }
+        let frame = PhysFrame::containing_address(PhysAddr::new(first.start_address().as_u64()));
+        frame_allocator.deallocate_range(frame, pages);
+    })
+}
+
 pub static BOOTLOADER_CONFIG: BootloaderConfig = {
     let mut config = BootloaderConfig::new_default();
     config.mappings.physical_memory = Some(Mapping::Dynamic);
@@ -208,12 +218,13 @@ fn kernel_main(boot_info: &'static mut BootInfo) -> ! {
     let framebuffer = boot_info.framebuffer.as_mut().unwrap();
     let fbinfo = framebuffer.info();
 
-    let fbm = framebuffer.buffer_mut();
-    let fbm2 = unsafe {
-        let p = fbm.as_mut_ptr();
-        slice::from_raw_parts_mut(p, fbinfo.byte_len)
-    };
-    init_logger(fbm, fbinfo.clone(), LevelFilter::Trace, true, true);
+    init_logger(
+        framebuffer.buffer_mut(),
+        fbinfo.clone(),
+        LevelFilter::Trace,
+        true,
+        true,
+    );
 
     // x86_64::instructions::interrupts::int3();
     let virtual_full_mapping_offset = VirtAddr::new(
@@ -273,6 +284,18 @@ fn kernel_main(boot_info: &'static mut BootInfo) -> ! {
         allocator::init_heap(mapper, frame_allocator).expect("heap initialization failed");
     });
 
+    let ramdisk = boot_info.ramdisk_addr.into_option().and_then(|addr| {
+        let data =
+            unsafe { slice::from_raw_parts(addr as *const u8, boot_info.ramdisk_len as usize) };
+        Ramdisk::parse(data)
+    });
+    let ramdisk = ramdisk.unwrap_or_else(|| {
+        log::error!("no valid ramdisk, no app to run");
+        Ramdisk::empty()
+    });
+    log::info!("ramdisk: {} apps", ramdisk.apps().count());
+    ramdisk::RAMDISK.init_once(|| ramdisk);
+
     let rsdp_addr = boot_info.rsdp_addr.into_option().expect("no rsdp");
     let acpi_tables = unsafe { AcpiTables::from_rsdp(ACPI_HANDLER, rsdp_addr as usize).unwrap() };
     log::info!("acpi_read");
@@ -380,7 +403,6 @@ fn kernel_main(boot_info: &'static mut BootInfo) -> ! {
     }
 
     let mut fb = Box::new(FB::new(&fbinfo));
-    // fb.flush(fbm2, &fbinfo);
     let fb_clone: *mut FB = &mut *fb;
     log::info!("fbclone {:?}", fb_clone);
 
@@ -388,6 +410,15 @@ fn kernel_main(boot_info: &'static mut BootInfo) -> ! {
         let mut executor = task::executor::Executor::new();
         let spawner = executor.spawner();
 
+        let has_gpu = virtio_devices
+            .iter()
+            .any(|virtio| matches!(virtio.device_type, DeviceType::Gpu));
+        if !has_gpu {
+            // Nothing else to show the frames with. The logs would draw over them, serial only
+            if let Some(framebuffer) = logger::take_framebuffer() {
+                spawner.run(drivers::boot_fb::drive(framebuffer, fbinfo, fb_clone));
+            }
+        }
         for virtio in virtio_devices.into_iter() {
             match virtio.device_type {
                 DeviceType::Input => spawner.run(drivers::virtio_input::drive(virtio)),
@@ -401,27 +432,52 @@ fn kernel_main(boot_info: &'static mut BootInfo) -> ! {
 
         spawner.run(async move {
             use app::*;
-            let mut apps: Vec<App> = Vec::new();
-            let apps_raw = [
-                &include_bytes!("../../../app_background/target/x86_64/release/func")[..],
-                &include_bytes!("../../../app_console/target/x86_64/release/func")[..],
-                &include_bytes!("../../../app_cursor/target/x86_64/release/func")[..],
-                // &include_bytes!("../../../app_test/target/x86_64/release/func")[..],
-                // &include_bytes!("../../../app_c/target/main")[..],
-            ];
-            for app_bytes in apps_raw.iter() {
-                apps.push(App::new(app_bytes, false));
+            let mut procs = process::ProcessTable::new();
+            let ramdisk = ramdisk::RAMDISK.get().unwrap();
+            let mut autostart = ramdisk.autostart();
+            if autostart.is_empty() {
+                // No config, trust the manifests
+                for (name, image) in ramdisk.apps() {
+                    let wants = ElfFile::new(image)
+                        .ok()
+                        .and_then(|elf| read_manifest(&elf))
+                        .map_or(false, |m| m.is_autostart());
+                    if wants {
+                        autostart.push(ramdisk::AppConf::new(name));
+                    }
+                }
+            }
+            for conf in autostart.iter() {
+                if conf.option("autostart") == Some("no") {
+                    continue;
+                }
+                if let Some(app) = process::load(conf) {
+                    procs.push(app);
+                }
             }
+            procs.update();
 
+            // One scheduler pass per frame
             loop {
                 let input = globals::INPUT.read();
-                for app in apps.iter_mut() {
-                    let mut arg = Context::new(log_fn, fb.share(), calloc, cdalloc, &input);
-                    app.call(&mut arg);
+                let now = global_time_ms();
+                let frame = frame::current();
+                let display = display::current();
+                compositor::update(&input);
+                for app in procs.apps_mut() {
+                    if !app.is_ready(now, &input) {
+                        continue;
+                    }
+                    let surface = compositor::share(app, fb.w, fb.h);
+                    let mut arg = new_context(log_fn, surface, &input, &frame, &display);
+                    let ret = app.call(&mut arg);
+                    app.handle_ret(ret, now, &input);
                 }
+                procs.update();
+                compositor::compose(&mut fb);
 
                 globals::INPUT.update(|e| e.step());
-                yield_once().await;
+                frame::next().await;
             }
         });
         executor.run();
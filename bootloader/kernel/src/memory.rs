@@ -1,3 +1,4 @@
+use alloc::vec::Vec;
 use arrayvec::ArrayVec;
 use x86_64::PhysAddr;
 use x86_64::{structures::paging::PageTable, VirtAddr};
@@ -46,6 +47,8 @@ pub struct BootInfoFrameAllocator {
     memory_map: &'static MemoryRegions,
     next: usize,
     buf: ArrayVec<PhysFrame, BUFFER_SIZE_ADVANCE>,
+    /// Contiguous frames given back by `deallocate_range`, as first frame and count
+    free: Vec<(PhysFrame, usize)>,
 }
 unsafe impl Send for BootInfoFrameAllocator {}
 
@@ -60,9 +63,61 @@ impl BootInfoFrameAllocator {
             memory_map,
             next: 1,
             buf: ArrayVec::new(),
+            free: Vec::new(),
         }
     }
 
+    /// `count` contiguous frames, returns the first one.
+    /// Ranges given back are reused first, frames skipped to find a contiguous run are kept for
+    /// later ranges.
+    pub fn allocate_range(&mut self, count: usize) -> Option<PhysFrame> {
+        if count == 0 {
+            return None;
+        }
+        if let Some(i) = self.free.iter().position(|&(_, n)| n >= count) {
+            let (start, n) = self.free[i];
+            if n == count {
+                self.free.swap_remove(i);
+            } else {
+                self.free[i] = (start + count as u64, n - count);
+            }
+            return Some(start);
+        }
+        let mut start = self.allocate_frame()?;
+        let mut n = 1;
+        while n < count {
+            let Some(frame) = self.allocate_frame() else {
+                self.deallocate_range(start, n);
+                return None;
+            };
+            if frame == start + n as u64 {
+                n += 1;
+            } else {
+                // The usable region ended, start over from this frame
+                self.deallocate_range(start, n);
+                start = frame;
+                n = 1;
+            }
+        }
+        Some(start)
+    }
+
+    /// Give back `count` contiguous frames from `allocate_range`, they must not be mapped anymore
+    pub fn deallocate_range(&mut self, start: PhysFrame, count: usize) {
+        let (mut start, mut count) = (start, count);
+        // Merge with the ranges right before and after
+        while let Some(i) = self
+            .free
+            .iter()
+            .position(|&(s, n)| s + n as u64 == start || start + count as u64 == s)
+        {
+            let (s, n) = self.free.swap_remove(i);
+            start = start.min(s);
+            count += n;
+        }
+        self.free.push((start, count));
+    }
+
     /// Returns an iterator over the usable frames specified in the memory map.
     fn usable_frames(&self) -> impl Iterator<Item = PhysFrame> {
         // get usable regions from memory map
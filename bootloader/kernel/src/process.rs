@@ -0,0 +1,212 @@
+//! Process table: the running apps, and what apps ask about the others through their Context.
+//!
+//! Apps are called while the table is borrowed by the scheduler loop, so `spawn` and `kill`
+//! only queue a request, applied by `ProcessTable::update` after the pass.
+//! `ps` reads the snapshot taken by the last `update`, which also wakes the apps waiting for
+//! a message (see `ipc`).
+use alloc::{collections::VecDeque, vec::Vec};
+use lazy_static::lazy_static;
+use spin::Mutex;
+
+use crate::{
+    allocator,
+    app::{App, AppState, MemInfo, ProcInfo, CONTEXT_VERSION},
+    compositor,
+    guard::{Budget, DEFAULT_BUDGET_MS},
+    interpose::{self, Interposer, Policy},
+    ipc, library,
+    ramdisk::{AppConf, RAMDISK},
+    service, shm,
+};
+pub use fomos_abi::EXIT_KILLED;
+
+///How many exited processes `ps` still lists
+const EXITED_KEEP: usize = 16;
+
+#[derive(Default)]
+struct Pending {
+    spawn: Vec<App>,
+    kill: Vec<u64>,
+}
+
+lazy_static! {
+    static ref PENDING: Mutex<Pending> = Mutex::new(Pending::default());
+    static ref SNAPSHOT: Mutex<Vec<ProcInfo>> = Mutex::new(Vec::new());
+}
+
+///Load an app of the ramdisk, `None` (and a log) if it cannot run on this kernel.
+///`conf` gives its launch options: quota, policy and time budget
+pub fn load(conf: &AppConf) -> Option<App> {
+    let name = conf.name;
+    let Some(image) = RAMDISK.get().and_then(|ramdisk| ramdisk.get(name)) else {
+        log::error!("{} is not in the ramdisk", name);
+        return None;
+    };
+    // Apps without manifest predate it, and the dynamic allocation age
+    let mut app = match App::new(image, name, 0, false) {
+        Ok(app) => app,
+        Err(err) => {
+            log::error!("cannot load {}: {}", name, err);
+            return None;
+        }
+    };
+    if app.min_context_version > CONTEXT_VERSION {
+        log::error!(
+            "{} needs Context v{}, kernel only has v{}",
+            app.name,
+            app.min_context_version,
+            CONTEXT_VERSION
+        );
+        return None;
+    }
+    allocator::set_quota(app.pid, conf.size("quota"));
+    let policy = Policy::from_conf(conf);
+    if policy != Policy::default() {
+        log::info!("{} runs with {:?}", app.name, policy);
+    }
+    interpose::set_policy(app.pid, policy);
+    app.interposer = Interposer::new(policy);
+    app.budget = Budget {
+        ms: conf
+            .number("budget")
+            .map_or(DEFAULT_BUDGET_MS, |ms| ms as u64),
+        abort: conf.option("watchdog") == Some("abort"),
+    };
+    log::info!(
+        "loaded {} {} as pid {}",
+        app.name,
+        app.manifest.as_ref().map(|m| m.version_str()).unwrap_or(""),
+        app.pid
+    );
+    Some(app)
+}
+
+pub struct ProcessTable {
+    apps: Vec<App>,
+    ///Most recent last
+    exited: VecDeque<ProcInfo>,
+}
+
+impl ProcessTable {
+    pub fn new() -> Self {
+        Self {
+            apps: Vec::new(),
+            exited: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, app: App) {
+        self.apps.push(app);
+    }
+
+    pub fn apps_mut(&mut self) -> impl Iterator<Item = &mut App> {
+        self.apps.iter_mut()
+    }
+
+    ///To call after every scheduler pass: apply the spawn and kill requests,
+    ///reap the exited apps and refresh what `ps` sees
+    pub fn update(&mut self) {
+        let (spawn, kill) = {
+            let mut pending = PENDING.lock();
+            (
+                core::mem::take(&mut pending.spawn),
+                core::mem::take(&mut pending.kill),
+            )
+        };
+        self.apps.extend(spawn);
+        for pid in kill {
+            if let Some(app) = self.apps.iter_mut().find(|app| app.pid == pid) {
+                log::info!("{} (pid {}) killed", app.name, app.pid);
+                app.state = AppState::Exited(EXIT_KILLED);
+            }
+        }
+
+        for app in self.apps.iter().filter(|app| app.has_exited()) {
+            allocator::forget_app(app.pid);
+            ipc::forget(app.pid);
+            shm::forget(app.pid);
+            service::forget(app.pid);
+            compositor::forget(app.pid);
+            interpose::forget(app.pid);
+            if self.exited.len() == EXITED_KEEP {
+                self.exited.pop_front();
+            }
+            self.exited.push_back(app.info());
+        }
+        self.apps.retain(|app| !app.has_exited());
+        library::collect();
+        for app in self.apps.iter_mut() {
+            if ipc::has_mail(app.pid) {
+                app.notify();
+            }
+        }
+
+        let mut snapshot = SNAPSHOT.lock();
+        snapshot.clear();
+        snapshot.extend(self.apps.iter().map(App::info));
+        snapshot.extend(self.exited.iter().copied());
+    }
+}
+
+///Whether `pid` was alive at the last `ProcessTable::update`
+pub fn is_running(pid: u64) -> bool {
+    SNAPSHOT
+        .lock()
+        .iter()
+        .any(|info| info.pid == pid && info.is_alive())
+}
+
+///`name` is the app name, optionally followed by `key=value` launch options
+///overriding the ones of `apps.conf`
+pub extern "C" fn spawn_fn(name: *const u8, len: u32) -> i64 {
+    let line = if len == 0 {
+        &[][..]
+    } else {
+        unsafe { core::slice::from_raw_parts(name, len as usize) }
+    };
+    let Ok(line) = core::str::from_utf8(line) else {
+        return -1;
+    };
+    let mut conf = AppConf::parse(line);
+    if let Some(defaults) = RAMDISK.get().and_then(|ramdisk| ramdisk.conf(conf.name)) {
+        conf.merge(defaults);
+    }
+    match load(&conf) {
+        Some(app) => {
+            let pid = app.pid;
+            PENDING.lock().spawn.push(app);
+            pid as i64
+        }
+        None => -1,
+    }
+}
+
+pub extern "C" fn kill_fn(pid: u64) -> i32 {
+    let running = is_running(pid);
+    let mut pending = PENDING.lock();
+    let spawning = pending.spawn.iter().any(|app| app.pid == pid);
+    if !running && !spawning {
+        return -1;
+    }
+    pending.kill.push(pid);
+    0
+}
+
+pub extern "C" fn ps_fn(out: *mut ProcInfo, cap: usize) -> usize {
+    let snapshot = SNAPSHOT.lock();
+    let count = snapshot.len().min(cap);
+    if count > 0 {
+        unsafe { core::ptr::copy_nonoverlapping(snapshot.as_ptr(), out, count) };
+    }
+    snapshot.len()
+}
+
+pub extern "C" fn mem_fn(pid: u64, out: *mut MemInfo) -> i32 {
+    match allocator::app_mem(pid) {
+        Some(mem) if is_running(pid) => {
+            unsafe { out.write(mem.info()) };
+            0
+        }
+        _ => -1,
+    }
+}
@@ -0,0 +1,154 @@
+use alloc::vec::Vec;
+use conquer_once::spin::OnceCell;
+
+///Packed by `bootloader/build.rs`
+const RAMDISK_MAGIC: &[u8; 8] = b"FOMOSRD1";
+///Name of the autostart config inside the ramdisk
+pub const APPS_CONF: &str = "apps.conf";
+///Shared libraries are packed next to the apps, see `library`
+pub const LIB_PREFIX: &str = "lib_";
+
+pub static RAMDISK: OnceCell<Ramdisk> = OnceCell::uninit();
+
+///Named files loaded by the bootloader next to the kernel
+pub struct Ramdisk {
+    files: Vec<(&'static str, &'static [u8])>,
+}
+
+fn read_u32(data: &[u8], at: usize) -> Option<u32> {
+    let bytes = data.get(at..at + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+impl Ramdisk {
+    pub fn parse(data: &'static [u8]) -> Option<Ramdisk> {
+        if data.get(..8)? != RAMDISK_MAGIC {
+            return None;
+        }
+        let count = read_u32(data, 8)? as usize;
+        // Each file takes at least its 8 byte header, a corrupt count fails below
+        let mut files = Vec::with_capacity(count.min(data.len() / 8));
+        let mut at = 12;
+        for _ in 0..count {
+            let name_len = read_u32(data, at)? as usize;
+            let data_len = read_u32(data, at + 4)? as usize;
+            at += 8;
+            let name = core::str::from_utf8(data.get(at..at + name_len)?).ok()?;
+            at += name_len;
+            let file = data.get(at..at + data_len)?;
+            at += data_len;
+            files.push((name, file));
+        }
+        Some(Ramdisk { files })
+    }
+
+    pub fn empty() -> Ramdisk {
+        Ramdisk { files: Vec::new() }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&'static [u8]> {
+        self.files
+            .iter()
+            .find(|(file_name, _)| *file_name == name)
+            .map(|(_, data)| *data)
+    }
+
+    ///Every app image, that is every file but the config and the shared libraries
+    pub fn apps(&self) -> impl Iterator<Item = (&'static str, &'static [u8])> + '_ {
+        self.files
+            .iter()
+            .copied()
+            .filter(|(name, _)| *name != APPS_CONF && !name.starts_with(LIB_PREFIX))
+    }
+
+    ///Apps to start at boot, in order
+    pub fn autostart(&self) -> Vec<AppConf<'static>> {
+        match self.get(APPS_CONF) {
+            Some(conf) => parse_conf(core::str::from_utf8(conf).unwrap_or("")),
+            None => Vec::new(),
+        }
+    }
+
+    ///Options of an app in `apps.conf`, whether it autostarts or is spawned later
+    pub fn conf(&self, name: &str) -> Option<AppConf<'static>> {
+        self.autostart().into_iter().find(|conf| conf.name == name)
+    }
+}
+
+///One line of `apps.conf`, or one launch request: the app name, then `key=value` options
+#[derive(Debug, Clone)]
+pub struct AppConf<'a> {
+    pub name: &'a str,
+    pub options: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> AppConf<'a> {
+    pub fn new(name: &'a str) -> Self {
+        Self {
+            name,
+            options: Vec::new(),
+        }
+    }
+    pub fn parse(line: &'a str) -> Self {
+        let mut words = line.split_whitespace();
+        let mut conf = AppConf::new(words.next().unwrap_or(line));
+        for word in words {
+            match word.split_once('=') {
+                Some(option) => conf.options.push(option),
+                None => log::warn!("{}: ignored option {}", conf.name, word),
+            }
+        }
+        conf
+    }
+    ///Options already set win over the ones of `other`
+    pub fn merge(&mut self, other: AppConf<'a>) {
+        self.options.extend(other.options);
+    }
+    pub fn option(&self, key: &str) -> Option<&'a str> {
+        self.options
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, value)| *value)
+    }
+    pub fn flag(&self, key: &str) -> bool {
+        matches!(self.option(key), Some("yes" | "on" | "1" | "true"))
+    }
+    pub fn number(&self, key: &str) -> Option<u32> {
+        let value = self.option(key)?;
+        let n = value.parse().ok();
+        if n.is_none() {
+            log::warn!("{}: invalid {}={}", self.name, key, value);
+        }
+        n
+    }
+    ///A size option, in bytes, with an optional K, M or G suffix
+    pub fn size(&self, key: &str) -> Option<usize> {
+        let value = self.option(key)?;
+        let (digits, unit) = match value.as_bytes().last()? {
+            b'K' | b'k' => (&value[..value.len() - 1], 1 << 10),
+            b'M' | b'm' => (&value[..value.len() - 1], 1 << 20),
+            b'G' | b'g' => (&value[..value.len() - 1], 1 << 30),
+            _ => (value, 1),
+        };
+        match digits
+            .parse::<usize>()
+            .ok()
+            .and_then(|n| n.checked_mul(unit))
+        {
+            Some(size) => Some(size),
+            None => {
+                log::warn!("{}: invalid {}={}", self.name, key, value);
+                None
+            }
+        }
+    }
+}
+
+///One app per line, `#` starts a comment
+fn parse_conf(conf: &'static str) -> Vec<AppConf<'static>> {
+    conf.lines()
+        .map(|line| line.split('#').next().unwrap_or("").trim())
+        .filter(|line| !line.is_empty())
+        .map(AppConf::parse)
+        .collect()
+}
@@ -0,0 +1,169 @@
+//! Service registry: apps offering functions to other apps by name (`font.render`,
+//! `clipboard.get`), called synchronously through the kernel.
+//!
+//! The kernel remembers which pid owns each service and drops its services when it exits,
+//! so a stale id gives `SERVICE_GONE` instead of a jump into freed code.
+//! A service runs as its owner (`current_pid`) and is guarded like an app call: if it
+//! crashes, only that call fails, and the service is unregistered.
+//! What it allocates during the call and does not free is the caller's (`allocator::hand_over`).
+use alloc::string::String;
+use hashbrown::HashMap;
+use lazy_static::lazy_static;
+use spin::Mutex;
+
+use crate::{
+    allocator,
+    app::{
+        current_pid, swap_current_pid, ServiceFn, SERVICE_BAD_ENTRY, SERVICE_BAD_NAME,
+        SERVICE_EXISTS, SERVICE_FAULT, SERVICE_GONE,
+    },
+    guard,
+};
+
+///Longest service name
+const MAX_NAME: usize = 64;
+
+#[derive(Clone, Copy)]
+struct Service {
+    id: u64,
+    owner: u64,
+    entry: ServiceFn,
+    ///Code range of the owner, see `guard::call_nested`
+    code: (u64, u64),
+}
+
+#[derive(Default)]
+struct Registry {
+    by_name: HashMap<String, Service>,
+    next_id: u64,
+}
+
+lazy_static! {
+    static ref REGISTRY: Mutex<Registry> = Mutex::new(Registry::default());
+}
+
+fn read_name<'a>(name: *const u8, len: u32) -> Option<&'a str> {
+    let name = if len == 0 {
+        &[][..]
+    } else {
+        unsafe { core::slice::from_raw_parts(name, len as usize) }
+    };
+    let name = core::str::from_utf8(name).ok()?;
+    (!name.is_empty() && name.len() <= MAX_NAME).then_some(name)
+}
+
+///Drop the services of an exited app
+pub fn forget(pid: u64) {
+    REGISTRY.lock().by_name.retain(|name, service| {
+        if service.owner == pid {
+            log::info!("service {} of pid {} unregistered", name, pid);
+        }
+        service.owner != pid
+    });
+}
+
+pub extern "C" fn register_fn(name: *const u8, len: u32, entry: ServiceFn) -> i32 {
+    let Some(name) = read_name(name, len) else {
+        return SERVICE_BAD_NAME;
+    };
+    let owner = current_pid();
+    let code = guard::current_code();
+    let addr = entry as usize as u64;
+    if addr < code.0 || addr >= code.1 {
+        return SERVICE_BAD_ENTRY;
+    }
+    let mut registry = REGISTRY.lock();
+    let id = match registry.by_name.get(name) {
+        Some(service) if service.owner != owner => return SERVICE_EXISTS,
+        // Ids already looked up keep working
+        Some(service) => service.id,
+        None => {
+            registry.next_id += 1;
+            registry.next_id
+        }
+    };
+    let service = Service {
+        id,
+        owner,
+        entry,
+        code,
+    };
+    registry.by_name.insert(String::from(name), service);
+    log::info!("pid {} registered service {}", owner, name);
+    0
+}
+
+pub extern "C" fn lookup_fn(name: *const u8, len: u32) -> i64 {
+    let Some(name) = read_name(name, len) else {
+        return SERVICE_BAD_NAME as i64;
+    };
+    match REGISTRY.lock().by_name.get(name) {
+        Some(service) => service.id as i64,
+        None => SERVICE_GONE as i64,
+    }
+}
+
+///Arguments of a service call, passed through `guard::call_nested`
+struct Call {
+    entry: ServiceFn,
+    arg: *const u8,
+    arg_len: u32,
+    out: *mut u8,
+    out_cap: u32,
+}
+
+extern "C" fn trampoline(call: *mut u8) -> i32 {
+    let call = unsafe { &*(call as *const Call) };
+    (call.entry)(call.arg, call.arg_len, call.out, call.out_cap)
+}
+
+pub extern "C" fn call_fn(
+    id: u64,
+    arg: *const u8,
+    arg_len: u32,
+    out: *mut u8,
+    out_cap: u32,
+) -> i32 {
+    let Some(service) = REGISTRY
+        .lock()
+        .by_name
+        .values()
+        .find(|service| service.id == id)
+        .copied()
+    else {
+        return SERVICE_GONE;
+    };
+    let mut call = Call {
+        entry: service.entry,
+        arg,
+        arg_len,
+        out,
+        out_cap,
+    };
+    // The policy of the owner applies too, `interpose` looks it up by pid
+    let caller = swap_current_pid(service.owner);
+    let mark = allocator::mark();
+    let res = guard::call_nested(trampoline, &mut call as *mut Call as *mut u8, service.code);
+    swap_current_pid(caller);
+    // Blocks the service returned in `out` are the caller's to free, they stay if the owner
+    // restarts or exits. So is anything else it allocated and did not free during the call:
+    // a service keeps its own state in memory from its app calls (see `ServiceFn`)
+    allocator::hand_over(service.owner, caller, mark);
+    match res {
+        Ok(res) => res,
+        Err(status) => {
+            log::error!(
+                "service {} of pid {} failed with status {} while called by pid {}",
+                service.id,
+                service.owner,
+                status,
+                caller
+            );
+            REGISTRY
+                .lock()
+                .by_name
+                .retain(|_, registered| registered.id != service.id);
+            SERVICE_FAULT
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! Shared memory regions: named blocks of the kernel heap several apps can map, for data too
+//! large to copy through `ipc` (images, audio buffers, documents).
+//!
+//! A region counts the holds of each pid on it, and is freed once nobody holds it anymore,
+//! its name can then be reused. Exited apps lose their holds.
+//! A region is charged to the quota of its creator (see `allocator::charge`) until it is freed.
+//! Like the rest of the heap, nothing stops an app from using a region after releasing it:
+//! the holds only decide how long the memory lives.
+use alloc::{alloc::Layout, string::String};
+use core::alloc::GlobalAlloc;
+use hashbrown::HashMap;
+use lazy_static::lazy_static;
+use spin::Mutex;
+
+use crate::{
+    allocator::{self, ALLOCATOR},
+    app::{
+        current_pid, Shm, SHM_BAD_NAME, SHM_BAD_SIZE, SHM_EXISTS, SHM_MAX_SIZE, SHM_NOT_FOUND,
+        SHM_NO_MEMORY,
+    },
+};
+
+///Longest region name
+const MAX_NAME: usize = 64;
+
+struct Region {
+    id: u64,
+    ///Pid the region is charged to
+    creator: u64,
+    ptr: *mut u8,
+    layout: Layout,
+    ///Holds by pid
+    holders: HashMap<u64, u32>,
+}
+
+// Regions are only touched under the `REGIONS` lock
+unsafe impl Send for Region {}
+
+impl Region {
+    fn map(&mut self, pid: u64) -> Shm {
+        *self.holders.entry(pid).or_default() += 1;
+        Shm {
+            id: self.id,
+            ptr: self.ptr,
+            size: self.layout.size() as u64,
+        }
+    }
+}
+
+impl Drop for Region {
+    fn drop(&mut self) {
+        unsafe { ALLOCATOR.dealloc(self.ptr, self.layout) };
+        allocator::uncharge(self.creator, self.layout.size());
+    }
+}
+
+#[derive(Default)]
+struct Regions {
+    by_name: HashMap<String, Region>,
+    next_id: u64,
+}
+
+impl Regions {
+    ///Free the regions nobody holds
+    fn collect(&mut self) {
+        self.by_name.retain(|name, region| {
+            let held = !region.holders.is_empty();
+            if !held {
+                log::info!("shared memory {} freed", name);
+            }
+            held
+        });
+    }
+}
+
+lazy_static! {
+    static ref REGIONS: Mutex<Regions> = Mutex::new(Regions::default());
+}
+
+fn read_name<'a>(name: *const u8, len: u32) -> Option<&'a str> {
+    let name = if len == 0 {
+        &[][..]
+    } else {
+        unsafe { core::slice::from_raw_parts(name, len as usize) }
+    };
+    let name = core::str::from_utf8(name).ok()?;
+    (!name.is_empty() && name.len() <= MAX_NAME).then_some(name)
+}
+
+///Drop every hold of an exited app
+pub fn forget(pid: u64) {
+    let mut regions = REGIONS.lock();
+    for region in regions.by_name.values_mut() {
+        region.holders.remove(&pid);
+    }
+    regions.collect();
+}
+
+pub extern "C" fn create_fn(name: *const u8, len: u32, size: usize, out: *mut Shm) -> i32 {
+    let Some(name) = read_name(name, len) else {
+        return SHM_BAD_NAME;
+    };
+    if size == 0 || size > SHM_MAX_SIZE {
+        return SHM_BAD_SIZE;
+    }
+    let mut regions = REGIONS.lock();
+    if regions.by_name.contains_key(name) {
+        return SHM_EXISTS;
+    }
+    let Ok(layout) = Layout::from_size_align(size, 4096) else {
+        return SHM_BAD_SIZE;
+    };
+    let pid = current_pid();
+    if !allocator::charge(pid, layout.size()) {
+        return SHM_NO_MEMORY;
+    }
+    let ptr = unsafe { ALLOCATOR.alloc_zeroed(layout) };
+    if ptr.is_null() {
+        allocator::uncharge(pid, layout.size());
+        return SHM_NO_MEMORY;
+    }
+    regions.next_id += 1;
+    let mut region = Region {
+        id: regions.next_id,
+        creator: pid,
+        ptr,
+        layout,
+        holders: HashMap::new(),
+    };
+    unsafe { out.write(region.map(pid)) };
+    regions.by_name.insert(String::from(name), region);
+    0
+}
+
+pub extern "C" fn map_fn(name: *const u8, len: u32, out: *mut Shm) -> i32 {
+    let Some(name) = read_name(name, len) else {
+        return SHM_BAD_NAME;
+    };
+    match REGIONS.lock().by_name.get_mut(name) {
+        Some(region) => {
+            unsafe { out.write(region.map(current_pid())) };
+            0
+        }
+        None => SHM_NOT_FOUND,
+    }
+}
+
+pub extern "C" fn release_fn(id: u64) -> i32 {
+    let pid = current_pid();
+    let mut regions = REGIONS.lock();
+    let Some(region) = regions.by_name.values_mut().find(|region| region.id == id) else {
+        return SHM_NOT_FOUND;
+    };
+    match region.holders.get_mut(&pid) {
+        Some(holds) if *holds > 1 => *holds -= 1,
+        Some(_) => {
+            region.holders.remove(&pid);
+        }
+        None => return SHM_NOT_FOUND,
+    }
+    regions.collect();
+    0
+}
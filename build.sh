@@ -1,4 +1,8 @@
 #!/bin/bash
+# Shared libraries first, apps link against them
+cd lib_font
+RUSTFLAGS="-C relocation-model=pic" cargo build --release
+cd ..
 cd app_background 
 RUSTFLAGS="-C relocation-model=pie -C link-arg=-pie" cargo build --release
 cd ..
@@ -0,0 +1,554 @@
+//! Writes `fomos.h`, the C view of this crate, for `app_c`.
+//!
+//! `cargo run --bin gen_c_header > ../app_c/fomos.h`
+//!
+//! Field offsets and sizes are taken from the Rust types and asserted in the header, so a C
+//! declaration that drifts from the Rust one fails to compile instead of reading garbage.
+use core::mem::{offset_of, size_of};
+use std::fmt::Write;
+
+use fomos_abi::*;
+
+struct Field {
+    decl: &'static str,
+    ///Offset of the matching Rust field, `None` for a part of one (slice length)
+    offset: Option<usize>,
+}
+
+struct Struct {
+    name: &'static str,
+    doc: &'static str,
+    size: usize,
+    fields: Vec<Field>,
+}
+
+macro_rules! field {
+    ($t:ty, $f:ident, $decl:expr) => {
+        Field {
+            decl: $decl,
+            offset: Some(offset_of!($t, $f)),
+        }
+    };
+}
+
+///Field name of a C declaration, `int (*log)(..)` or `uint8_t keys[N]`
+fn field_name(decl: &str) -> &str {
+    let decl = match decl.find("(*") {
+        Some(start) => &decl[start + 2..decl.find(')').unwrap()],
+        None => decl.rsplit([' ', '*']).next().unwrap(),
+    };
+    decl.split('[').next().unwrap()
+}
+
+fn structs() -> Vec<Struct> {
+    vec![
+        Struct {
+            name: "RGBA",
+            doc: "One framebuffer pixel",
+            size: size_of::<RGBA>(),
+            fields: vec![
+                field!(RGBA, r, "uint8_t r"),
+                field!(RGBA, g, "uint8_t g"),
+                field!(RGBA, b, "uint8_t b"),
+                field!(RGBA, a, "uint8_t a"),
+            ],
+        },
+        Struct {
+            name: "FB",
+            doc: "Framebuffer shared with the app for the duration of one call",
+            size: size_of::<FB>(),
+            fields: vec![
+                field!(FB, pixels, "RGBA *pixels"),
+                Field {
+                    decl: "size_t len",
+                    offset: None,
+                },
+                field!(FB, w, "size_t w"),
+                field!(FB, h, "size_t h"),
+            ],
+        },
+        Struct {
+            name: "InputEvent",
+            doc: "Key press (trigger) or release, with its Linux evdev code",
+            size: size_of::<InputEvent>(),
+            fields: vec![
+                field!(InputEvent, trigger, "bool trigger"),
+                field!(InputEvent, key, "size_t key"),
+            ],
+        },
+        Struct {
+            name: "Input",
+            doc: "Mouse position, state of every key (KEY_STATE_*) and the last key events",
+            size: size_of::<Input>(),
+            fields: vec![
+                field!(Input, mouse_x, "size_t mouse_x"),
+                field!(Input, mouse_y, "size_t mouse_y"),
+                field!(Input, keys, "uint8_t keys[KEY_COUNT]"),
+                field!(Input, history_last_index, "size_t history_last_index"),
+                field!(Input, history_ring, "InputEvent history_ring[HISTORY_SIZE]"),
+            ],
+        },
+        Struct {
+            name: "ProcInfo",
+            doc: "One entry of the process table, see `ps`",
+            size: size_of::<ProcInfo>(),
+            fields: vec![
+                field!(ProcInfo, pid, "uint64_t pid"),
+                field!(ProcInfo, start_time, "uint64_t start_time"),
+                field!(ProcInfo, calls, "uint64_t calls"),
+                field!(ProcInfo, exit_status, "int32_t exit_status"),
+                field!(ProcInfo, state, "uint8_t state"),
+                field!(ProcInfo, flags, "uint8_t flags"),
+                field!(ProcInfo, _reserved, "uint8_t _reserved[2]"),
+                field!(ProcInfo, name, "char name[32]"),
+            ],
+        },
+        Struct {
+            name: "MemInfo",
+            doc: "Memory accounting of one process, see `mem`",
+            size: size_of::<MemInfo>(),
+            fields: vec![
+                field!(MemInfo, live, "uint64_t live"),
+                field!(MemInfo, peak, "uint64_t peak"),
+                field!(MemInfo, allocs, "uint64_t allocs"),
+                field!(MemInfo, frees, "uint64_t frees"),
+                field!(MemInfo, failed, "uint64_t failed"),
+                field!(MemInfo, quota, "uint64_t quota"),
+            ],
+        },
+        Struct {
+            name: "Message",
+            doc: "Header of a message, see `recv`",
+            size: size_of::<Message>(),
+            fields: vec![
+                field!(Message, from, "uint64_t from"),
+                field!(Message, channel, "uint64_t channel"),
+                field!(Message, len, "uint32_t len"),
+                field!(Message, _reserved, "uint32_t _reserved"),
+            ],
+        },
+        Struct {
+            name: "Shm",
+            doc: "A shared memory region, see `shm_create`",
+            size: size_of::<Shm>(),
+            fields: vec![
+                field!(Shm, id, "uint64_t id"),
+                field!(Shm, ptr, "uint8_t *ptr"),
+                field!(Shm, size, "uint64_t size"),
+            ],
+        },
+        Struct {
+            name: "ClipInfo",
+            doc: "What the clipboard holds, see `clip_get`",
+            size: size_of::<ClipInfo>(),
+            fields: vec![
+                field!(ClipInfo, kind, "uint32_t kind"),
+                field!(ClipInfo, w, "uint32_t w"),
+                field!(ClipInfo, h, "uint32_t h"),
+                field!(ClipInfo, _reserved, "uint32_t _reserved"),
+                field!(ClipInfo, len, "uint64_t len"),
+                field!(ClipInfo, serial, "uint64_t serial"),
+            ],
+        },
+        Struct {
+            name: "SurfaceInfo",
+            doc: "Where and how the app surface (`fb`) is shown, see `surface_set`",
+            size: size_of::<SurfaceInfo>(),
+            fields: vec![
+                field!(SurfaceInfo, x, "int32_t x"),
+                field!(SurfaceInfo, y, "int32_t y"),
+                field!(SurfaceInfo, w, "uint32_t w"),
+                field!(SurfaceInfo, h, "uint32_t h"),
+                field!(SurfaceInfo, layer, "uint32_t layer"),
+                field!(SurfaceInfo, z, "uint32_t z"),
+                field!(SurfaceInfo, opacity, "uint8_t opacity"),
+                field!(SurfaceInfo, _reserved, "uint8_t _reserved[7]"),
+            ],
+        },
+        Struct {
+            name: "Rect",
+            doc: "Pixels of the app surface changed by a call, see `damage`",
+            size: size_of::<Rect>(),
+            fields: vec![
+                field!(Rect, x, "uint32_t x"),
+                field!(Rect, y, "uint32_t y"),
+                field!(Rect, w, "uint32_t w"),
+                field!(Rect, h, "uint32_t h"),
+            ],
+        },
+        Struct {
+            name: "Frame",
+            doc: "Frame clock as seen by the app, see `frame`",
+            size: size_of::<Frame>(),
+            fields: vec![
+                field!(Frame, number, "uint64_t number"),
+                field!(Frame, presented_ms, "uint64_t presented_ms"),
+                field!(Frame, delta_ms, "uint32_t delta_ms"),
+                field!(Frame, refresh_hz, "uint32_t refresh_hz"),
+            ],
+        },
+        Struct {
+            name: "Display",
+            doc: "Screens the framebuffer is shown on, see `display`",
+            size: size_of::<Display>(),
+            fields: vec![
+                field!(Display, serial, "uint64_t serial"),
+                field!(Display, w, "uint32_t w"),
+                field!(Display, h, "uint32_t h"),
+                field!(Display, count, "uint32_t count"),
+                field!(Display, _reserved, "uint32_t _reserved"),
+                field!(Display, screens, "Rect screens[16]"),
+            ],
+        },
+        Struct {
+            name: "Manifest",
+            doc: "Describes the app to the kernel, see `FOMOS_MANIFEST`",
+            size: size_of::<Manifest>(),
+            fields: vec![
+                field!(Manifest, magic, "char magic[8]"),
+                field!(Manifest, size, "uint32_t size"),
+                field!(Manifest, min_context_version, "uint8_t min_context_version"),
+                field!(Manifest, context_version, "uint8_t context_version"),
+                field!(Manifest, _reserved, "uint8_t _reserved[2]"),
+                field!(Manifest, flags, "uint32_t flags"),
+                field!(Manifest, window_w, "uint32_t window_w"),
+                field!(Manifest, window_h, "uint32_t window_h"),
+                field!(Manifest, layer, "uint32_t layer"),
+                field!(Manifest, caps, "uint64_t caps"),
+                field!(Manifest, name, "char name[32]"),
+                field!(Manifest, version, "char version[16]"),
+            ],
+        },
+        Struct {
+            name: "Context",
+            doc: "Argument of every call of `_start`. Fields past `size` were not given by \
+                  the kernel, check `fomos_has` first",
+            size: size_of::<Context>(),
+            fields: vec![
+                field!(Context, version, "uint8_t version"),
+                field!(Context, _reserved, "uint8_t _reserved[3]"),
+                field!(Context, size, "uint32_t size"),
+                field!(Context, start_time, "uint64_t start_time"),
+                field!(Context, log, "void (*log)(const char *s, uint32_t len)"),
+                field!(Context, pid, "uint64_t pid"),
+                field!(Context, fb, "FB fb"),
+                field!(Context, calloc, "void *(*calloc)(size_t size, size_t align)"),
+                field!(
+                    Context,
+                    cdalloc,
+                    "void (*cdalloc)(void *ptr, size_t size, size_t align)"
+                ),
+                // Option<Box<T>>: null, or a pointer the kernel keeps between calls.
+                // It never drops it, on `abort` it frees every block of the app instead
+                field!(Context, store, "void **store"),
+                field!(Context, input, "const Input *input"),
+                field!(Context, caps, "uint64_t caps"),
+                field!(
+                    Context,
+                    spawn,
+                    "int64_t (*spawn)(const char *name, uint32_t len)"
+                ),
+                field!(Context, kill, "int32_t (*kill)(uint64_t pid)"),
+                field!(Context, ps, "size_t (*ps)(ProcInfo *out, size_t cap)"),
+                field!(Context, mem, "int32_t (*mem)(uint64_t pid, MemInfo *out)"),
+                field!(
+                    Context,
+                    abort,
+                    "void (*abort)(const char *msg, uint32_t len)"
+                ),
+                field!(Context, open, "int64_t (*open)(const char *name, uint32_t len)"),
+                field!(
+                    Context,
+                    send,
+                    "int32_t (*send)(uint64_t to, const void *data, uint32_t len)"
+                ),
+                field!(
+                    Context,
+                    recv,
+                    "int32_t (*recv)(Message *msg, void *data, uint32_t cap)"
+                ),
+                field!(
+                    Context,
+                    shm_create,
+                    "int32_t (*shm_create)(const char *name, uint32_t len, size_t size, Shm *out)"
+                ),
+                field!(
+                    Context,
+                    shm_map,
+                    "int32_t (*shm_map)(const char *name, uint32_t len, Shm *out)"
+                ),
+                field!(Context, shm_release, "int32_t (*shm_release)(uint64_t id)"),
+                field!(
+                    Context,
+                    register,
+                    "int32_t (*register_)(const char *name, uint32_t len, ServiceFn entry)"
+                ),
+                field!(
+                    Context,
+                    lookup,
+                    "int64_t (*lookup)(const char *name, uint32_t len)"
+                ),
+                field!(
+                    Context,
+                    call,
+                    "int32_t (*call)(uint64_t id, const void *arg, uint32_t arg_len, void *out, uint32_t out_cap)"
+                ),
+                field!(
+                    Context,
+                    clip_set,
+                    "int64_t (*clip_set)(const ClipInfo *info, const void *data)"
+                ),
+                field!(
+                    Context,
+                    clip_get,
+                    "int32_t (*clip_get)(ClipInfo *info, void *data, size_t cap)"
+                ),
+                field!(Context, clip_serial, "uint64_t (*clip_serial)(void)"),
+                field!(
+                    Context,
+                    surface_get,
+                    "void (*surface_get)(SurfaceInfo *info)"
+                ),
+                field!(
+                    Context,
+                    surface_set,
+                    "int32_t (*surface_set)(const SurfaceInfo *info)"
+                ),
+                field!(
+                    Context,
+                    damage,
+                    "void (*damage)(const Rect *rects, uint32_t n)"
+                ),
+                field!(Context, frame, "const Frame *frame"),
+                field!(Context, display, "const Display *display"),
+            ],
+        },
+    ]
+}
+
+fn constants() -> Vec<(&'static str, String)> {
+    let mut all = vec![
+        ("CONTEXT_VERSION", CONTEXT_VERSION.to_string()),
+        ("KEY_COUNT", KEY_COUNT.to_string()),
+        ("HISTORY_SIZE", HISTORY_SIZE.to_string()),
+    ];
+    for (name, cap) in [
+        ("CAP_LOG", caps::LOG),
+        ("CAP_FB", caps::FB),
+        ("CAP_ALLOC", caps::ALLOC),
+        ("CAP_STORE", caps::STORE),
+        ("CAP_INPUT", caps::INPUT),
+        ("CAP_PROC", caps::PROC),
+        ("CAP_MEM", caps::MEM),
+        ("CAP_ABORT", caps::ABORT),
+        ("CAP_IPC", caps::IPC),
+        ("CAP_SHM", caps::SHM),
+        ("CAP_SERVICE", caps::SERVICE),
+        ("CAP_CLIPBOARD", caps::CLIPBOARD),
+        ("CAP_SURFACE", caps::SURFACE),
+        ("CAP_DAMAGE", caps::DAMAGE),
+        ("CAP_FRAME", caps::FRAME),
+        ("CAP_DISPLAY", caps::DISPLAY),
+    ] {
+        all.push((name, format!("{:#x}ull", cap)));
+    }
+    for (name, state) in [
+        ("KEY_STATE_OFF", KeyState::Off),
+        ("KEY_STATE_OFF_FROM_ON", KeyState::OffFromOn),
+        ("KEY_STATE_OFF_TRANSIENT_ON", KeyState::OffTransientOn),
+        ("KEY_STATE_ON_FROM_OFF", KeyState::OnFromOff),
+        ("KEY_STATE_ON_TRANSIENT_OFF", KeyState::OnTransientOff),
+        ("KEY_STATE_ON", KeyState::On),
+    ] {
+        all.push((name, (state as u8).to_string()));
+    }
+    for (name, state) in [
+        ("PROC_READY", PROC_READY),
+        ("PROC_SLEEPING", PROC_SLEEPING),
+        ("PROC_WAITING_INPUT", PROC_WAITING_INPUT),
+        ("PROC_WAITING_MESSAGE", PROC_WAITING_MESSAGE),
+        ("PROC_EXITED", PROC_EXITED),
+        ("PROC_CRASHED", PROC_CRASHED),
+    ] {
+        all.push((name, state.to_string()));
+    }
+    all.push(("PROC_OVER_BUDGET", PROC_OVER_BUDGET.to_string()));
+    for (name, status) in [
+        ("EXIT_KILLED", EXIT_KILLED),
+        ("EXIT_FAULT", EXIT_FAULT),
+        ("EXIT_PANIC", EXIT_PANIC),
+        ("EXIT_TIMEOUT", EXIT_TIMEOUT),
+        ("IPC_NO_TARGET", IPC_NO_TARGET),
+        ("IPC_TOO_LARGE", IPC_TOO_LARGE),
+        ("IPC_FULL", IPC_FULL),
+        ("IPC_TOO_SMALL", IPC_TOO_SMALL),
+        ("IPC_BAD_NAME", IPC_BAD_NAME),
+        ("IPC_NO_MEMORY", IPC_NO_MEMORY),
+        ("SHM_EXISTS", SHM_EXISTS),
+        ("SHM_NOT_FOUND", SHM_NOT_FOUND),
+        ("SHM_BAD_NAME", SHM_BAD_NAME),
+        ("SHM_BAD_SIZE", SHM_BAD_SIZE),
+        ("SHM_NO_MEMORY", SHM_NO_MEMORY),
+        ("SERVICE_GONE", SERVICE_GONE),
+        ("SERVICE_FAULT", SERVICE_FAULT),
+        ("SERVICE_EXISTS", SERVICE_EXISTS),
+        ("SERVICE_BAD_NAME", SERVICE_BAD_NAME),
+        ("SERVICE_BAD_ENTRY", SERVICE_BAD_ENTRY),
+        ("CLIP_BAD_ENTRY", CLIP_BAD_ENTRY),
+        ("CLIP_TOO_LARGE", CLIP_TOO_LARGE),
+        ("CLIP_TOO_SMALL", CLIP_TOO_SMALL),
+        ("CLIP_NO_MEMORY", CLIP_NO_MEMORY),
+        ("SURFACE_BAD_SIZE", SURFACE_BAD_SIZE),
+        ("SURFACE_BAD_LAYER", SURFACE_BAD_LAYER),
+        ("SURFACE_NO_MEMORY", SURFACE_NO_MEMORY),
+    ] {
+        // i32::MIN does not fit a C int literal
+        let value = match status {
+            i32::MIN => format!("({} - 1)", i32::MIN + 1),
+            status => format!("({})", status),
+        };
+        all.push((name, value));
+    }
+    all.push(("CHANNEL_BIT", format!("{:#x}ull", CHANNEL_BIT)));
+    all.push(("MAX_MESSAGE", MAX_MESSAGE.to_string()));
+    all.push(("INBOX_CAPACITY", INBOX_CAPACITY.to_string()));
+    all.push(("SHM_MAX_SIZE", SHM_MAX_SIZE.to_string()));
+    all.push(("CLIP_EMPTY", CLIP_EMPTY.to_string()));
+    all.push(("CLIP_TEXT", CLIP_TEXT.to_string()));
+    all.push(("CLIP_IMAGE", CLIP_IMAGE.to_string()));
+    all.push(("CLIP_MAX_SIZE", CLIP_MAX_SIZE.to_string()));
+    all.push(("LAYER_NORMAL", LAYER_NORMAL.to_string()));
+    all.push(("LAYER_BACKGROUND", LAYER_BACKGROUND.to_string()));
+    all.push(("LAYER_OVERLAY", LAYER_OVERLAY.to_string()));
+    all.push(("SURFACE_MAX_SIDE", SURFACE_MAX_SIDE.to_string()));
+    all.push(("SURFACE_MAX_SCREENS", SURFACE_MAX_SCREENS.to_string()));
+    all.push(("DAMAGE_MAX_RECTS", DAMAGE_MAX_RECTS.to_string()));
+    all.push(("DISPLAY_MAX_SCREENS", DISPLAY_MAX_SCREENS.to_string()));
+    let magic = std::str::from_utf8(&MANIFEST_MAGIC).unwrap();
+    all.push(("MANIFEST_MAGIC", format!("\"{}\"", magic)));
+    all.push(("MANIFEST_SECTION", format!("\"{}\"", MANIFEST_SECTION)));
+    all.push(("MANIFEST_AUTOSTART", MANIFEST_AUTOSTART.to_string()));
+    all
+}
+
+const PRELUDE: &str = "\
+// Generated by `cargo run --bin gen_c_header` in fomos_abi, do not edit.
+// C view of the types shared by the Fomos kernel and its apps.
+#ifndef FOMOS_H
+#define FOMOS_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+";
+
+fn header() -> String {
+    let mut out = String::from(PRELUDE);
+    let w = &mut out;
+
+    writeln!(w).unwrap();
+    for (name, value) in constants() {
+        writeln!(w, "#define {} {}", name, value).unwrap();
+    }
+
+    // `Ret`, what `_start` returns
+    write!(
+        w,
+        "
+#define RET_CONTINUE {}
+#define RET_EXIT(status) ((status) < 0 ? (status) : ({:#x} | ((status) & 0xffffff)))
+#define RET_SLEEP(ms) ({:#x} | ((ms) > 0xffffff ? 0xffffff : (ms)))
+#define RET_WAIT_INPUT {:#x}
+#define RET_WAIT_MESSAGE {:#x}
+
+// Entry point of a service, see `register_`
+typedef int32_t (*ServiceFn)(const void *arg, uint32_t arg_len, void *out, uint32_t out_cap);
+",
+        Ret::Continue.encode(),
+        Ret::Exit(0).encode(),
+        Ret::Sleep(0).encode(),
+        Ret::WaitInput.encode(),
+        Ret::WaitMessage.encode()
+    )
+    .unwrap();
+
+    for s in structs() {
+        writeln!(w, "\n// {}\ntypedef struct\n{{", s.doc).unwrap();
+        for field in &s.fields {
+            writeln!(w, "    {};", field.decl).unwrap();
+        }
+        writeln!(w, "}} {};", s.name).unwrap();
+        writeln!(
+            w,
+            "_Static_assert(sizeof({}) == {}, \"{} size\");",
+            s.name, s.size, s.name
+        )
+        .unwrap();
+        for field in &s.fields {
+            if let Some(offset) = field.offset {
+                let name = field_name(field.decl);
+                writeln!(
+                    w,
+                    "_Static_assert(offsetof({}, {}) == {}, \"{}.{} offset\");",
+                    s.name, name, offset, s.name, name
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    // Same as `caps::version_of`, newest capability first
+    let mut versions = caps::VERSIONS.to_vec();
+    versions.sort_by_key(|&(_, version)| core::cmp::Reverse(version));
+    let mut version_of = String::new();
+    for (cap, version) in versions.iter().filter(|&&(_, version)| version > 0) {
+        write!(version_of, "((caps) & {:#x}ull) ? {} : ", cap, version).unwrap();
+    }
+
+    // Same as `Context::has`, including kernels from before `caps`
+    write!(
+        w,
+        "
+// Whether the kernel gave every field of the `cap` bits
+static inline bool fomos_has(const Context *ctx, uint64_t cap)
+{{
+    uint64_t caps;
+    if (ctx->version >= 2 && ctx->size >= offsetof(Context, caps) + 8)
+        caps = ctx->caps;
+    else if (ctx->version == 0)
+        caps = {:#x};
+    else
+        caps = {:#x};
+    return (caps & cap) == cap;
+}}
+
+// Oldest Context version with the fields of every capability in `caps`
+#define FOMOS_CAPS_VERSION(caps) ({}0)
+
+// Embeds the Manifest of the app, like `manifest!` in Rust:
+// FOMOS_MANIFEST(CAP_LOG | CAP_ALLOC, .name = \"app_c\");
+// min_context_version defaults to what the caps need. Without one, the app gets
+// the oldest Context
+#define FOMOS_MANIFEST(caps_, ...)                            \\
+    __attribute__((used, section(MANIFEST_SECTION)))          \\
+    static const Manifest fomos_manifest = {{                  \\
+        .magic = MANIFEST_MAGIC,                              \\
+        .size = sizeof(Manifest),                             \\
+        .context_version = CONTEXT_VERSION,                   \\
+        .caps = (caps_),                                      \\
+        .min_context_version = FOMOS_CAPS_VERSION(caps_),     \\
+        __VA_ARGS__}}
+
+#endif
+",
+        version_caps(0),
+        version_caps(1),
+        version_of
+    )
+    .unwrap();
+    out
+}
+
+fn main() {
+    print!("{}", header());
+}
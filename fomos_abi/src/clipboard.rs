@@ -0,0 +1,33 @@
+///`ClipInfo::kind`: nothing was copied yet
+pub const CLIP_EMPTY: u32 = 0;
+///UTF-8 text
+pub const CLIP_TEXT: u32 = 1;
+///`w * h` pixels, `RGBA` row by row
+pub const CLIP_IMAGE: u32 = 2;
+
+///Largest clipboard entry, in bytes
+pub const CLIP_MAX_SIZE: usize = 16 * 1024 * 1024;
+
+///Unknown kind, text not utf8, or image size not matching `w * h`
+pub const CLIP_BAD_ENTRY: i32 = -1;
+///Entry larger than `CLIP_MAX_SIZE`
+pub const CLIP_TOO_LARGE: i32 = -2;
+///`clip_get` buffer smaller than `ClipInfo::len`, only the info was filled
+pub const CLIP_TOO_SMALL: i32 = -3;
+///Out of kernel memory, or over the memory quota of the app
+pub const CLIP_NO_MEMORY: i32 = -4;
+
+///What the clipboard holds, without the data
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ClipInfo {
+    pub kind: u32,
+    ///Image size in pixels, 0 for text
+    pub w: u32,
+    pub h: u32,
+    pub _reserved: u32,
+    ///Size of the data in bytes
+    pub len: u64,
+    ///Change counter, see `Context::clip_serial`
+    pub serial: u64,
+}
@@ -0,0 +1,226 @@
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+///Most rectangles one `damage` call looks at, the rest is ignored
+pub const DAMAGE_MAX_RECTS: u32 = 64;
+
+///A rectangle of pixels, of the app surface for `Context::damage`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+impl Rect {
+    pub const fn new(x: u32, y: u32, w: u32, h: u32) -> Self {
+        Self { x, y, w, h }
+    }
+    pub const fn is_empty(&self) -> bool {
+        self.w == 0 || self.h == 0
+    }
+    pub const fn area(&self) -> u64 {
+        self.w as u64 * self.h as u64
+    }
+    pub const fn right(&self) -> u32 {
+        self.x.saturating_add(self.w)
+    }
+    pub const fn bottom(&self) -> u32 {
+        self.y.saturating_add(self.h)
+    }
+    ///Smallest rectangle holding both
+    pub fn union(&self, other: &Rect) -> Rect {
+        if self.is_empty() {
+            return *other;
+        }
+        if other.is_empty() {
+            return *self;
+        }
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        Rect::new(
+            x,
+            y,
+            self.right().max(other.right()) - x,
+            self.bottom().max(other.bottom()) - y,
+        )
+    }
+    ///Part of both, empty if they do not overlap
+    pub fn intersect(&self, other: &Rect) -> Rect {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = self.right().min(other.right());
+        let bottom = self.bottom().min(other.bottom());
+        if right <= x || bottom <= y {
+            return Rect::default();
+        }
+        Rect::new(x, y, right - x, bottom - y)
+    }
+    ///Whether they overlap or share an edge
+    pub fn touches(&self, other: &Rect) -> bool {
+        !self.is_empty()
+            && !other.is_empty()
+            && self.x <= other.right()
+            && other.x <= self.right()
+            && self.y <= other.bottom()
+            && other.y <= self.bottom()
+    }
+    ///Part inside a `w` x `h` area at 0, 0
+    pub fn clip(&self, w: u32, h: u32) -> Rect {
+        self.intersect(&Rect::new(0, 0, w, h))
+    }
+}
+
+///Past this many rectangles, a `Damage` replaces them by their bounding box
+#[cfg(feature = "alloc")]
+const MAX_RECTS: usize = 16;
+
+///A set of rectangles, overlapping or touching ones are merged
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, Default)]
+pub struct Damage {
+    rects: Vec<Rect>,
+}
+
+#[cfg(feature = "alloc")]
+impl Damage {
+    pub const fn new() -> Self {
+        Self { rects: Vec::new() }
+    }
+
+    pub fn add(&mut self, rect: Rect) {
+        if rect.is_empty() {
+            return;
+        }
+        let mut rect = rect;
+        // Merging can make the rectangle touch ones it did not, until nothing changes
+        while let Some(i) = self.rects.iter().position(|r| r.touches(&rect)) {
+            rect = rect.union(&self.rects.swap_remove(i));
+        }
+        self.rects.push(rect);
+        if self.rects.len() > MAX_RECTS {
+            let bounds = self.bounds();
+            self.rects.clear();
+            self.rects.push(bounds);
+        }
+    }
+
+    pub fn extend(&mut self, other: &Damage) {
+        for &rect in other.rects.iter() {
+            self.add(rect);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rects.is_empty()
+    }
+
+    pub fn rects(&self) -> &[Rect] {
+        &self.rects
+    }
+
+    ///Smallest rectangle holding all of them
+    pub fn bounds(&self) -> Rect {
+        self.rects
+            .iter()
+            .fold(Rect::default(), |bounds, r| bounds.union(r))
+    }
+
+    pub fn clear(&mut self) {
+        self.rects.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn union() {
+        let a = Rect::new(0, 0, 10, 10);
+        let b = Rect::new(20, 5, 10, 10);
+        assert_eq!(a.union(&b), Rect::new(0, 0, 30, 15));
+        assert_eq!(b.union(&a), Rect::new(0, 0, 30, 15));
+        assert_eq!(a.union(&Rect::default()), a);
+        assert_eq!(Rect::new(50, 50, 0, 3).union(&b), b);
+    }
+
+    #[test]
+    fn intersect() {
+        let a = Rect::new(0, 0, 10, 10);
+        assert_eq!(a.intersect(&Rect::new(5, 2, 10, 3)), Rect::new(5, 2, 5, 3));
+        assert_eq!(a.intersect(&a), a);
+        assert!(a.intersect(&Rect::new(10, 0, 5, 5)).is_empty());
+        assert!(a.intersect(&Rect::new(20, 20, 5, 5)).is_empty());
+    }
+
+    #[test]
+    fn touches() {
+        let a = Rect::new(0, 0, 10, 10);
+        assert!(a.touches(&Rect::new(5, 5, 10, 10)));
+        // Sharing an edge or a corner
+        assert!(a.touches(&Rect::new(10, 0, 5, 5)));
+        assert!(a.touches(&Rect::new(10, 10, 5, 5)));
+        assert!(!a.touches(&Rect::new(11, 0, 5, 5)));
+        assert!(!a.touches(&Rect::new(5, 5, 0, 5)));
+    }
+
+    #[test]
+    fn clip() {
+        let a = Rect::new(5, 5, 10, 10);
+        assert_eq!(a.clip(100, 100), a);
+        assert_eq!(a.clip(8, 12), Rect::new(5, 5, 3, 7));
+        assert!(a.clip(5, 100).is_empty());
+    }
+
+    #[test]
+    fn saturating_edges() {
+        let a = Rect::new(u32::MAX - 5, 10, 100, u32::MAX);
+        assert_eq!(a.right(), u32::MAX);
+        assert_eq!(a.bottom(), u32::MAX);
+        assert_eq!(
+            a.union(&Rect::new(0, 0, 1, 1)),
+            Rect::new(0, 0, u32::MAX, u32::MAX)
+        );
+        assert_eq!(a.clip(u32::MAX, 20), Rect::new(u32::MAX - 5, 10, 5, 10));
+        assert_eq!(a.area(), 100 * u32::MAX as u64);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn damage_merges() {
+        let mut damage = Damage::new();
+        damage.add(Rect::new(0, 0, 10, 10));
+        damage.add(Rect::new(100, 5, 10, 10));
+        damage.add(Rect::default());
+        assert_eq!(damage.rects().len(), 2);
+        // Grows the first one
+        damage.add(Rect::new(10, 0, 5, 5));
+        assert_eq!(damage.rects().len(), 2);
+        // Touches the first one only, the union then touches the second one
+        damage.add(Rect::new(15, 0, 85, 1));
+        assert_eq!(damage.rects(), [Rect::new(0, 0, 110, 15)]);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn damage_collapses() {
+        let mut damage = Damage::new();
+        for i in 0..MAX_RECTS as u32 {
+            damage.add(Rect::new(i * 10, i * 10, 5, 5));
+        }
+        assert_eq!(damage.rects().len(), MAX_RECTS);
+        damage.add(Rect::new(500, 500, 5, 5));
+        assert_eq!(damage.rects(), [Rect::new(0, 0, 505, 505)]);
+
+        let mut other = Damage::new();
+        other.add(Rect::new(600, 0, 1, 1));
+        damage.extend(&other);
+        assert_eq!(damage.bounds(), Rect::new(0, 0, 601, 505));
+        damage.clear();
+        assert!(damage.is_empty());
+        assert!(damage.bounds().is_empty());
+    }
+}
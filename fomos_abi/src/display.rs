@@ -0,0 +1,29 @@
+use crate::Rect;
+
+///Most screens a `Display` describes, as many as a virtio-gpu device has scanouts
+pub const DISPLAY_MAX_SCREENS: usize = 16;
+
+///Screens the framebuffer is shown on, see `Context::display`.
+///
+///Every screen shows its part of the framebuffer, side by side. A full screen surface
+///follows the framebuffer size, the others stay where they are.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Display {
+    ///Bumped by every change of the screens, cheap to poll to notice a resize or a new screen
+    pub serial: u64,
+    ///Size of the framebuffer, the bounds of every screen
+    pub w: u32,
+    pub h: u32,
+    ///Valid entries of `screens`
+    pub count: u32,
+    pub _reserved: u32,
+    ///Part of the framebuffer shown by each screen
+    pub screens: [Rect; DISPLAY_MAX_SCREENS],
+}
+
+impl Display {
+    pub fn screens(&self) -> &[Rect] {
+        &self.screens[..(self.count as usize).min(DISPLAY_MAX_SCREENS)]
+    }
+}
@@ -0,0 +1,129 @@
+//! Kernel symbols an app can import, bound by the loader instead of read from the Context.
+//!
+//! An app declares them as `extern "C"` (see `imports`) and links with undefined symbols
+//! allowed (`-C link-arg=-zundefs`), the kernel resolves them at load time.
+//! A symbol missing from the kernel rejects the app, unless it is imported weak: it is then
+//! bound to null. `fomos_exports_version` tells which symbols the kernel has.
+//!
+//! Exports are append-only, like the Context: a symbol never changes signature.
+use crate::{ClipInfo, MemInfo, Message, ProcInfo, Rect, ServiceFn, Shm, SurfaceInfo};
+
+///Every symbol and the version of the export table it appeared in, in that order.
+///The kernel table must list the same symbols in the same order (see `same_symbols`)
+pub const EXPORTS: &[(&str, u32)] = &[
+    ("fomos_exports_version", 1),
+    ("fomos_log", 1),
+    ("fomos_alloc", 1),
+    ("fomos_free", 1),
+    ("fomos_time_ms", 1),
+    ("fomos_pid", 1),
+    ("fomos_spawn", 1),
+    ("fomos_kill", 1),
+    ("fomos_ps", 1),
+    ("fomos_mem", 1),
+    ("fomos_abort", 1),
+    ("fomos_open", 2),
+    ("fomos_send", 2),
+    ("fomos_recv", 2),
+    ("fomos_shm_create", 3),
+    ("fomos_shm_map", 3),
+    ("fomos_shm_release", 3),
+    ("fomos_register", 4),
+    ("fomos_lookup", 4),
+    ("fomos_call", 4),
+    ("fomos_clip_set", 5),
+    ("fomos_clip_get", 5),
+    ("fomos_clip_serial", 5),
+    ("fomos_surface_get", 6),
+    ("fomos_surface_set", 6),
+    ("fomos_damage", 7),
+];
+
+///Latest version of the export table, see `since`
+pub const EXPORTS_VERSION: u32 = EXPORTS[EXPORTS.len() - 1].1;
+
+// Append-only: versions never go down, so the last one is the latest
+const _: () = {
+    let mut i = 1;
+    while i < EXPORTS.len() {
+        assert!(EXPORTS[i - 1].1 <= EXPORTS[i].1);
+        i += 1;
+    }
+};
+
+///Version of the export table each symbol appeared in
+pub fn since(name: &str) -> Option<u32> {
+    EXPORTS
+        .iter()
+        .find(|(symbol, _)| *symbol == name)
+        .map(|&(_, version)| version)
+}
+
+const fn same_str(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+///Whether `names` are the symbols of `EXPORTS`, in the same order.
+///For a `const` assert on the kernel table
+pub const fn same_symbols(names: &[&str]) -> bool {
+    if names.len() != EXPORTS.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < names.len() {
+        if !same_str(names[i], EXPORTS[i].0) {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+pub mod imports {
+    //! Declarations of the kernel exports, same signatures as the matching Context entries.
+    use super::*;
+
+    extern "C" {
+        ///Version of the table the kernel was built with
+        pub static fomos_exports_version: u32;
+        pub fn fomos_log(s: *const u8, l: u32);
+        pub fn fomos_alloc(size: usize, align: usize) -> *mut u8;
+        pub fn fomos_free(ptr: *mut u8, size: usize, align: usize);
+        ///Kernel time in ms
+        pub fn fomos_time_ms() -> u64;
+        ///Pid of the calling app
+        pub fn fomos_pid() -> u64;
+        pub fn fomos_spawn(name: *const u8, len: u32) -> i64;
+        pub fn fomos_kill(pid: u64) -> i32;
+        pub fn fomos_ps(out: *mut ProcInfo, cap: usize) -> usize;
+        pub fn fomos_mem(pid: u64, out: *mut MemInfo) -> i32;
+        pub fn fomos_abort(msg: *const u8, len: u32);
+        pub fn fomos_open(name: *const u8, len: u32) -> i64;
+        pub fn fomos_send(to: u64, data: *const u8, len: u32) -> i32;
+        pub fn fomos_recv(msg: *mut Message, data: *mut u8, cap: u32) -> i32;
+        pub fn fomos_shm_create(name: *const u8, len: u32, size: usize, out: *mut Shm) -> i32;
+        pub fn fomos_shm_map(name: *const u8, len: u32, out: *mut Shm) -> i32;
+        pub fn fomos_shm_release(id: u64) -> i32;
+        pub fn fomos_register(name: *const u8, len: u32, entry: ServiceFn) -> i32;
+        pub fn fomos_lookup(name: *const u8, len: u32) -> i64;
+        pub fn fomos_call(id: u64, arg: *const u8, arg_len: u32, out: *mut u8, out_cap: u32)
+            -> i32;
+        pub fn fomos_clip_set(info: *const ClipInfo, data: *const u8) -> i64;
+        pub fn fomos_clip_get(info: *mut ClipInfo, data: *mut u8, cap: usize) -> i32;
+        pub fn fomos_clip_serial() -> u64;
+        pub fn fomos_surface_get(info: *mut SurfaceInfo);
+        pub fn fomos_surface_set(info: *const SurfaceInfo) -> i32;
+        pub fn fomos_damage(rects: *const Rect, n: u32);
+    }
+}
@@ -0,0 +1,53 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct RGBA {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+///Framebuffer shared with an app for the duration of one call
+#[repr(C)]
+pub struct FB<'a> {
+    pub pixels: &'a mut [RGBA],
+    pub w: usize,
+    pub h: usize,
+}
+
+impl<'a> FB<'a> {
+    ///Pixel at `x`, `y`, `None` outside of the framebuffer
+    pub fn get(&self, x: usize, y: usize) -> Option<RGBA> {
+        if x >= self.w {
+            return None;
+        }
+        self.pixels.get(x + y * self.w).copied()
+    }
+    pub fn pixel_mut(&mut self, x: usize, y: usize) -> Option<&mut RGBA> {
+        if x >= self.w {
+            return None;
+        }
+        self.pixels.get_mut(x + y * self.w)
+    }
+    ///Does nothing outside of the framebuffer
+    pub fn set(&mut self, x: usize, y: usize, color: RGBA) {
+        if let Some(p) = self.pixel_mut(x, y) {
+            *p = color;
+        }
+    }
+    ///One line of pixels, empty past the bottom
+    pub fn row_mut(&mut self, y: usize) -> &mut [RGBA] {
+        let start = (y * self.w).min(self.pixels.len());
+        let end = (start + self.w).min(self.pixels.len());
+        &mut self.pixels[start..end]
+    }
+    ///Clipped to the framebuffer
+    pub fn fill_rect(&mut self, x: usize, y: usize, w: usize, h: usize, color: RGBA) {
+        let x2 = (x + w).min(self.w);
+        for y in y..(y + h).min(self.h) {
+            if let Some(row) = self.row_mut(y).get_mut(x..x2) {
+                row.fill(color);
+            }
+        }
+    }
+}
@@ -0,0 +1,13 @@
+///Frame clock as seen by an app, see `Context::frame`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Frame {
+    ///Frames presented since boot. What the app draws in this call is shown in the next one
+    pub number: u64,
+    ///Kernel time the last frame was presented, in ms
+    pub presented_ms: u64,
+    ///Time between the last two presented frames, in ms
+    pub delta_ms: u32,
+    ///Frames the kernel tries to present per second
+    pub refresh_hz: u32,
+}
@@ -0,0 +1,205 @@
+pub const HISTORY_SIZE: usize = 64;
+pub const KEY_COUNT: usize = 1024;
+
+///Mouse buttons, by their evdev code in `Input::keys`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MouseButton {
+    Left = 0x110,
+    Right = 0x111,
+    Middle = 0x112,
+}
+
+#[repr(C)]
+#[derive(Clone, Debug, Copy)]
+pub struct InputEvent {
+    pub trigger: bool,
+    ///Linux evdev key code
+    pub key: usize,
+}
+
+#[repr(C)]
+#[derive(Clone, Debug, Copy)]
+pub struct Input {
+    pub mouse_x: usize,
+    pub mouse_y: usize,
+    pub keys: [KeyState; KEY_COUNT],
+    pub history_last_index: usize,
+    pub history_ring: [InputEvent; HISTORY_SIZE],
+}
+
+impl Default for Input {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Input {
+    pub const fn new() -> Self {
+        Self {
+            mouse_x: 0,
+            mouse_y: 0,
+            keys: [KeyState::Off; KEY_COUNT],
+            history_last_index: 0,
+            history_ring: [InputEvent {
+                trigger: false,
+                key: 0,
+            }; HISTORY_SIZE],
+        }
+    }
+    ///State of a key by evdev code, `Off` if out of range
+    pub fn key(&self, code: usize) -> KeyState {
+        self.keys.get(code).copied().unwrap_or_default()
+    }
+    pub fn button(&self, button: MouseButton) -> KeyState {
+        self.key(button as usize)
+    }
+    pub fn mouse(&self) -> (usize, usize) {
+        (self.mouse_x, self.mouse_y)
+    }
+    ///Key events after `last`, a previous `history_last_index`.
+    ///Only the last `HISTORY_SIZE` are kept, older ones are skipped
+    pub fn events_since(&self, last: usize) -> impl Iterator<Item = InputEvent> + '_ {
+        let first = (last + 1).max((self.history_last_index + 1).saturating_sub(HISTORY_SIZE));
+        (first..=self.history_last_index).map(|i| self.history_ring[i % HISTORY_SIZE])
+    }
+    pub fn step(&mut self) {
+        for k in self.keys.iter_mut() {
+            k.step();
+        }
+    }
+    pub fn handle_incoming_state(&mut self, key: usize, b: bool) {
+        self.history_last_index += 1;
+        self.history_ring[self.history_last_index % HISTORY_SIZE] = InputEvent { trigger: b, key };
+        self.keys[key].handle_incoming_state(b);
+    }
+}
+
+#[repr(u8)]
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Default)]
+pub enum KeyState {
+    ///Key is not pressed now
+    #[default]
+    Off = 0,
+    ///Key is not pressed now, but was last frame
+    OffFromOn = 1,
+    ///Key is not pressed now, it was not pressed last frame either, but was during frame (sequence Off -> On -> Off)
+    OffTransientOn = 2,
+    OnFromOff = 128,
+    OnTransientOff = 129,
+    On = 130,
+}
+impl KeyState {
+    ///Pressed now
+    pub fn is_down(self) -> bool {
+        self as u8 >= 128
+    }
+    ///Went down since the last frame, even if already released
+    pub fn pressed(self) -> bool {
+        matches!(
+            self,
+            KeyState::OnFromOff | KeyState::OnTransientOff | KeyState::OffTransientOn
+        )
+    }
+    ///Went up since the last frame, even if already pressed again
+    pub fn released(self) -> bool {
+        matches!(
+            self,
+            KeyState::OffFromOn | KeyState::OffTransientOn | KeyState::OnTransientOff
+        )
+    }
+    pub fn handle_incoming_state(&mut self, b: bool) {
+        *self = match (*self, b) {
+            (KeyState::Off, true) => KeyState::OnFromOff,
+            (KeyState::On, false) => KeyState::OffFromOn,
+            (KeyState::OffFromOn, true) => KeyState::OnTransientOff,
+            (KeyState::OnFromOff, false) => KeyState::OffTransientOn,
+            (_, false) => KeyState::Off,
+            (_, true) => KeyState::On,
+        }
+    }
+    ///To call every kernel loop
+    pub fn step(&mut self) {
+        *self = match *self {
+            KeyState::OffTransientOn => KeyState::Off,
+            KeyState::OffFromOn => KeyState::Off,
+            KeyState::OnFromOff => KeyState::On,
+            KeyState::OnTransientOff => KeyState::On,
+            _ => *self,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys_since(input: &Input, last: usize) -> impl Iterator<Item = usize> + '_ {
+        input.events_since(last).map(|e| e.key)
+    }
+
+    #[test]
+    fn press_and_release() {
+        let mut k = KeyState::Off;
+        k.handle_incoming_state(true);
+        assert_eq!(k, KeyState::OnFromOff);
+        assert!(k.is_down() && k.pressed() && !k.released());
+        k.step();
+        assert_eq!(k, KeyState::On);
+        assert!(k.is_down() && !k.pressed());
+        k.handle_incoming_state(false);
+        assert_eq!(k, KeyState::OffFromOn);
+        assert!(!k.is_down() && k.released());
+        k.step();
+        assert_eq!(k, KeyState::Off);
+    }
+
+    #[test]
+    fn transients_within_a_frame() {
+        let mut k = KeyState::Off;
+        k.handle_incoming_state(true);
+        k.handle_incoming_state(false);
+        assert_eq!(k, KeyState::OffTransientOn);
+        assert!(!k.is_down() && k.pressed() && k.released());
+        k.step();
+        assert_eq!(k, KeyState::Off);
+
+        let mut k = KeyState::On;
+        k.handle_incoming_state(false);
+        k.handle_incoming_state(true);
+        assert_eq!(k, KeyState::OnTransientOff);
+        assert!(k.is_down() && k.pressed() && k.released());
+        k.step();
+        assert_eq!(k, KeyState::On);
+    }
+
+    #[test]
+    fn key_out_of_range() {
+        let mut input = Input::new();
+        input.handle_incoming_state(MouseButton::Left as usize, true);
+        assert_eq!(input.button(MouseButton::Left), KeyState::OnFromOff);
+        assert_eq!(input.key(KEY_COUNT), KeyState::Off);
+    }
+
+    #[test]
+    fn events_since_last() {
+        let mut input = Input::new();
+        assert_eq!(keys_since(&input, 0).count(), 0);
+        for key in 1..=3 {
+            input.handle_incoming_state(key, true);
+        }
+        assert!(keys_since(&input, 0).eq([1, 2, 3]));
+        assert!(keys_since(&input, 2).eq([3]));
+        assert_eq!(keys_since(&input, 3).count(), 0);
+    }
+
+    #[test]
+    fn events_since_skips_overwritten() {
+        let mut input = Input::new();
+        let count = HISTORY_SIZE + 10;
+        for key in 1..=count {
+            input.handle_incoming_state(key, true);
+        }
+        assert!(keys_since(&input, 0).eq(count - HISTORY_SIZE + 1..=count));
+        assert!(keys_since(&input, count - 2).eq([count - 1, count]));
+    }
+}
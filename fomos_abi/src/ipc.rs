@@ -0,0 +1,31 @@
+///Set in the ids returned by `Context::open`, so a channel id is never taken for a pid
+pub const CHANNEL_BIT: u64 = 1 << 63;
+///Largest message `send` accepts, in bytes
+pub const MAX_MESSAGE: usize = 4096;
+///Messages an inbox holds before `send` to it fails with `IPC_FULL`
+pub const INBOX_CAPACITY: usize = 64;
+
+///No such running pid, or no such channel
+pub const IPC_NO_TARGET: i32 = -1;
+///Message larger than `MAX_MESSAGE`
+pub const IPC_TOO_LARGE: i32 = -2;
+///The inbox of the receiver is full, or of every other member of the channel
+pub const IPC_FULL: i32 = -3;
+///`recv` buffer too small for the next message, it stays in the inbox
+pub const IPC_TOO_SMALL: i32 = -4;
+///Channel name empty, too long or not utf8
+pub const IPC_BAD_NAME: i32 = -5;
+///Out of kernel memory, or over the memory quota of the sender
+pub const IPC_NO_MEMORY: i32 = -6;
+
+///Header of a message, filled by `Context::recv`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Message {
+    pub from: u64,
+    ///Channel the message was sent to, 0 if it was sent to this pid directly
+    pub channel: u64,
+    ///Size of the data, copied to the `recv` buffer
+    pub len: u32,
+    pub _reserved: u32,
+}
@@ -0,0 +1,570 @@
+//! Types shared by the Fomos kernel and its apps.
+//!
+//! Everything an app receives in `_start(ctx: &mut Context) -> i32` is defined here, once.
+//! The layout of each type is asserted at compile time (see `layout`), so changing a field
+//! breaks the build of the kernel and of every app instead of corrupting memory at runtime.
+//!
+//! C apps cannot use this crate, `app_c/fomos.h` is generated from it (see `gen_c_header`).
+#![no_std]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+mod clipboard;
+mod damage;
+mod display;
+pub mod exports;
+mod fb;
+mod frame;
+mod input;
+mod ipc;
+mod manifest;
+mod process;
+mod ret;
+mod service;
+mod shm;
+mod surface;
+
+pub use clipboard::*;
+pub use damage::*;
+pub use display::*;
+pub use fb::*;
+pub use frame::*;
+pub use input::*;
+pub use ipc::*;
+pub use manifest::*;
+pub use process::*;
+pub use ret::*;
+pub use service::*;
+pub use shm::*;
+pub use surface::*;
+
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+
+pub type LogFn = extern "C" fn(s: *const u8, l: u32);
+pub type CallocFn = extern "C" fn(size: usize, align: usize) -> *mut u8;
+pub type CdallocFn = extern "C" fn(ptr: *mut u8, size: usize, align: usize);
+///Load an app of the boot image set by name, returns its pid or a negative value on failure
+pub type SpawnFn = extern "C" fn(name: *const u8, len: u32) -> i64;
+///Returns 0, or a negative value if there is no such running pid
+pub type KillFn = extern "C" fn(pid: u64) -> i32;
+///Fills up to `cap` entries, returns how many processes there are
+pub type PsFn = extern "C" fn(out: *mut ProcInfo, cap: usize) -> usize;
+///Fills `out` for a running pid and returns 0, or returns a negative value
+pub type MemFn = extern "C" fn(pid: u64, out: *mut MemInfo) -> i32;
+///Report a panic and abort the current call, only returns outside of a call (do not go on).
+///The kernel drops the app store: the next call starts from scratch
+pub type AbortFn = extern "C" fn(msg: *const u8, len: u32);
+///Join a named channel, creating it if needed. Returns its id (see `CHANNEL_BIT`),
+///or a negative value
+pub type OpenFn = extern "C" fn(name: *const u8, len: u32) -> i64;
+///Queue a copy of `data` in the inbox of a pid, or of every other member of a channel.
+///Returns how many inboxes got it, or one of the `IPC_*` errors
+pub type SendFn = extern "C" fn(to: u64, data: *const u8, len: u32) -> i32;
+///Pop the oldest message of the inbox: fills `msg` and copies its data to `data`.
+///Returns 1, 0 if the inbox is empty, or `IPC_TOO_SMALL` if `cap` is less than `msg.len`
+pub type RecvFn = extern "C" fn(msg: *mut Message, data: *mut u8, cap: u32) -> i32;
+///Create a zeroed shared memory region of `size` bytes and map it in `out`.
+///Returns 0, or one of the `SHM_*` errors
+pub type ShmCreateFn = extern "C" fn(name: *const u8, len: u32, size: usize, out: *mut Shm) -> i32;
+///Map an existing region by name in `out`, the caller holds it until `shm_release`
+pub type ShmMapFn = extern "C" fn(name: *const u8, len: u32, out: *mut Shm) -> i32;
+///Drop one hold of the caller on a region, freed once nobody holds it
+pub type ShmReleaseFn = extern "C" fn(id: u64) -> i32;
+///Offer `entry` to the other apps under `name` (like `font.render`), until the app exits.
+///The owner registering its name again replaces the entry and keeps the id, a name owned by
+///another app gives `SERVICE_EXISTS`. Returns 0, or one of the `SERVICE_*` errors
+pub type RegisterFn = extern "C" fn(name: *const u8, len: u32, entry: ServiceFn) -> i32;
+///Id of a registered service, or one of the `SERVICE_*` errors
+pub type LookupFn = extern "C" fn(name: *const u8, len: u32) -> i64;
+///Call a service by id, returns what it returned, or one of the `SERVICE_*` errors
+pub type CallFn =
+    extern "C" fn(id: u64, arg: *const u8, arg_len: u32, out: *mut u8, out_cap: u32) -> i32;
+///Replace the clipboard with `info.kind`, `w` and `h`, and `info.len` bytes at `data`.
+///Returns the new serial, or one of the `CLIP_*` errors
+pub type ClipSetFn = extern "C" fn(info: *const ClipInfo, data: *const u8) -> i64;
+///Fill `info` and copy the data to `data`. Returns 1, 0 if the clipboard is empty,
+///or `CLIP_TOO_SMALL` if `cap` is less than `info.len`
+pub type ClipGetFn = extern "C" fn(info: *mut ClipInfo, data: *mut u8, cap: usize) -> i32;
+///Bumped by every `clip_set`, cheap to poll to notice a change
+pub type ClipSerialFn = extern "C" fn() -> u64;
+///Fill `info` with the surface of the app
+pub type SurfaceGetFn = extern "C" fn(info: *mut SurfaceInfo);
+///Move, fade, relayer or resize the surface of the app. A new size applies from the next
+///call (the `fb` of this one stays valid) and starts transparent. If the kernel or the
+///quota of the app cannot hold it then, the surface keeps its size (see `surface_get`).
+///Returns 0, or one of the `SURFACE_*` errors
+pub type SurfaceSetFn = extern "C" fn(info: *const SurfaceInfo) -> i32;
+///Tell the kernel which `rects` of `fb` this call changed, only they are shown again.
+///Can be called several times per call. An app that does not call it during a call changed
+///all of its surface, calling it with `n` 0 tells that nothing changed
+pub type DamageFn = extern "C" fn(rects: *const Rect, n: u32);
+
+///Latest Context version, the one described by `Context`
+pub const CONTEXT_VERSION: u8 = 13;
+
+///Capability bits of `Context::caps`.
+///A bit is only set if the kernel actually provides the matching fields.
+pub mod caps {
+    ///Context version that brought the fields of each capability
+    pub const VERSIONS: &[(u64, u8)] = &[
+        (LOG, 0),
+        (FB, 0),
+        (ALLOC, 1),
+        (STORE, 1),
+        (INPUT, 1),
+        (PROC, 3),
+        (MEM, 4),
+        (ABORT, 5),
+        (IPC, 6),
+        (SHM, 7),
+        (SERVICE, 8),
+        (CLIPBOARD, 9),
+        (SURFACE, 10),
+        (DAMAGE, 11),
+        (FRAME, 12),
+        (DISPLAY, 13),
+    ];
+
+    ///Oldest Context version with the fields of every capability in `caps`
+    pub const fn version_of(caps: u64) -> u8 {
+        let mut version = 0;
+        let mut i = 0;
+        while i < VERSIONS.len() {
+            let (cap, since) = VERSIONS[i];
+            if caps & cap != 0 && since > version {
+                version = since;
+            }
+            i += 1;
+        }
+        version
+    }
+
+    pub const LOG: u64 = 1 << 0;
+    pub const FB: u64 = 1 << 1;
+    pub const ALLOC: u64 = 1 << 2;
+    pub const STORE: u64 = 1 << 3;
+    pub const INPUT: u64 = 1 << 4;
+    ///`spawn`, `kill` and `ps`
+    pub const PROC: u64 = 1 << 5;
+    ///`mem`
+    pub const MEM: u64 = 1 << 6;
+    ///`abort`
+    pub const ABORT: u64 = 1 << 7;
+    ///`open`, `send` and `recv`
+    pub const IPC: u64 = 1 << 8;
+    ///`shm_create`, `shm_map` and `shm_release`
+    pub const SHM: u64 = 1 << 9;
+    ///`register`, `lookup` and `call`
+    pub const SERVICE: u64 = 1 << 10;
+    ///`clip_set`, `clip_get` and `clip_serial`
+    pub const CLIPBOARD: u64 = 1 << 11;
+    ///`surface_get` and `surface_set`
+    pub const SURFACE: u64 = 1 << 12;
+    ///`damage`
+    pub const DAMAGE: u64 = 1 << 13;
+    ///`frame`
+    pub const FRAME: u64 = 1 << 14;
+    ///`display`
+    pub const DISPLAY: u64 = 1 << 15;
+}
+
+///Argument of every app call.
+///
+///Fields are append-only: an app built against an older Context must still find
+///its fields at the same offsets. See `legacy` for the oldest supported one.
+///An app built against a newer Context than the kernel provides must check `size`
+///(or `has`) before touching a field, see `context_size`.
+#[cfg(feature = "alloc")]
+#[repr(C)]
+pub struct Context<'a, T = ()> {
+    pub version: u8,
+    pub _reserved: [u8; 3],
+    ///Size in bytes of the Context given by the kernel, valid from version 2.
+    ///Lives in the padding after `version` so older layouts are unchanged.
+    pub size: u32,
+    pub start_time: u64,
+    pub log: LogFn,
+    pub pid: u64,
+    pub fb: FB<'a>,
+    pub calloc: CallocFn,
+    pub cdalloc: CdallocFn,
+    pub store: &'a mut Option<Box<T>>,
+    pub input: &'a Input,
+    //Version 2
+    ///See `caps`, only valid if `size` covers it, use `Context::caps`
+    pub caps: u64,
+    //Version 3
+    pub spawn: SpawnFn,
+    pub kill: KillFn,
+    pub ps: PsFn,
+    //Version 4
+    pub mem: MemFn,
+    //Version 5
+    pub abort: AbortFn,
+    //Version 6
+    pub open: OpenFn,
+    pub send: SendFn,
+    pub recv: RecvFn,
+    //Version 7
+    pub shm_create: ShmCreateFn,
+    pub shm_map: ShmMapFn,
+    pub shm_release: ShmReleaseFn,
+    //Version 8
+    pub register: RegisterFn,
+    pub lookup: LookupFn,
+    pub call: CallFn,
+    //Version 9
+    pub clip_set: ClipSetFn,
+    pub clip_get: ClipGetFn,
+    pub clip_serial: ClipSerialFn,
+    //Version 10
+    pub surface_get: SurfaceGetFn,
+    pub surface_set: SurfaceSetFn,
+    //Version 11
+    pub damage: DamageFn,
+    //Version 12
+    ///The apps are called once per frame, right after the previous one was presented
+    pub frame: &'a Frame,
+    //Version 13
+    pub display: &'a Display,
+}
+
+///Size of the Context of a given version.
+///Fields after it are not provided by the kernel, even if they physically exist.
+#[cfg(feature = "alloc")]
+pub const fn context_size(version: u8) -> usize {
+    use core::mem::{offset_of, size_of};
+    match version {
+        0 => offset_of!(Context, calloc),
+        1 => offset_of!(Context, caps),
+        2 => offset_of!(Context, spawn),
+        3 => offset_of!(Context, mem),
+        4 => offset_of!(Context, abort),
+        5 => offset_of!(Context, open),
+        6 => offset_of!(Context, shm_create),
+        7 => offset_of!(Context, register),
+        8 => offset_of!(Context, clip_set),
+        9 => offset_of!(Context, surface_get),
+        10 => offset_of!(Context, damage),
+        11 => offset_of!(Context, frame),
+        12 => offset_of!(Context, display),
+        _ => size_of::<Context>(),
+    }
+}
+
+///Capabilities implied by a Context version, for kernels that predate `caps`
+pub const fn version_caps(version: u8) -> u64 {
+    match version {
+        0 => caps::LOG | caps::FB,
+        _ => caps::LOG | caps::FB | caps::ALLOC | caps::STORE | caps::INPUT,
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, T> Context<'a, T> {
+    ///Size of the Context given by the kernel
+    pub fn size(&self) -> usize {
+        if self.version >= 2 {
+            self.size as usize
+        } else {
+            context_size(self.version)
+        }
+    }
+    ///Whether the field ending at byte `end` was given by the kernel
+    pub fn provides(&self, end: usize) -> bool {
+        end <= self.size()
+    }
+    pub fn caps(&self) -> u64 {
+        if self.provides(core::mem::offset_of!(Self, caps) + 8) {
+            self.caps
+        } else {
+            version_caps(self.version)
+        }
+    }
+    pub fn has(&self, cap: u64) -> bool {
+        self.caps() & cap == cap
+    }
+}
+
+pub mod legacy {
+    //! Contexts given to apps that predate the dynamic allocation age (`app_test`).
+    use super::*;
+
+    #[repr(C)]
+    pub struct Context<'a> {
+        pub version: u8,
+        pub start_time: u64,
+        pub log: LogFn,
+        pub pid: u64,
+        pub fb: FB<'a>,
+    }
+}
+
+#[allow(dead_code)]
+mod layout {
+    //! Compile time checks of the kernel/app boundary.
+    //! If one of these fails, the ABI changed: every app has to be rebuilt.
+    use super::*;
+    use core::mem::{align_of, offset_of, size_of};
+
+    const _: () = {
+        assert!(size_of::<RGBA>() == 4);
+        assert!(align_of::<RGBA>() == 1);
+
+        assert!(size_of::<FB>() == 32);
+        assert!(offset_of!(FB, pixels) == 0);
+        assert!(offset_of!(FB, w) == 16);
+        assert!(offset_of!(FB, h) == 24);
+
+        assert!(size_of::<KeyState>() == 1);
+        assert!(size_of::<InputEvent>() == 16);
+        assert!(offset_of!(InputEvent, trigger) == 0);
+        assert!(offset_of!(InputEvent, key) == 8);
+
+        assert!(size_of::<Input>() == 2072);
+        assert!(offset_of!(Input, mouse_x) == 0);
+        assert!(offset_of!(Input, mouse_y) == 8);
+        assert!(offset_of!(Input, keys) == 16);
+        assert!(offset_of!(Input, history_last_index) == 16 + KEY_COUNT);
+        assert!(offset_of!(Input, history_ring) == 24 + KEY_COUNT);
+
+        assert!(size_of::<legacy::Context>() == 64);
+        assert!(offset_of!(legacy::Context, version) == 0);
+        assert!(offset_of!(legacy::Context, start_time) == 8);
+        assert!(offset_of!(legacy::Context, log) == 16);
+        assert!(offset_of!(legacy::Context, pid) == 24);
+        assert!(offset_of!(legacy::Context, fb) == 32);
+    };
+
+    // Read by the kernel straight from the ELF section
+    const _: () = {
+        assert!(size_of::<Manifest>() == 88);
+        assert!(offset_of!(Manifest, magic) == 0);
+        assert!(offset_of!(Manifest, size) == 8);
+        assert!(offset_of!(Manifest, min_context_version) == 12);
+        assert!(offset_of!(Manifest, context_version) == 13);
+        assert!(offset_of!(Manifest, flags) == 16);
+        assert!(offset_of!(Manifest, window_w) == 20);
+        assert!(offset_of!(Manifest, window_h) == 24);
+        assert!(offset_of!(Manifest, layer) == 28);
+        assert!(offset_of!(Manifest, caps) == 32);
+        assert!(offset_of!(Manifest, name) == 40);
+        assert!(offset_of!(Manifest, version) == 72);
+    };
+
+    const _: () = {
+        assert!(size_of::<ProcInfo>() == 64);
+        assert!(offset_of!(ProcInfo, pid) == 0);
+        assert!(offset_of!(ProcInfo, start_time) == 8);
+        assert!(offset_of!(ProcInfo, calls) == 16);
+        assert!(offset_of!(ProcInfo, exit_status) == 24);
+        assert!(offset_of!(ProcInfo, state) == 28);
+        assert!(offset_of!(ProcInfo, flags) == 29);
+        assert!(offset_of!(ProcInfo, name) == 32);
+        assert!(size_of::<MemInfo>() == 48);
+        assert!(size_of::<Message>() == 24);
+        assert!(offset_of!(Message, from) == 0);
+        assert!(offset_of!(Message, channel) == 8);
+        assert!(offset_of!(Message, len) == 16);
+        assert!(size_of::<Shm>() == 24);
+        assert!(offset_of!(Shm, id) == 0);
+        assert!(offset_of!(Shm, ptr) == 8);
+        assert!(offset_of!(Shm, size) == 16);
+        assert!(size_of::<ClipInfo>() == 32);
+        assert!(offset_of!(ClipInfo, kind) == 0);
+        assert!(offset_of!(ClipInfo, w) == 4);
+        assert!(offset_of!(ClipInfo, h) == 8);
+        assert!(offset_of!(ClipInfo, len) == 16);
+        assert!(offset_of!(ClipInfo, serial) == 24);
+        assert!(size_of::<SurfaceInfo>() == 32);
+        assert!(offset_of!(SurfaceInfo, x) == 0);
+        assert!(offset_of!(SurfaceInfo, y) == 4);
+        assert!(offset_of!(SurfaceInfo, w) == 8);
+        assert!(offset_of!(SurfaceInfo, h) == 12);
+        assert!(offset_of!(SurfaceInfo, layer) == 16);
+        assert!(offset_of!(SurfaceInfo, z) == 20);
+        assert!(offset_of!(SurfaceInfo, opacity) == 24);
+        assert!(size_of::<Rect>() == 16);
+        assert!(offset_of!(Rect, x) == 0);
+        assert!(offset_of!(Rect, y) == 4);
+        assert!(offset_of!(Rect, w) == 8);
+        assert!(offset_of!(Rect, h) == 12);
+        assert!(size_of::<Frame>() == 24);
+        assert!(offset_of!(Frame, number) == 0);
+        assert!(offset_of!(Frame, presented_ms) == 8);
+        assert!(offset_of!(Frame, delta_ms) == 16);
+        assert!(offset_of!(Frame, refresh_hz) == 20);
+        assert!(size_of::<Display>() == 280);
+        assert!(offset_of!(Display, serial) == 0);
+        assert!(offset_of!(Display, w) == 8);
+        assert!(offset_of!(Display, h) == 12);
+        assert!(offset_of!(Display, count) == 16);
+        assert!(offset_of!(Display, screens) == 24);
+    };
+
+    // Return codes are part of the ABI too
+    const _: () = {
+        assert!(Ret::Continue.encode() == 0);
+        assert!(matches!(Ret::decode(-1), Ret::Exit(-1)));
+        assert!(matches!(Ret::decode(Ret::Exit(0).encode()), Ret::Exit(0)));
+        assert!(matches!(
+            Ret::decode(Ret::Sleep(16).encode()),
+            Ret::Sleep(16)
+        ));
+        assert!(matches!(
+            Ret::decode(Ret::WaitInput.encode()),
+            Ret::WaitInput
+        ));
+        assert!(matches!(
+            Ret::decode(Ret::WaitMessage.encode()),
+            Ret::WaitMessage
+        ));
+        assert!(matches!(Ret::decode(0x7f00_0000), Ret::Continue));
+    };
+
+    #[cfg(feature = "alloc")]
+    const _: () = {
+        assert!(size_of::<Context>() == 280);
+        // Old apps read the current Context through the legacy layout
+        assert!(offset_of!(Context, version) == offset_of!(legacy::Context, version));
+        assert!(offset_of!(Context, start_time) == offset_of!(legacy::Context, start_time));
+        assert!(offset_of!(Context, log) == offset_of!(legacy::Context, log));
+        assert!(offset_of!(Context, pid) == offset_of!(legacy::Context, pid));
+        assert!(offset_of!(Context, fb) == offset_of!(legacy::Context, fb));
+        assert!(offset_of!(Context, calloc) == 64);
+        assert!(offset_of!(Context, cdalloc) == 72);
+        assert!(offset_of!(Context, store) == 80);
+        assert!(offset_of!(Context, input) == 88);
+        assert!(offset_of!(Context, size) == 4);
+        assert!(offset_of!(Context, caps) == 96);
+        assert!(context_size(0) == size_of::<legacy::Context>());
+        assert!(context_size(1) == 96);
+        assert!(context_size(2) == 104);
+        assert!(offset_of!(Context, spawn) == 104);
+        assert!(offset_of!(Context, kill) == 112);
+        assert!(offset_of!(Context, ps) == 120);
+        assert!(context_size(3) == 128);
+        assert!(offset_of!(Context, mem) == 128);
+        assert!(context_size(4) == 136);
+        assert!(offset_of!(Context, abort) == 136);
+        assert!(context_size(5) == 144);
+        assert!(offset_of!(Context, open) == 144);
+        assert!(offset_of!(Context, send) == 152);
+        assert!(offset_of!(Context, recv) == 160);
+        assert!(context_size(6) == 168);
+        assert!(offset_of!(Context, shm_create) == 168);
+        assert!(offset_of!(Context, shm_map) == 176);
+        assert!(offset_of!(Context, shm_release) == 184);
+        assert!(context_size(7) == 192);
+        assert!(offset_of!(Context, register) == 192);
+        assert!(offset_of!(Context, lookup) == 200);
+        assert!(offset_of!(Context, call) == 208);
+        assert!(context_size(8) == 216);
+        assert!(offset_of!(Context, clip_set) == 216);
+        assert!(offset_of!(Context, clip_get) == 224);
+        assert!(offset_of!(Context, clip_serial) == 232);
+        assert!(context_size(9) == 240);
+        assert!(offset_of!(Context, surface_get) == 240);
+        assert!(offset_of!(Context, surface_set) == 248);
+        assert!(context_size(10) == 256);
+        assert!(offset_of!(Context, damage) == 256);
+        assert!(context_size(11) == 264);
+        assert!(offset_of!(Context, frame) == 264);
+        assert!(context_size(12) == 272);
+        assert!(offset_of!(Context, display) == 272);
+        assert!(context_size(CONTEXT_VERSION) == size_of::<Context>());
+        // The store type is app defined, it must not move anything
+        assert!(size_of::<Context<[u8; 4096]>>() == size_of::<Context>());
+    };
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use core::mem::{offset_of, size_of};
+
+    ///First Context field of each capability
+    const FIELDS: &[(u64, usize)] = &[
+        (caps::LOG, offset_of!(Context, log)),
+        (caps::FB, offset_of!(Context, fb)),
+        (caps::ALLOC, offset_of!(Context, calloc)),
+        (caps::STORE, offset_of!(Context, store)),
+        (caps::INPUT, offset_of!(Context, input)),
+        (caps::PROC, offset_of!(Context, spawn)),
+        (caps::MEM, offset_of!(Context, mem)),
+        (caps::ABORT, offset_of!(Context, abort)),
+        (caps::IPC, offset_of!(Context, open)),
+        (caps::SHM, offset_of!(Context, shm_create)),
+        (caps::SERVICE, offset_of!(Context, register)),
+        (caps::CLIPBOARD, offset_of!(Context, clip_set)),
+        (caps::SURFACE, offset_of!(Context, surface_get)),
+        (caps::DAMAGE, offset_of!(Context, damage)),
+        (caps::FRAME, offset_of!(Context, frame)),
+        (caps::DISPLAY, offset_of!(Context, display)),
+    ];
+
+    #[test]
+    fn sizes_grow_with_versions() {
+        for version in 1..=CONTEXT_VERSION {
+            assert!(context_size(version) > context_size(version - 1));
+        }
+        assert_eq!(context_size(0), size_of::<legacy::Context>());
+        assert_eq!(context_size(CONTEXT_VERSION), size_of::<Context>());
+        // Newer apps get what this crate knows
+        assert_eq!(context_size(CONTEXT_VERSION + 1), size_of::<Context>());
+        assert_eq!(context_size(u8::MAX), size_of::<Context>());
+    }
+
+    #[test]
+    fn caps_within_their_version() {
+        assert_eq!(FIELDS.len(), caps::VERSIONS.len());
+        for &(cap, since) in caps::VERSIONS {
+            assert!(since <= CONTEXT_VERSION);
+            let &(_, field) = FIELDS.iter().find(|&&(c, _)| c == cap).unwrap();
+            assert!(
+                field < context_size(since),
+                "cap {:#x} not in version {}",
+                cap,
+                since
+            );
+            if since > 0 {
+                assert!(
+                    field >= context_size(since - 1),
+                    "cap {:#x} already in version {}",
+                    cap,
+                    since - 1
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn version_of_caps() {
+        assert_eq!(caps::version_of(0), 0);
+        let mut all = 0;
+        for &(cap, since) in caps::VERSIONS {
+            assert_eq!(caps::version_of(cap), since);
+            assert_eq!(caps::version_of(cap | caps::LOG), since);
+            all |= cap;
+        }
+        let newest = caps::VERSIONS.iter().map(|&(_, since)| since).max();
+        assert_eq!(Some(caps::version_of(all)), newest);
+        // Bits no version knows about need nothing
+        assert_eq!(caps::version_of(1 << 63), 0);
+    }
+
+    #[test]
+    fn caps_of_old_versions() {
+        // Versions 0 and 1 had no `caps` field, they provide everything that existed then
+        for version in 0..=1 {
+            let existing = caps::VERSIONS
+                .iter()
+                .filter(|&&(_, since)| since <= version)
+                .fold(0, |all, &(cap, _)| all | cap);
+            assert_eq!(version_caps(version), existing);
+        }
+        assert!(offset_of!(Context, caps) >= context_size(1));
+    }
+}
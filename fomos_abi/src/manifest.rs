@@ -0,0 +1,264 @@
+use crate::{caps, CONTEXT_VERSION, LAYER_NORMAL};
+
+///Name of the ELF section holding the app `Manifest`
+pub const MANIFEST_SECTION: &str = ".fomos_manifest";
+pub const MANIFEST_MAGIC: [u8; 8] = *b"FOMOSMF1";
+
+pub const MANIFEST_AUTOSTART: u32 = 1 << 0;
+
+///Describes an app to the kernel, embedded in the app ELF by `manifest!`.
+///
+///Plain bytes only (no pointers), so it can be read without relocating the image.
+///Fields are append-only, `size` tells which ones the app knew about.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct Manifest {
+    pub magic: [u8; 8],
+    pub size: u32,
+    ///Oldest Context version the app can run with, by default the oldest with the fields of
+    ///its `caps`: it checks `Context::has` for the others
+    pub min_context_version: u8,
+    ///Context version the app was built against
+    pub context_version: u8,
+    pub _reserved: [u8; 2],
+    ///See `MANIFEST_AUTOSTART`
+    pub flags: u32,
+    ///Preferred window size, 0 if the app has no preference
+    pub window_w: u32,
+    pub window_h: u32,
+    ///Layer of the app surface, see `LAYER_*`
+    pub layer: u32,
+    ///Capabilities the app wants, see `caps`
+    pub caps: u64,
+    ///Nul padded utf8
+    pub name: [u8; 32],
+    ///Nul padded utf8
+    pub version: [u8; 16],
+}
+
+pub(crate) const fn copy_str<const N: usize>(s: &str) -> [u8; N] {
+    let bytes = s.as_bytes();
+    let mut out = [0; N];
+    let mut i = 0;
+    while i < bytes.len() && i < N {
+        out[i] = bytes[i];
+        i += 1;
+    }
+    out
+}
+
+pub(crate) fn read_str(bytes: &[u8]) -> &str {
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    core::str::from_utf8(&bytes[..len]).unwrap_or("")
+}
+
+impl Manifest {
+    pub const fn new(name: &str) -> Self {
+        Self {
+            magic: MANIFEST_MAGIC,
+            size: core::mem::size_of::<Manifest>() as u32,
+            min_context_version: 0,
+            context_version: CONTEXT_VERSION,
+            _reserved: [0; 2],
+            flags: 0,
+            window_w: 0,
+            window_h: 0,
+            layer: LAYER_NORMAL,
+            caps: 0,
+            name: copy_str(name),
+            version: [0; 16],
+        }
+    }
+    pub const fn version(mut self, version: &str) -> Self {
+        self.version = copy_str(version);
+        self
+    }
+    ///Never below what the `caps` need
+    pub const fn min_context_version(mut self, version: u8) -> Self {
+        let needed = caps::version_of(self.caps);
+        self.min_context_version = if version > needed { version } else { needed };
+        self
+    }
+    ///Also raises `min_context_version` to what these capabilities need
+    pub const fn caps(mut self, caps: u64) -> Self {
+        self.caps = caps;
+        let needed = caps::version_of(caps);
+        if needed > self.min_context_version {
+            self.min_context_version = needed;
+        }
+        self
+    }
+    pub const fn window(mut self, w: u32, h: u32) -> Self {
+        self.window_w = w;
+        self.window_h = h;
+        self
+    }
+    pub const fn layer(mut self, layer: u32) -> Self {
+        self.layer = layer;
+        self
+    }
+    pub const fn autostart(mut self, autostart: bool) -> Self {
+        if autostart {
+            self.flags |= MANIFEST_AUTOSTART;
+        } else {
+            self.flags &= !MANIFEST_AUTOSTART;
+        }
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        read_str(&self.name)
+    }
+    pub fn version_str(&self) -> &str {
+        read_str(&self.version)
+    }
+    pub fn is_autostart(&self) -> bool {
+        self.flags & MANIFEST_AUTOSTART != 0
+    }
+
+    ///Parse the content of the manifest section.
+    ///Fields unknown to the app that built it keep the `Manifest::new` defaults, except
+    ///`context_version` which is 0. Unknown trailing fields are ignored.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Manifest> {
+        const HEADER: usize = 12;
+        if bytes.len() < HEADER || bytes[..8] != MANIFEST_MAGIC {
+            return None;
+        }
+        let size = u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]) as usize;
+        if size < HEADER || size > bytes.len() {
+            return None;
+        }
+        let mut manifest = Manifest {
+            size: 0,
+            context_version: 0,
+            ..Manifest::new("")
+        };
+        let len = size.min(core::mem::size_of::<Manifest>());
+        // Safety: Manifest is plain old data, any byte pattern is valid
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                bytes.as_ptr(),
+                &mut manifest as *mut Manifest as *mut u8,
+                len,
+            );
+        }
+        Some(manifest)
+    }
+}
+
+///Embed a `Manifest` in the app ELF.
+///
+///```ignore
+///fomos_abi::manifest!(Manifest::new("app_cursor").version("0.1.0"));
+///```
+///The app needs `#![feature(used_with_arg)]`, so the linker keeps the unreferenced section.
+#[macro_export]
+macro_rules! manifest {
+    ($manifest:expr) => {
+        #[used(linker)]
+        #[link_section = ".fomos_manifest"]
+        static FOMOS_MANIFEST: $crate::Manifest = $manifest;
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::caps;
+    use core::mem::{offset_of, size_of};
+
+    const SIZE: usize = size_of::<Manifest>();
+
+    ///`manifest` claiming `size` bytes, as it lies in the ELF section, followed by 8 bytes of
+    ///fields it does not know about
+    fn section(manifest: &Manifest, size: usize) -> [u8; SIZE + 8] {
+        let mut bytes = [0xaa; SIZE + 8];
+        let manifest = Manifest {
+            size: size as u32,
+            ..*manifest
+        };
+        // Safety: Manifest is plain old data
+        let raw =
+            unsafe { core::slice::from_raw_parts(&manifest as *const Manifest as *const u8, SIZE) };
+        bytes[..SIZE].copy_from_slice(raw);
+        bytes
+    }
+
+    fn full() -> Manifest {
+        Manifest::new("app_test")
+            .version("1.2.3")
+            .window(640, 480)
+            .autostart(true)
+    }
+
+    #[test]
+    fn round_trip() {
+        let bytes = section(&full(), SIZE);
+        let manifest = Manifest::from_bytes(&bytes[..SIZE]).unwrap();
+        assert_eq!(manifest.name(), "app_test");
+        assert_eq!(manifest.version_str(), "1.2.3");
+        assert_eq!((manifest.window_w, manifest.window_h), (640, 480));
+        assert!(manifest.is_autostart());
+        assert_eq!(manifest.context_version, CONTEXT_VERSION);
+    }
+
+    #[test]
+    fn bad_magic() {
+        let mut bytes = section(&full(), SIZE);
+        bytes[7] = b'0';
+        assert!(Manifest::from_bytes(&bytes).is_none());
+    }
+
+    #[test]
+    fn bad_size() {
+        let bytes = section(&full(), 11);
+        assert!(Manifest::from_bytes(&bytes).is_none());
+        let bytes = section(&full(), SIZE);
+        assert!(Manifest::from_bytes(&bytes[..SIZE - 1]).is_none());
+        assert!(Manifest::from_bytes(&bytes[..8]).is_none());
+        assert!(Manifest::from_bytes(&[]).is_none());
+    }
+
+    #[test]
+    fn older_keeps_defaults() {
+        let size = offset_of!(Manifest, window_w);
+        let bytes = section(&full(), size);
+        let manifest = Manifest::from_bytes(&bytes[..size]).unwrap();
+        assert_eq!(manifest.size as usize, size);
+        assert!(manifest.is_autostart());
+        assert_eq!((manifest.window_w, manifest.window_h), (0, 0));
+        assert_eq!(manifest.caps, 0);
+        assert_eq!(manifest.name(), "");
+        assert_eq!(manifest.version_str(), "");
+
+        let bytes = section(&full(), 12);
+        let manifest = Manifest::from_bytes(&bytes[..12]).unwrap();
+        assert_eq!(manifest.context_version, 0);
+        assert_eq!(manifest.min_context_version, 0);
+        assert!(!manifest.is_autostart());
+    }
+
+    #[test]
+    fn newer_ignores_extra_fields() {
+        let bytes = section(&full(), SIZE + 8);
+        let manifest = Manifest::from_bytes(&bytes).unwrap();
+        assert_eq!(manifest.size as usize, SIZE + 8);
+        assert_eq!(manifest.name(), "app_test");
+        assert_eq!(manifest.version_str(), "1.2.3");
+        assert!(manifest.is_autostart());
+    }
+
+    #[test]
+    fn caps_raise_min_version() {
+        let manifest = Manifest::new("app").caps(caps::LOG | caps::ALLOC);
+        assert_eq!(manifest.min_context_version, caps::version_of(caps::ALLOC));
+        let manifest = manifest.min_context_version(0);
+        assert_eq!(manifest.min_context_version, caps::version_of(caps::ALLOC));
+        let manifest = Manifest::new("app")
+            .min_context_version(3)
+            .caps(caps::INPUT);
+        assert_eq!(manifest.min_context_version, 3);
+        let manifest = Manifest::new("app").caps(caps::LOG | caps::FB);
+        assert_eq!(manifest.min_context_version, 0);
+    }
+}
@@ -0,0 +1,79 @@
+use crate::manifest::{copy_str, read_str};
+
+///Status of a process killed through `Context::kill`
+pub const EXIT_KILLED: i32 = -9;
+///Status of a process stopped by a fault in its code
+pub const EXIT_FAULT: i32 = -11;
+///Status of a process that kept panicking, see `Context::abort`
+pub const EXIT_PANIC: i32 = -6;
+///Status of a process aborted by the watchdog, for running past its time budget
+pub const EXIT_TIMEOUT: i32 = -14;
+
+///`ProcInfo::flags`: a call of the process ran past its time budget
+pub const PROC_OVER_BUDGET: u8 = 1 << 0;
+
+///`ProcInfo::state`, a plain u8: newer kernels may add states
+pub const PROC_READY: u8 = 0;
+pub const PROC_SLEEPING: u8 = 1;
+pub const PROC_WAITING_INPUT: u8 = 2;
+pub const PROC_WAITING_MESSAGE: u8 = 3;
+pub const PROC_EXITED: u8 = 4;
+///Stopped by the kernel, see `exit_status`
+pub const PROC_CRASHED: u8 = 5;
+
+///One entry of the process table, as listed by `Context::ps`
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct ProcInfo {
+    pub pid: u64,
+    ///Kernel time of the load, in ms
+    pub start_time: u64,
+    ///Number of `_start` calls so far
+    pub calls: u64,
+    ///Only meaningful once `PROC_EXITED` or `PROC_CRASHED`
+    pub exit_status: i32,
+    ///One of the `PROC_*` states
+    pub state: u8,
+    ///See `PROC_OVER_BUDGET`
+    pub flags: u8,
+    pub _reserved: [u8; 2],
+    ///Nul padded utf8
+    pub name: [u8; 32],
+}
+
+impl ProcInfo {
+    pub const fn new(pid: u64, name: &str) -> Self {
+        Self {
+            pid,
+            start_time: 0,
+            calls: 0,
+            exit_status: 0,
+            state: PROC_READY,
+            flags: 0,
+            _reserved: [0; 2],
+            name: copy_str(name),
+        }
+    }
+    pub fn name(&self) -> &str {
+        read_str(&self.name)
+    }
+    pub fn is_alive(&self) -> bool {
+        !matches!(self.state, PROC_EXITED | PROC_CRASHED)
+    }
+}
+
+///Memory accounting of one process, as given by `Context::mem`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MemInfo {
+    ///Bytes allocated through `calloc` and not freed yet
+    pub live: u64,
+    ///Highest `live` so far
+    pub peak: u64,
+    pub allocs: u64,
+    pub frees: u64,
+    ///Allocations refused, by the quota or an exhausted heap
+    pub failed: u64,
+    ///Maximum `live`, 0 if unlimited
+    pub quota: u64,
+}
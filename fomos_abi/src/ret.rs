@@ -0,0 +1,104 @@
+///What an app asks the kernel for, when returning from `_start`.
+///
+///Encoded in the `i32` returned by the app:
+/// - `0` keeps running, so does any unknown code (forward compatibility),
+/// - a negative value exits with that status (legacy error codes),
+/// - otherwise the high byte is the request, the low 24 bits its argument.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ret {
+    ///Call me again on the next kernel loop
+    Continue,
+    ///Never call me again. Positive statuses are truncated to 24 bits
+    Exit(i32),
+    ///Call me again in at least that many ms (at most 24 bits)
+    Sleep(u32),
+    ///Call me again once a new input arrived (key event or mouse move)
+    WaitInput,
+    ///Call me again once a message arrived in my inbox
+    WaitMessage,
+}
+
+const TAG_SHIFT: u32 = 24;
+const ARG_MASK: u32 = (1 << TAG_SHIFT) - 1;
+const TAG_EXIT: u32 = 1;
+const TAG_SLEEP: u32 = 2;
+const TAG_WAIT_INPUT: u32 = 3;
+const TAG_WAIT_MESSAGE: u32 = 4;
+
+impl Ret {
+    pub const fn encode(self) -> i32 {
+        let (tag, arg) = match self {
+            Ret::Continue => return 0,
+            Ret::Exit(status) if status < 0 => return status,
+            Ret::Exit(status) => (TAG_EXIT, status as u32),
+            Ret::Sleep(ms) => (TAG_SLEEP, if ms > ARG_MASK { ARG_MASK } else { ms }),
+            Ret::WaitInput => (TAG_WAIT_INPUT, 0),
+            Ret::WaitMessage => (TAG_WAIT_MESSAGE, 0),
+        };
+        ((tag << TAG_SHIFT) | (arg & ARG_MASK)) as i32
+    }
+    pub const fn decode(code: i32) -> Ret {
+        if code < 0 {
+            return Ret::Exit(code);
+        }
+        let arg = code as u32 & ARG_MASK;
+        match code as u32 >> TAG_SHIFT {
+            TAG_EXIT => Ret::Exit(arg as i32),
+            TAG_SLEEP => Ret::Sleep(arg),
+            TAG_WAIT_INPUT => Ret::WaitInput,
+            TAG_WAIT_MESSAGE => Ret::WaitMessage,
+            _ => Ret::Continue,
+        }
+    }
+}
+
+impl From<Ret> for i32 {
+    fn from(ret: Ret) -> i32 {
+        ret.encode()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for ret in [
+            Ret::Continue,
+            Ret::Exit(0),
+            Ret::Exit(3),
+            Ret::Exit(-1),
+            Ret::Exit(i32::MIN),
+            Ret::Sleep(0),
+            Ret::Sleep(16),
+            Ret::Sleep(ARG_MASK),
+            Ret::WaitInput,
+            Ret::WaitMessage,
+        ] {
+            assert_eq!(Ret::decode(ret.encode()), ret);
+        }
+    }
+
+    #[test]
+    fn truncates_arguments() {
+        assert_eq!(
+            Ret::decode(Ret::Sleep(u32::MAX).encode()),
+            Ret::Sleep(ARG_MASK)
+        );
+        assert_eq!(Ret::decode(Ret::Exit(0x0100_0005).encode()), Ret::Exit(5));
+    }
+
+    #[test]
+    fn legacy_codes() {
+        assert_eq!(Ret::decode(0), Ret::Continue);
+        assert_eq!(Ret::decode(-12), Ret::Exit(-12));
+        assert_eq!(i32::from(Ret::Exit(-12)), -12);
+    }
+
+    #[test]
+    fn unknown_tags_continue() {
+        assert_eq!(Ret::decode(0x7f00_0000), Ret::Continue);
+        assert_eq!(Ret::decode((5 << TAG_SHIFT) | 42), Ret::Continue);
+    }
+}
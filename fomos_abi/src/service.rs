@@ -0,0 +1,18 @@
+///Entry point of a service, run synchronously on behalf of the app calling it.
+///Reads `arg_len` bytes at `arg`, writes at most `out_cap` bytes at `out`.
+///The result is passed through to the caller, values from `SERVICE_GONE` up to
+///`SERVICE_BAD_ENTRY` are reserved for the kernel.
+///What it allocates and does not free belongs to the caller once it returns: the service
+///keeps its own state in memory allocated during the calls of its app
+pub type ServiceFn = extern "C" fn(arg: *const u8, arg_len: u32, out: *mut u8, out_cap: u32) -> i32;
+
+///No such service, or its app exited since the lookup
+pub const SERVICE_GONE: i32 = i32::MIN;
+///The service crashed or panicked during the call, it is unregistered
+pub const SERVICE_FAULT: i32 = i32::MIN + 1;
+///`register`: the name belongs to a service of another app
+pub const SERVICE_EXISTS: i32 = i32::MIN + 2;
+///Name empty, too long or not utf8
+pub const SERVICE_BAD_NAME: i32 = i32::MIN + 3;
+///`register`: the entry point is not in the code of the app registering it
+pub const SERVICE_BAD_ENTRY: i32 = i32::MIN + 4;
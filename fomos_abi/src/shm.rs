@@ -0,0 +1,33 @@
+///Largest shared memory region, in bytes
+pub const SHM_MAX_SIZE: usize = 64 * 1024 * 1024;
+
+///`shm_create`: a region of that name already exists
+pub const SHM_EXISTS: i32 = -1;
+///`shm_map`: no region of that name, `shm_release`: not held by the caller
+pub const SHM_NOT_FOUND: i32 = -2;
+///Name empty, too long or not utf8
+pub const SHM_BAD_NAME: i32 = -3;
+///Size 0 or larger than `SHM_MAX_SIZE`
+pub const SHM_BAD_SIZE: i32 = -4;
+///The kernel heap is exhausted, or the region would put its creator past its memory quota
+pub const SHM_NO_MEMORY: i32 = -5;
+
+///A shared memory region as mapped by an app, filled by `Context::shm_create` and `shm_map`.
+///The memory stays valid until the app releases its hold on it, or exits
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct Shm {
+    pub id: u64,
+    pub ptr: *mut u8,
+    pub size: u64,
+}
+
+impl Default for Shm {
+    fn default() -> Self {
+        Self {
+            id: 0,
+            ptr: core::ptr::null_mut(),
+            size: 0,
+        }
+    }
+}
@@ -0,0 +1,39 @@
+///`SurfaceInfo::layer`: stacked like windows, the kernel raises the one clicked
+pub const LAYER_NORMAL: u32 = 0;
+///Below every normal surface (wallpapers)
+pub const LAYER_BACKGROUND: u32 = 1;
+///Above every normal surface (cursors, notifications)
+pub const LAYER_OVERLAY: u32 = 2;
+
+///Largest surface side, in pixels
+pub const SURFACE_MAX_SIDE: u32 = 8192;
+///Largest surface area, in screens: `w * h` at most this many times the size of the desktop
+pub const SURFACE_MAX_SCREENS: u32 = 2;
+
+///`w` or `h` is 0 or larger than `SURFACE_MAX_SIDE`
+pub const SURFACE_BAD_SIZE: i32 = -1;
+///Unknown `layer`
+pub const SURFACE_BAD_LAYER: i32 = -2;
+///`w * h` larger than `SURFACE_MAX_SCREENS` desktops
+pub const SURFACE_NO_MEMORY: i32 = -3;
+
+///Where and how the surface of an app (its `Context::fb`) is shown.
+///
+///Pixels are blended by their `a`: 0 is transparent, 255 is opaque.
+///A surface starts transparent, the size of the screen, at 0, 0.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SurfaceInfo {
+    ///Top left corner on screen, can be partly off screen
+    pub x: i32,
+    pub y: i32,
+    pub w: u32,
+    pub h: u32,
+    ///See `LAYER_*`
+    pub layer: u32,
+    ///Rank in the stack, 0 at the bottom. Decided by the kernel, ignored by `surface_set`
+    pub z: u32,
+    ///Multiplies the alpha of every pixel, 255 shows them as drawn
+    pub opacity: u8,
+    pub _reserved: [u8; 7],
+}
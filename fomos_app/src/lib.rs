@@ -0,0 +1,76 @@
+//! Runtime of the Rust apps: everything `_start` used to repeat before getting to the app.
+//!
+//! ```ignore
+//! use fomos_app::*;
+//!
+//! struct Counter(u64);
+//!
+//! impl App for Counter {
+//!     fn init(_ctx: &mut Context<Self>) -> Self {
+//!         Counter(0)
+//!     }
+//!     fn update(&mut self, ctx: &mut Context<Self>) -> Ret {
+//!         self.0 += 1;
+//!         if ctx.input.button(MouseButton::Left).pressed() {
+//!             log!("click {}", self.0);
+//!         }
+//!         Ret::Continue
+//!     }
+//! }
+//!
+//! entry!(Counter);
+//! ```
+//!
+//! Linking this crate provides the global allocator and the panic handler of the app.
+#![no_std]
+
+extern crate alloc;
+
+mod rt;
+
+pub use fomos_abi::*;
+pub use rt::{log, setup};
+
+#[doc(hidden)]
+pub use alloc::format as __format;
+
+use alloc::boxed::Box;
+
+///An app and its state, kept by the kernel between calls (`Context::store`)
+pub trait App: Sized {
+    ///First call, or first call after the app state was dropped by an `abort`
+    fn init(ctx: &mut Context<Self>) -> Self;
+    ///Every call. `ctx.store` is empty meanwhile, the state is `self`
+    fn update(&mut self, ctx: &mut Context<Self>) -> Ret;
+}
+
+///Body of the `_start` generated by `entry!`
+pub fn run<A: App>(ctx: &mut Context<A>) -> i32 {
+    setup(ctx);
+    let mut app = match ctx.store.take() {
+        Some(app) => app,
+        None => Box::new(A::init(ctx)),
+    };
+    let ret = app.update(ctx);
+    *ctx.store = Some(app);
+    ret.encode()
+}
+
+///Defines `_start` for an `App`
+#[macro_export]
+macro_rules! entry {
+    ($app:ty) => {
+        #[no_mangle]
+        pub extern "C" fn _start(ctx: &mut $crate::Context<$app>) -> i32 {
+            $crate::run(ctx)
+        }
+    };
+}
+
+///`format!` to the kernel log
+#[macro_export]
+macro_rules! log {
+    ($($arg:tt)*) => {
+        $crate::log(&$crate::__format!($($arg)*))
+    };
+}
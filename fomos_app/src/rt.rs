@@ -0,0 +1,89 @@
+//! Allocator, logger and panic handler, pointed at the Context of the current call.
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    fmt::Write,
+};
+
+use fomos_abi::{caps, AbortFn, CallocFn, CdallocFn, Context, LogFn};
+
+extern "C" fn nop(_s: *const u8, _l: u32) {}
+extern "C" fn a_init(_size: usize, _align: usize) -> *mut u8 {
+    panic!("allocation before setup")
+}
+extern "C" fn d_init(_ptr: *mut u8, _size: usize, _align: usize) {
+    panic!("deallocation before setup")
+}
+
+struct AllocFromCtx {
+    a: CallocFn,
+    d: CdallocFn,
+}
+
+unsafe impl GlobalAlloc for AllocFromCtx {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        (self.a)(layout.size(), layout.align())
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        (self.d)(ptr, layout.size(), layout.align());
+    }
+}
+
+#[global_allocator]
+static mut ALLOCATOR: AllocFromCtx = AllocFromCtx {
+    a: a_init,
+    d: d_init,
+};
+static mut LOGGER: LogFn = nop;
+///Lets the panic handler hand control back to the kernel, if it supports it
+static mut ABORT: Option<AbortFn> = None;
+
+///Point the allocator, logger and panic handler at this Context.
+///Done by `entry!` on every call, the kernel may give different functions each time
+pub fn setup<T>(ctx: &Context<T>) {
+    unsafe {
+        ALLOCATOR.a = ctx.calloc;
+        ALLOCATOR.d = ctx.cdalloc;
+        LOGGER = ctx.log;
+        ABORT = ctx.has(caps::ABORT).then_some(ctx.abort);
+    }
+}
+
+pub fn log(s: &str) {
+    unsafe { LOGGER(s.as_ptr(), s.len() as u32) }
+}
+
+///Fixed size text, cut at the last char that fits.
+///The panic handler formats into it as it cannot allocate: the panic may come from the allocator
+struct StackStr {
+    buf: [u8; 512],
+    len: usize,
+}
+
+impl Write for StackStr {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let mut n = s.len().min(self.buf.len() - self.len);
+        while !s.is_char_boundary(n) {
+            n -= 1;
+        }
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+        Ok(())
+    }
+}
+
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    log("panic");
+    let mut s = StackStr {
+        buf: [0; 512],
+        len: 0,
+    };
+    let _ = write!(s, "{:?}", info);
+    let s = &s.buf[..s.len];
+    unsafe { LOGGER(s.as_ptr(), s.len() as u32) };
+    if let Some(abort) = unsafe { ABORT } {
+        abort(s.as_ptr(), s.len() as u32);
+    }
+    loop {}
+}
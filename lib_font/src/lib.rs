@@ -0,0 +1,54 @@
+//! Console font for the apps: noto sans mono, regular, 16 px high.
+//!
+//! A shared library (`cdylib`) with a C interface: apps link it (`#[link(name = "lib_font")]`)
+//! and the kernel binds them to the one copy it loaded.
+#![no_std]
+
+use fomos_abi::exports::imports::fomos_abort;
+use noto_sans_mono_bitmap::{get_raster, get_raster_width, FontWeight, RasterHeight};
+
+const WEIGHT: FontWeight = FontWeight::Regular;
+const HEIGHT: RasterHeight = RasterHeight::Size16;
+///Drawn for chars the font does not have
+const BACKUP_CHAR: char = '�';
+
+///Width of every glyph, in pixels, the font is monospace
+#[no_mangle]
+pub extern "C" fn font_width() -> u32 {
+    get_raster_width(WEIGHT, HEIGHT) as u32
+}
+
+///Height of every glyph, in pixels
+#[no_mangle]
+pub extern "C" fn font_height() -> u32 {
+    HEIGHT.val() as u32
+}
+
+///Write the glyph of the char `c` in `out`, `font_width() * font_height()` intensities
+///row by row. Returns 0, or -1 if `cap` is too small
+#[no_mangle]
+pub extern "C" fn font_glyph(c: u32, out: *mut u8, cap: usize) -> i32 {
+    let c = char::from_u32(c).unwrap_or(BACKUP_CHAR);
+    let Some(raster) =
+        get_raster(c, WEIGHT, HEIGHT).or_else(|| get_raster(BACKUP_CHAR, WEIGHT, HEIGHT))
+    else {
+        return -1;
+    };
+    let w = font_width() as usize;
+    if out.is_null() || cap < w * font_height() as usize {
+        return -1;
+    }
+    let out = unsafe { core::slice::from_raw_parts_mut(out, cap) };
+    for (row, dst) in raster.raster().iter().zip(out.chunks_exact_mut(w)) {
+        dst.copy_from_slice(&row[..w]);
+    }
+    0
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    // Runs on behalf of the app calling the font, the kernel aborts that call
+    let msg = "lib_font panicked";
+    unsafe { fomos_abort(msg.as_ptr(), msg.len() as u32) };
+    loop {}
+}